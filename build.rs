@@ -0,0 +1,82 @@
+//! Lists the puzzle files and packs in `assets/puzzles` for the web build,
+//! which has them compiled in since a browser has no file system to read
+//! them from. The list is written to `embedded_puzzles.rs` in the build's
+//! output folder, and `puzzle_files` includes it.
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use nurikabe_core::format::{PACK_MANIFEST, PUZZLE_EXTENSIONS};
+
+const PUZZLE_DIR: &str = "assets/puzzles";
+
+fn main() -> io::Result<()> {
+    println!("cargo:rerun-if-changed={}", PUZZLE_DIR);
+    let root = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join(PUZZLE_DIR);
+
+    let mut puzzles = Vec::new();
+    let mut packs = Vec::new();
+    for path in sorted_entries(&root)? {
+        let name = file_name(&path);
+        if path.is_file() && is_puzzle(&path) {
+            puzzles.push((name, path));
+        } else if path.join(PACK_MANIFEST).is_file() {
+            // the puzzles of a pack are read from its folder, like on the
+            // desktop
+            for puzzle in sorted_entries(&path)? {
+                if puzzle.is_file() && is_puzzle(&puzzle) {
+                    puzzles.push((format!("{}/{}", name, file_name(&puzzle)), puzzle));
+                }
+            }
+            packs.push((name, path.join(PACK_MANIFEST)));
+        }
+    }
+
+    let mut out = String::new();
+    out.push_str(
+        "/// The puzzle files built into the web build, as paths inside\n\
+         /// `assets/puzzles` and contents.\n",
+    );
+    out.push_str(&list("EMBEDDED_PUZZLES", &puzzles));
+    out.push_str(
+        "/// The folders in `assets/puzzles` holding a pack, with the text of\n\
+         /// each pack's manifest.\n",
+    );
+    out.push_str(&list("EMBEDDED_PACKS", &packs));
+    let out_path = Path::new(&env::var("OUT_DIR").unwrap()).join("embedded_puzzles.rs");
+    fs::write(out_path, out)
+}
+
+/// The files and folders directly inside `dir`, sorted by name.
+fn sorted_entries(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<_>>()?;
+    entries.sort();
+    Ok(entries)
+}
+
+/// Whether the file at `path` is a puzzle, by the same test the game uses.
+fn is_puzzle(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name != PACK_MANIFEST)
+        && path
+            .extension()
+            .is_some_and(|extension| PUZZLE_EXTENSIONS.iter().any(|ext| extension == *ext))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap().to_string_lossy().into_owned()
+}
+
+/// The code for a constant `name` pairing each name in `files` with the
+/// contents of its file.
+fn list(name: &str, files: &[(String, PathBuf)]) -> String {
+    let mut list = format!("const {}: &[(&str, &str)] = &[\n", name);
+    for (file, path) in files {
+        list.push_str(&format!("    ({:?}, include_str!({:?})),\n", file, path));
+    }
+    list.push_str("];\n");
+    list
+}
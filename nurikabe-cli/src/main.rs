@@ -0,0 +1,236 @@
+//! Works with puzzle files from the command line, without starting the game.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Instant,
+};
+
+use nurikabe_core::{
+    check::{self, Problem},
+    difficulty::{self, Difficulty},
+    format::{self, Format, PACK_MANIFEST, PUZZLE_EXTENSIONS},
+    generator,
+    grid::{Grid, GridSize},
+    solver,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+const USAGE: &str = "usage:
+  nurikabe-cli --solve <file>
+  nurikabe-cli --generate ROWSxCOLS [--difficulty easy|medium|hard] [--seed N]
+               [--format legacy|janko|puzzlink|code|ascii|json] [--output <file>]
+  nurikabe-cli --validate <dir>
+  nurikabe-cli --convert <in> <out> [--format legacy|janko|puzzlink|code|ascii|json]";
+/// How many puzzles `--generate` makes before giving up on finding one of the
+/// difficulty asked for.
+const MAX_GENERATE_ATTEMPTS: usize = 1000;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.as_slice() {
+        [flag, file] if flag == "--solve" => solve(Path::new(file)),
+        [flag, size, options @ ..] if flag == "--generate" => generate(size, options),
+        [flag, dir] if flag == "--validate" => validate(Path::new(dir)),
+        [flag, input, output, options @ ..] if flag == "--convert" => {
+            convert(Path::new(input), Path::new(output), options)
+        }
+        _ => Err(USAGE.to_string()),
+    };
+    result.unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        ExitCode::from(2)
+    })
+}
+
+/// Reads the puzzle in the file at `path`, in whichever format it is in.
+fn read_grid(path: &Path) -> Result<Grid, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("can't read {:?}: {}", path, err))?;
+    format::parse(&text, Format::for_file(path, &text))
+        .map_err(|err| format!("can't read {:?}: {}", path, err))
+}
+
+/// Prints the solution to the puzzle at `path` and how long it took to find.
+fn solve(path: &Path) -> Result<ExitCode, String> {
+    let grid = read_grid(path)?;
+    let start = Instant::now();
+    let (solution, stats) = solver::solve_with_stats(&grid.clues_only());
+    let elapsed = start.elapsed();
+    let code = match solution {
+        Some(solution) => {
+            print!("{}", solution);
+            ExitCode::SUCCESS
+        }
+        None => {
+            println!("no solution");
+            ExitCode::FAILURE
+        }
+    };
+    println!(
+        "time: {:.3} ms, guesses: {}, backtracks: {}",
+        elapsed.as_secs_f64() * 1000.0,
+        stats.guesses,
+        stats.backtracks
+    );
+    Ok(code)
+}
+
+/// Writes a new puzzle with a single solution to standard output, or to the
+/// file given with `--output`.
+fn generate(size: &str, options: &[String]) -> Result<ExitCode, String> {
+    let grid_size = size
+        .split_once('x')
+        .and_then(|(rows, cols)| {
+            Some(GridSize {
+                rows: rows.parse().ok()?,
+                cols: cols.parse().ok()?,
+            })
+        })
+        .filter(|size| size.rows > 0 && size.cols > 0)
+        .ok_or_else(|| format!("{:?} isn't a size like 7x7", size))?;
+    let mut wanted = None;
+    let mut rng = StdRng::from_entropy();
+    let mut output_format = Format::Legacy;
+    let mut output = None;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let value = options
+            .next()
+            .ok_or_else(|| format!("{} needs a value\n{}", option, USAGE))?;
+        match option.as_str() {
+            "--difficulty" => {
+                let difficulty = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard]
+                    .into_iter()
+                    .find(|difficulty| difficulty.to_string().eq_ignore_ascii_case(value))
+                    .ok_or_else(|| format!("unknown difficulty {:?}", value))?;
+                wanted = Some(difficulty);
+            }
+            "--seed" => {
+                let seed = value
+                    .parse()
+                    .map_err(|_| format!("the seed {:?} isn't a number", value))?;
+                rng = StdRng::seed_from_u64(seed);
+            }
+            "--format" => {
+                output_format = value
+                    .parse()
+                    .map_err(|_| format!("unknown format {:?}", value))?;
+            }
+            "--output" => output = Some(value),
+            _ => return Err(format!("unknown option {}\n{}", option, USAGE)),
+        }
+    }
+
+    let mut generate = || {
+        generator::generate(grid_size, &mut rng).ok_or_else(|| {
+            format!(
+                "no puzzle of that size turned up; boards can't be a single row or column, or\nbigger than {0}x{0}",
+                generator::MAX_GENERATED_SIZE
+            )
+        })
+    };
+    let grid = match wanted {
+        None => generate()?,
+        Some(wanted) => (0..MAX_GENERATE_ATTEMPTS)
+            .map(|_| generate())
+            .find(|grid| match grid {
+                Ok(grid) => difficulty::rate(grid) == Some(wanted),
+                Err(_) => true,
+            })
+            .ok_or_else(|| {
+                format!(
+                    "no {} puzzle turned up in {} tries",
+                    wanted.to_string().to_lowercase(),
+                    MAX_GENERATE_ATTEMPTS
+                )
+            })??,
+    };
+    let text = format::write(&grid, output_format);
+    match output {
+        Some(path) => {
+            fs::write(path, text).map_err(|err| format!("can't write {:?}: {}", path, err))?
+        }
+        None => print!("{}", text),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Writes the puzzle at `input` to `output` in another format. The format is
+/// picked by the extension of `output` unless `--format` is given.
+fn convert(input: &Path, output: &Path, options: &[String]) -> Result<ExitCode, String> {
+    let output_format = match options {
+        [] => Format::for_extension(output).ok_or_else(|| {
+            format!(
+                "can't tell the format from {:?}, pick one with --format",
+                output
+            )
+        })?,
+        [flag, value] if flag == "--format" => value
+            .parse()
+            .map_err(|_| format!("unknown format {:?}", value))?,
+        _ => return Err(USAGE.to_string()),
+    };
+    let grid = read_grid(input)?;
+    fs::write(output, format::write(&grid, output_format))
+        .map_err(|err| format!("can't write {:?}: {}", output, err))?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Checks every puzzle file under `dir` and prints a line for each. Exits
+/// with 0 if every puzzle is fine, 1 if any can't be read, has clues that
+/// can't work or doesn't have exactly one solution, and 2 if the folder
+/// can't be read.
+fn validate(dir: &Path) -> Result<ExitCode, String> {
+    let mut files = Vec::new();
+    find_puzzle_files(dir, &mut files)?;
+    files.sort();
+    let mut bad = 0;
+    for path in &files {
+        let problems = match read_grid(path) {
+            Ok(grid) => check::problems(&grid)
+                .iter()
+                .map(Problem::to_string)
+                .collect(),
+            Err(err) => vec![err],
+        };
+        if problems.is_empty() {
+            println!("{}: ok", path.display());
+        } else {
+            bad += 1;
+            println!("{}: {}", path.display(), problems.join("; "));
+        }
+    }
+    println!(
+        "{} puzzles checked, {} ok, {} with problems",
+        files.len(),
+        files.len() - bad,
+        bad
+    );
+    Ok(if bad == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Collects the puzzle files in `dir` and the folders inside it.
+fn find_puzzle_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|err| format!("can't read {:?}: {}", dir, err))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|err| format!("can't read {:?}: {}", dir, err))?
+            .path();
+        if path.is_dir() {
+            find_puzzle_files(&path, files)?;
+        } else if path.file_name().is_some_and(|name| name != PACK_MANIFEST)
+            && path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| PUZZLE_EXTENSIONS.contains(&ext))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
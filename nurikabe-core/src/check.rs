@@ -0,0 +1,160 @@
+use core::fmt;
+
+use crate::{
+    difficulty::{self, Difficulty},
+    grid::{Grid, GridSize},
+    solver, CellState,
+};
+
+/// Something that keeps a puzzle from having exactly one solution.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Problem {
+    /// Two clues side by side, which would have to share an island.
+    TouchingClues((usize, usize), (usize, usize)),
+    NoClues,
+    /// The clues need more island cells than the board has.
+    TooManyCells {
+        total: usize,
+        cells: usize,
+    },
+    NoSolution,
+    ManySolutions,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::TouchingClues((row, col), (r, c)) => write!(
+                f,
+                "clues at ({}, {}) and ({}, {}) touch",
+                row + 1,
+                col + 1,
+                r + 1,
+                c + 1
+            ),
+            Problem::NoClues => write!(f, "there are no clues"),
+            Problem::TooManyCells { total, cells } => write!(
+                f,
+                "the clues add up to {} but there are only {} cells",
+                total, cells
+            ),
+            Problem::NoSolution => write!(f, "no solution"),
+            Problem::ManySolutions => write!(f, "more than one solution"),
+        }
+    }
+}
+
+/// What checking a puzzle turned up.
+pub struct Report {
+    pub problems: Vec<Problem>,
+    /// The rating of the puzzle, if it has exactly one solution.
+    pub difficulty: Option<Difficulty>,
+    /// The cells to point out to whoever wrote the puzzle: the clues at
+    /// fault, or the cells that two solutions disagree on.
+    pub cells: Vec<(usize, usize)>,
+}
+
+/// Everything wrong with a puzzle: clues that can never be part of a valid
+/// board, and then whether it has no solution or more than one.
+pub fn problems(grid: &Grid) -> Vec<Problem> {
+    let GridSize { rows, cols } = grid.grid_size;
+    let mut problems = Vec::new();
+    let mut total = 0;
+    for (row, col, state) in grid.iter() {
+        let CellState::Value(clue) = state else {
+            continue;
+        };
+        total += clue as usize;
+        // only looking down and right, so each pair is reported once
+        for (r, c, state) in grid.orthogonal_neighbors(row, col) {
+            if (r, c) > (row, col) && matches!(state, CellState::Value(_)) {
+                problems.push(Problem::TouchingClues((row, col), (r, c)));
+            }
+        }
+    }
+    if total == 0 {
+        problems.push(Problem::NoClues);
+    } else if total > rows * cols {
+        problems.push(Problem::TooManyCells {
+            total,
+            cells: rows * cols,
+        });
+    }
+    if !problems.is_empty() {
+        return problems;
+    }
+    match solver::count_solutions(grid, 2) {
+        0 => problems.push(Problem::NoSolution),
+        1 => {}
+        _ => problems.push(Problem::ManySolutions),
+    }
+    problems
+}
+
+/// Checks a puzzle like `problems`, also rating it when it is sound and
+/// finding the cells behind each problem when it isn't.
+pub fn check(grid: &Grid) -> Report {
+    let problems = problems(grid);
+    let mut cells = Vec::new();
+    for problem in &problems {
+        match *problem {
+            Problem::TouchingClues(first, second) => cells.extend([first, second]),
+            Problem::TooManyCells { .. } => cells.extend(clues(grid)),
+            Problem::NoSolution => cells.extend(blame_clues(grid)),
+            Problem::ManySolutions => {
+                if let [first, second] = &solver::solutions(grid, 2)[..] {
+                    cells.extend(differences(first, second));
+                }
+            }
+            Problem::NoClues => {}
+        }
+    }
+    cells.sort();
+    cells.dedup();
+    let difficulty = if problems.is_empty() {
+        difficulty::rate(grid)
+    } else {
+        None
+    };
+    Report {
+        problems,
+        difficulty,
+        cells,
+    }
+}
+
+fn clues(grid: &Grid) -> Vec<(usize, usize)> {
+    let cols = grid.grid_size.cols;
+    grid.cells()
+        .iter()
+        .enumerate()
+        .filter(|(_, state)| matches!(state, CellState::Value(_)))
+        .map(|(cell, _)| (cell / cols, cell % cols))
+        .collect()
+}
+
+/// The clues that, taken away on their own, leave a puzzle that can be
+/// solved, which makes them the likely culprits when it can't.
+fn blame_clues(grid: &Grid) -> Vec<(usize, usize)> {
+    clues(grid)
+        .into_iter()
+        .filter(|&(row, col)| {
+            let mut without = grid.clues_only();
+            without.set(row, col, CellState::Blank);
+            solver::count_solutions(&without, 1) > 0
+        })
+        .collect()
+}
+
+/// The cells where two solutions differ.
+fn differences(first: &Grid, second: &Grid) -> Vec<(usize, usize)> {
+    let cols = first.grid_size.cols;
+    first
+        .cells()
+        .iter()
+        .zip(second.cells())
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+        .map(|(cell, _)| (cell / cols, cell % cols))
+        .collect()
+}
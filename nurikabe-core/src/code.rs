@@ -0,0 +1,202 @@
+use core::fmt;
+use std::error::Error;
+
+use crate::{
+    grid::{Grid, GridSize},
+    CellState,
+};
+
+/// The URL-safe base64 alphabet, so codes survive being pasted into links.
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+/// A run byte with this bit set stands for that many blank cells (plus one)
+/// instead of a clue.
+const BLANK_RUN: u8 = 0x80;
+/// The most cells a code or puzz.link URL may describe, so a bad one can't
+/// ask for a huge grid.
+pub(crate) const MAX_CELLS: usize = 100 * 100;
+
+/// Why a share code couldn't be read.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CodeError {
+    /// The code has a character that isn't part of the alphabet.
+    BadCharacter(char),
+    /// The code ends before the grid size does, or has a stray character
+    /// left over at the end.
+    Truncated,
+    /// The grid is empty or too big.
+    BadSize,
+    /// The code describes more cells than the grid holds.
+    TooManyCells,
+}
+
+impl fmt::Display for CodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodeError::BadCharacter(c) => write!(f, "{:?} can't be part of a code", c),
+            CodeError::Truncated => write!(f, "the code is cut short"),
+            CodeError::BadSize => write!(f, "the grid size is invalid"),
+            CodeError::TooManyCells => write!(f, "the code has more cells than the grid"),
+        }
+    }
+}
+
+impl Error for CodeError {}
+
+/// Packs the clues of `grid` into a short string of letters, digits, `-` and
+/// `_`. The width and height come first, then each clue as a byte, with runs
+/// of blank cells folded into a single byte and the last run left off.
+pub fn encode(grid: &Grid) -> String {
+    let GridSize { rows, cols } = grid.grid_size;
+    let mut bytes = Vec::new();
+    push_varint(&mut bytes, cols);
+    push_varint(&mut bytes, rows);
+    let mut blanks = 0;
+    for &state in grid.cells() {
+        match state {
+            CellState::Value(v) if v > 0 => {
+                push_blanks(&mut bytes, blanks);
+                blanks = 0;
+                bytes.push(v as u8);
+            }
+            _ => blanks += 1,
+        }
+    }
+    to_text(&bytes)
+}
+
+/// Reads a code written by `encode` back into a grid of clues.
+pub fn decode(code: &str) -> Result<Grid, CodeError> {
+    let mut bytes = from_text(code)?.into_iter();
+    let cols = read_varint(&mut bytes)?;
+    let rows = read_varint(&mut bytes)?;
+    if cols == 0 || rows == 0 || cols.saturating_mul(rows) > MAX_CELLS {
+        return Err(CodeError::BadSize);
+    }
+    let mut grid = Grid::new(GridSize { rows, cols });
+    let mut cell = 0;
+    for byte in bytes {
+        if cell >= rows * cols {
+            return Err(CodeError::TooManyCells);
+        }
+        if byte & BLANK_RUN != 0 {
+            cell += usize::from(byte & !BLANK_RUN) + 1;
+        } else {
+            grid.set(cell / cols, cell % cols, CellState::Value(byte as i8));
+            cell += 1;
+        }
+    }
+    if cell > rows * cols {
+        return Err(CodeError::TooManyCells);
+    }
+    Ok(grid)
+}
+
+/// Writes `bytes` in the code alphabet, six bits to a character.
+pub(crate) fn to_text(bytes: &[u8]) -> String {
+    let mut code = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+            bits | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..=chunk.len() {
+            code.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    code
+}
+
+/// Reads bytes written by `to_text`.
+pub(crate) fn from_text(code: &str) -> Result<Vec<u8>, CodeError> {
+    let mut bytes = Vec::new();
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    for c in code.trim().chars() {
+        let value = ALPHABET
+            .iter()
+            .position(|&a| a as char == c)
+            .ok_or(CodeError::BadCharacter(c))?;
+        bits = bits << 6 | value as u32;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            bytes.push((bits >> bit_count) as u8);
+            bits &= (1 << bit_count) - 1;
+        }
+    }
+    // a lone character at the end can't hold a whole byte
+    if bit_count == 6 {
+        return Err(CodeError::Truncated);
+    }
+    Ok(bytes)
+}
+
+fn push_blanks(bytes: &mut Vec<u8>, mut blanks: usize) {
+    while blanks > 0 {
+        let run = blanks.min(128);
+        bytes.push(BLANK_RUN | (run - 1) as u8);
+        blanks -= run;
+    }
+}
+
+/// Writes `n` seven bits at a time, low bits first, with the top bit of each
+/// byte marking that more follow.
+pub(crate) fn push_varint(bytes: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        bytes.push((n & 0x7f) as u8 | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+pub(crate) fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Result<usize, CodeError> {
+    let mut n = 0usize;
+    for shift in (0..usize::BITS).step_by(7) {
+        let byte = bytes.next().ok_or(CodeError::Truncated)?;
+        n |= usize::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(CodeError::BadSize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        // a run of blanks longer than one byte holds, and clues at both ends
+        let mut grid = Grid::new(GridSize { rows: 20, cols: 15 });
+        grid.set(0, 0, CellState::Value(1));
+        grid.set(10, 7, CellState::Value(42));
+        grid.set(19, 14, CellState::Value(9));
+        let read = decode(&encode(&grid)).unwrap();
+        assert_eq!(read.grid_size, grid.grid_size);
+        assert_eq!(read.cells(), grid.cells());
+    }
+
+    #[test]
+    fn leaves_out_marks() {
+        let mut grid = Grid::new(GridSize { rows: 2, cols: 2 });
+        grid.set(0, 0, CellState::Value(1));
+        grid.set(1, 1, CellState::River);
+        assert_eq!(
+            decode(&encode(&grid)).unwrap().cells(),
+            grid.clues_only().cells()
+        );
+    }
+
+    #[test]
+    fn rejects_bad_codes() {
+        assert_eq!(decode("AB!C").err(), Some(CodeError::BadCharacter('!')));
+        assert_eq!(decode("").err(), Some(CodeError::Truncated));
+        // a width and height of zero
+        assert_eq!(decode(&to_text(&[0, 0])).err(), Some(CodeError::BadSize));
+        // five clues on a board of four cells
+        assert_eq!(
+            decode(&to_text(&[2, 2, 1, 1, 1, 1, 1])).err(),
+            Some(CodeError::TooManyCells)
+        );
+    }
+}
@@ -0,0 +1,464 @@
+use core::fmt;
+use std::collections::VecDeque;
+
+use crate::{
+    grid::{Grid, GridSize},
+    CellState,
+};
+
+/// A named rule a human solver would use to fill in a cell.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Technique {
+    /// An island that has reached its clue size is surrounded by sea.
+    CompleteIsland,
+    /// A cell touching two different clued islands would join them.
+    BetweenClues,
+    /// No clued island can grow far enough to reach the cell.
+    Unreachable,
+    /// An unfinished island has only one cell it can grow into.
+    OnlyExpansion,
+    /// The cell is the last gap in an otherwise all-sea 2x2 block.
+    PoolPrevention,
+    /// A separated part of the sea has only one way to join the rest.
+    SeaExpansion,
+}
+
+impl Technique {
+    /// Every technique, from the simplest to spot to the hardest.
+    pub const ALL: [Technique; 6] = [
+        Technique::CompleteIsland,
+        Technique::BetweenClues,
+        Technique::Unreachable,
+        Technique::OnlyExpansion,
+        Technique::PoolPrevention,
+        Technique::SeaExpansion,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Technique::CompleteIsland => "Complete island",
+            Technique::BetweenClues => "Between two clues",
+            Technique::Unreachable => "Unreachable cell",
+            Technique::OnlyExpansion => "Only way to grow",
+            Technique::PoolPrevention => "Pool prevention",
+            Technique::SeaExpansion => "Sea expansion",
+        }
+    }
+
+    fn apply(&self, grid: &Grid, analysis: &Analysis) -> Vec<Deduction> {
+        let mut deductions = Vec::new();
+        let mut push = |row: usize, col: usize, state: CellState, reason: Reason| {
+            if !deductions
+                .iter()
+                .any(|d: &Deduction| d.row == row && d.col == col)
+            {
+                deductions.push(Deduction {
+                    row,
+                    col,
+                    state,
+                    reason,
+                });
+            }
+        };
+        let GridSize { rows, cols } = grid.grid_size;
+        match self {
+            Technique::CompleteIsland => {
+                for island in analysis.islands.iter().filter(|i| i.is_complete()) {
+                    let size = island.cells.len();
+                    for (row, col) in blank_neighbors(grid, &island.cells) {
+                        push(row, col, CellState::River, Reason::CompleteIsland { size });
+                    }
+                }
+            }
+            Technique::BetweenClues => {
+                for (row, col) in blanks(grid) {
+                    let mut touching = grid
+                        .neighbors(row, col)
+                        .into_iter()
+                        .filter_map(|(r, c)| analysis.owners[r][c])
+                        .filter_map(|i| analysis.islands[i].clue.map(|clue| (i, clue)));
+                    if let Some((first, first_clue)) = touching.next() {
+                        if let Some((_, second_clue)) = touching.find(|&(i, _)| i != first) {
+                            let reason = Reason::BetweenClues {
+                                clues: (first_clue, second_clue),
+                            };
+                            push(row, col, CellState::River, reason);
+                        }
+                    }
+                }
+            }
+            Technique::Unreachable => {
+                for (row, col) in blanks(grid) {
+                    if !analysis.reachable[row][col] {
+                        push(row, col, CellState::River, Reason::Unreachable);
+                    }
+                }
+            }
+            Technique::OnlyExpansion => {
+                for island in analysis.islands.iter().filter(|i| !i.is_complete()) {
+                    if let [(row, col)] = blank_neighbors(grid, &island.cells)[..] {
+                        let reason = Reason::OnlyExpansion { clue: island.clue };
+                        push(row, col, CellState::Island, reason);
+                    }
+                }
+            }
+            Technique::PoolPrevention => {
+                for row in 0..rows.saturating_sub(1) {
+                    for col in 0..cols.saturating_sub(1) {
+                        let block = pool_block(row, col);
+                        let rivers = block
+                            .iter()
+                            .filter(|&&(r, c)| grid.get(r, c) == CellState::River)
+                            .count();
+                        let blanks: Vec<_> = block
+                            .into_iter()
+                            .filter(|&(r, c)| grid.get(r, c).is_blank())
+                            .collect();
+                        if let (3, &[(r, c)]) = (rivers, blanks.as_slice()) {
+                            push(r, c, CellState::Island, Reason::PoolPrevention);
+                        }
+                    }
+                }
+            }
+            Technique::SeaExpansion => {
+                let seas = grid.components(CellState::River);
+                if seas.len() > 1 {
+                    for sea in &seas {
+                        if let [(row, col)] = blank_neighbors(grid, sea)[..] {
+                            push(row, col, CellState::River, Reason::SeaExpansion);
+                        }
+                    }
+                }
+            }
+        }
+        deductions
+    }
+}
+
+impl fmt::Display for Technique {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Why a deduction holds, with the details needed to explain it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Reason {
+    /// The cell borders a finished island of `size` cells.
+    CompleteIsland {
+        size: usize,
+    },
+    /// The cell touches the islands of both clues.
+    BetweenClues {
+        clues: (usize, usize),
+    },
+    Unreachable,
+    /// The cell is the only way out for an island with the given clue, or for
+    /// an island that has no clue yet.
+    OnlyExpansion {
+        clue: Option<usize>,
+    },
+    PoolPrevention,
+    SeaExpansion,
+}
+
+impl Reason {
+    pub fn technique(&self) -> Technique {
+        match self {
+            Reason::CompleteIsland { .. } => Technique::CompleteIsland,
+            Reason::BetweenClues { .. } => Technique::BetweenClues,
+            Reason::Unreachable => Technique::Unreachable,
+            Reason::OnlyExpansion { .. } => Technique::OnlyExpansion,
+            Reason::PoolPrevention => Technique::PoolPrevention,
+            Reason::SeaExpansion => Technique::SeaExpansion,
+        }
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::CompleteIsland { size } => write!(
+                f,
+                "This cell must be sea because it borders the island of {}, which is already complete.",
+                size
+            ),
+            Reason::BetweenClues { clues: (a, b) } => write!(
+                f,
+                "This cell must be sea because it touches both the island of {} and the island of {}, and an island can only hold one number.",
+                a, b
+            ),
+            Reason::Unreachable => write!(
+                f,
+                "This cell must be sea because it is unreachable from any clue."
+            ),
+            Reason::OnlyExpansion { clue: Some(clue) } => write!(
+                f,
+                "This cell must be island because it is the only way the island of {} can keep growing.",
+                clue
+            ),
+            Reason::OnlyExpansion { clue: None } => write!(
+                f,
+                "This cell must be island because it is the only way the island next to it can reach a number."
+            ),
+            Reason::PoolPrevention => write!(
+                f,
+                "This cell must be island because otherwise it would complete a 2x2 pool of sea."
+            ),
+            Reason::SeaExpansion => write!(
+                f,
+                "This cell must be sea because it is the only way for part of the sea to join the rest."
+            ),
+        }
+    }
+}
+
+/// A single cell that is forced by the current state of the grid, along with
+/// the reason it is forced.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Deduction {
+    pub row: usize,
+    pub col: usize,
+    pub state: CellState,
+    pub reason: Reason,
+}
+
+/// Raised when a partially filled grid can no longer be completed.
+#[derive(Debug)]
+pub struct Contradiction;
+
+/// A connected group of island cells (clues included) in a partially
+/// solved grid. Blank cells are never part of an island.
+pub struct Island {
+    pub cells: Vec<(usize, usize)>,
+    pub clue: Option<usize>,
+}
+
+impl Island {
+    pub fn is_complete(&self) -> bool {
+        self.clue == Some(self.cells.len())
+    }
+}
+
+/// What the techniques need to know about the grid, computed once per step.
+struct Analysis {
+    islands: Vec<Island>,
+    /// The index of the island each cell belongs to.
+    owners: Vec<Vec<Option<usize>>>,
+    /// Whether some clued island could grow to include each cell.
+    reachable: Vec<Vec<bool>>,
+}
+
+impl Analysis {
+    /// Analyzes the grid, failing if it already breaks a rule that further
+    /// moves can't fix.
+    fn new(grid: &Grid) -> Result<Self, Contradiction> {
+        let GridSize { rows, cols } = grid.grid_size;
+        let islands = islands(grid)?;
+        let mut owners = vec![vec![None; cols]; rows];
+        for (i, island) in islands.iter().enumerate() {
+            for &(row, col) in &island.cells {
+                owners[row][col] = Some(i);
+            }
+            let oversized = island.clue.is_some_and(|clue| island.cells.len() > clue);
+            let stuck = !island.is_complete() && blank_neighbors(grid, &island.cells).is_empty();
+            if oversized || stuck {
+                return Err(Contradiction);
+            }
+        }
+
+        let mut analysis = Analysis {
+            islands,
+            owners,
+            reachable: vec![vec![false; cols]; rows],
+        };
+        for i in 0..analysis.islands.len() {
+            analysis.mark_reachable(grid, i);
+        }
+        for (row, col, state) in grid.iter() {
+            if state == CellState::Island && !analysis.reachable[row][col] {
+                return Err(Contradiction);
+            }
+        }
+
+        for row in 0..rows.saturating_sub(1) {
+            for col in 0..cols.saturating_sub(1) {
+                if pool_block(row, col)
+                    .iter()
+                    .all(|&(r, c)| grid.get(r, c) == CellState::River)
+                {
+                    return Err(Contradiction);
+                }
+            }
+        }
+
+        // every river cell must be able to reach every other one
+        let open = grid.components_where(|state| {
+            matches!(
+                state,
+                CellState::River | CellState::Blank | CellState::Maybe
+            )
+        });
+        let seas = open
+            .iter()
+            .filter(|region| {
+                region
+                    .iter()
+                    .any(|&(r, c)| grid.get(r, c) == CellState::River)
+            })
+            .count();
+        if seas > 1 {
+            return Err(Contradiction);
+        }
+        Ok(analysis)
+    }
+
+    /// Breadth-first search over the cells island `i` could claim, where each
+    /// step costs one cell of its remaining budget.
+    fn mark_reachable(&mut self, grid: &Grid, i: usize) {
+        let GridSize { rows, cols } = grid.grid_size;
+        let island = &self.islands[i];
+        let Some(clue) = island.clue else {
+            return;
+        };
+        let budget = clue.saturating_sub(island.cells.len());
+        let mut distance = vec![vec![usize::MAX; cols]; rows];
+        let mut queue = VecDeque::new();
+        for &(row, col) in &island.cells {
+            distance[row][col] = 0;
+            self.reachable[row][col] = true;
+            queue.push_back((row, col));
+        }
+        while let Some((row, col)) = queue.pop_front() {
+            let next = distance[row][col] + 1;
+            if next > budget {
+                continue;
+            }
+            for (r, c) in grid.neighbors(row, col) {
+                if distance[r][c] != usize::MAX || !self.can_claim(grid, i, r, c) {
+                    continue;
+                }
+                distance[r][c] = next;
+                self.reachable[r][c] = true;
+                queue.push_back((r, c));
+            }
+        }
+    }
+
+    /// Whether island `i` could extend into `(row, col)` without swallowing
+    /// a different clued island.
+    fn can_claim(&self, grid: &Grid, i: usize, row: usize, col: usize) -> bool {
+        let foreign = |r: usize, c: usize| {
+            self.owners[r][c].is_some_and(|j| j != i && self.islands[j].clue.is_some())
+        };
+        match grid.get(row, col) {
+            CellState::Blank | CellState::Island | CellState::Maybe => {
+                !foreign(row, col)
+                    && grid
+                        .neighbors(row, col)
+                        .into_iter()
+                        .all(|(r, c)| !foreign(r, c))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Finds every cell that the simplest applicable technique can fill in. An
+/// empty list means no technique makes progress.
+pub fn next_deductions(grid: &Grid) -> Result<Vec<Deduction>, Contradiction> {
+    let analysis = Analysis::new(grid)?;
+    for technique in Technique::ALL {
+        let deductions = technique.apply(grid, &analysis);
+        if !deductions.is_empty() {
+            return Ok(deductions);
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// Finds the blank cells the clues alone show to be sea: next to a finished
+/// island, touching two clues at once, or out of reach of every clue. Unlike
+/// `propagate`, nothing is built on top of these, so on a fresh grid this is
+/// only what a player would fill in before thinking hard.
+pub fn forced_sea(grid: &Grid) -> Result<Vec<(usize, usize)>, Contradiction> {
+    let analysis = Analysis::new(grid)?;
+    let mut cells = Vec::new();
+    for technique in [
+        Technique::CompleteIsland,
+        Technique::BetweenClues,
+        Technique::Unreachable,
+    ] {
+        for deduction in technique.apply(grid, &analysis) {
+            let cell = (deduction.row, deduction.col);
+            if deduction.state == CellState::River && !cells.contains(&cell) {
+                cells.push(cell);
+            }
+        }
+    }
+    Ok(cells)
+}
+
+/// Applies techniques to the grid until none of them makes progress,
+/// returning every deduction made along the way.
+pub fn propagate(grid: &mut Grid) -> Result<Vec<Deduction>, Contradiction> {
+    let mut log = Vec::new();
+    loop {
+        let deductions = next_deductions(grid)?;
+        if deductions.is_empty() {
+            return Ok(log);
+        }
+        for deduction in &deductions {
+            grid.set(deduction.row, deduction.col, deduction.state);
+        }
+        log.extend(deductions);
+    }
+}
+
+/// Finds every island in the grid, along with the clue it contains. Fails if
+/// an island holds more than one clue.
+pub fn islands(grid: &Grid) -> Result<Vec<Island>, Contradiction> {
+    let mut islands = Vec::new();
+    for cells in
+        grid.components_where(|state| matches!(state, CellState::Island | CellState::Value(_)))
+    {
+        let mut clues = cells.iter().filter_map(|&(r, c)| match grid.get(r, c) {
+            CellState::Value(v) => Some(v as usize),
+            _ => None,
+        });
+        let clue = clues.next();
+        if clues.next().is_some() {
+            return Err(Contradiction);
+        }
+        islands.push(Island { cells, clue });
+    }
+    Ok(islands)
+}
+
+pub fn blank_neighbors(grid: &Grid, cells: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut blanks = Vec::new();
+    for &(row, col) in cells {
+        for (r, c, state) in grid.orthogonal_neighbors(row, col) {
+            if state.is_blank() && !blanks.contains(&(r, c)) {
+                blanks.push((r, c));
+            }
+        }
+    }
+    blanks
+}
+
+fn blanks(grid: &Grid) -> Vec<(usize, usize)> {
+    grid.iter()
+        .filter(|(_, _, state)| state.is_blank())
+        .map(|(row, col, _)| (row, col))
+        .collect()
+}
+
+fn pool_block(row: usize, col: usize) -> [(usize, usize); 4] {
+    [
+        (row, col),
+        (row + 1, col),
+        (row, col + 1),
+        (row + 1, col + 1),
+    ]
+}
@@ -0,0 +1,74 @@
+use core::fmt;
+use std::str::FromStr;
+
+use crate::{
+    deduction::{self, Technique},
+    grid::Grid,
+    solver,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    /// How hard it is to spot a single technique.
+    fn of_technique(technique: Technique) -> Self {
+        match technique {
+            Technique::CompleteIsland | Technique::BetweenClues | Technique::Unreachable => {
+                Difficulty::Easy
+            }
+            Technique::OnlyExpansion | Technique::PoolPrevention | Technique::SeaExpansion => {
+                Difficulty::Medium
+            }
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Easy" => Ok(Difficulty::Easy),
+            "Medium" => Ok(Difficulty::Medium),
+            "Hard" => Ok(Difficulty::Hard),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Rates a puzzle by solving it the way a person would: the rating is the
+/// hardest technique needed, or `Hard` if the techniques run dry and a cell
+/// has to be guessed. Returns `None` if the puzzle has no solution.
+pub fn rate(grid: &Grid) -> Option<Difficulty> {
+    let solution = solver::solve(grid)?;
+    let mut grid = grid.clues_only();
+    let mut difficulty = Difficulty::Easy;
+    loop {
+        let deductions = deduction::propagate(&mut grid).ok()?;
+        for deduction in deductions {
+            difficulty = difficulty.max(Difficulty::of_technique(deduction.reason.technique()));
+        }
+        let Some((row, col, _)) = grid.iter().find(|(_, _, state)| state.is_blank()) else {
+            return Some(difficulty);
+        };
+        // stuck: take the answer for one cell from the solution and carry on
+        difficulty = Difficulty::Hard;
+        grid.set(row, col, solution.get(row, col));
+    }
+}
@@ -0,0 +1,455 @@
+use core::fmt;
+use std::{error::Error, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    code::{self, CodeError},
+    grid::{Grid, GridSize, JankoError, PuzzLinkError},
+    CellState,
+};
+
+/// The extensions of the files in a puzzle folder that hold puzzles: `txt`
+/// for the game's own format and `janko` for the janko.at format.
+pub const PUZZLE_EXTENSIONS: [&str; 2] = ["txt", "janko"];
+
+/// The file in a puzzle folder that lists a pack of puzzles rather than
+/// holding one.
+pub const PACK_MANIFEST: &str = "pack.txt";
+
+/// A way of writing a puzzle down as text.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    /// The game's own file format, with a size line and one line per clue.
+    Legacy,
+    /// The janko.at text format.
+    Janko,
+    /// A puzz.link URL.
+    PuzzLink,
+    /// A share code made by `code::encode`.
+    Code,
+    /// A picture of the grid like `Grid`'s `Display` output.
+    Ascii,
+    /// A JSON object like `{"grid": [[2, 0], [0, 1]]}`, with 0 for a blank.
+    Json,
+}
+
+impl Format {
+    /// Guesses the format of `text` from its shape. Anything that doesn't
+    /// look like one of the other formats is taken to be ASCII.
+    pub fn detect(text: &str) -> Format {
+        let text = text.trim();
+        let is_code_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+        if text.contains('?') && text.contains("nurikabe/") {
+            Format::PuzzLink
+        } else if text.starts_with('{') {
+            Format::Json
+        } else if text
+            .lines()
+            .any(|line| matches!(line.trim(), "[problem]" | "problem"))
+        {
+            Format::Janko
+        } else if text.starts_with('#') {
+            Format::Legacy
+        } else if !text.is_empty() && text.chars().all(is_code_char) && !is_ascii_row(text) {
+            Format::Code
+        } else {
+            Format::Ascii
+        }
+    }
+
+    /// The format of a puzzle file with the text `text`, going by the file's
+    /// extension where it has a telling one.
+    pub fn for_file(path: &Path, text: &str) -> Format {
+        Format::for_extension(path).unwrap_or_else(|| Format::detect(text))
+    }
+
+    /// The format a file's extension stands for: `txt` for the game's own
+    /// format, `janko`, `json`, `url` for a puzz.link URL and `code` for a
+    /// share code.
+    pub fn for_extension(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()? {
+            "txt" => Some(Format::Legacy),
+            "janko" => Some(Format::Janko),
+            "json" => Some(Format::Json),
+            "url" => Some(Format::PuzzLink),
+            "code" => Some(Format::Code),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Legacy => "legacy",
+            Format::Janko => "janko",
+            Format::PuzzLink => "puzzlink",
+            Format::Code => "code",
+            Format::Ascii => "ascii",
+            Format::Json => "json",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "legacy" => Ok(Format::Legacy),
+            "janko" => Ok(Format::Janko),
+            "puzzlink" => Ok(Format::PuzzLink),
+            "code" => Ok(Format::Code),
+            "ascii" => Ok(Format::Ascii),
+            "json" => Ok(Format::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Why text couldn't be read as a puzzle.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FormatError {
+    Legacy,
+    Janko(JankoError),
+    PuzzLink(PuzzLinkError),
+    Code(CodeError),
+    /// The ASCII grid is empty or has a character that isn't a cell.
+    Ascii,
+    Json(String),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Legacy => write!(f, "not a valid puzzle file"),
+            FormatError::Janko(err) => write!(f, "janko.at puzzle: {}", err),
+            FormatError::PuzzLink(err) => write!(f, "puzz.link URL: {}", err),
+            FormatError::Code(err) => write!(f, "puzzle code: {}", err),
+            FormatError::Ascii => write!(f, "not a grid of cells"),
+            FormatError::Json(err) => write!(f, "JSON puzzle: {}", err),
+        }
+    }
+}
+
+impl Error for FormatError {}
+
+/// Reads `text` as a puzzle in `format`.
+pub fn parse(text: &str, format: Format) -> Result<Grid, FormatError> {
+    match format {
+        Format::Legacy => Grid::from_puzzle_string(text).ok_or(FormatError::Legacy),
+        Format::Janko => Grid::from_janko_string(text).map_err(FormatError::Janko),
+        Format::PuzzLink => Grid::from_puzz_link(text).map_err(FormatError::PuzzLink),
+        Format::Code => code::decode(text).map_err(FormatError::Code),
+        Format::Ascii => parse_ascii(text).ok_or(FormatError::Ascii),
+        Format::Json => parse_json(text),
+    }
+}
+
+/// Writes the clues of `grid` as text in `format`, which `parse` reads back.
+pub fn write(grid: &Grid, format: Format) -> String {
+    let GridSize { rows, cols } = grid.grid_size;
+    let clues = || {
+        (0..rows)
+            .flat_map(move |row| (0..cols).map(move |col| (row, col)))
+            .filter_map(|(row, col)| match grid.get(row, col) {
+                CellState::Value(v) => Some((row, col, v)),
+                _ => None,
+            })
+    };
+    match format {
+        Format::Legacy => {
+            let mut text = format!(
+                "# size: width then height\n{},{}\n\n# rooms: size, row , column\n",
+                cols, rows
+            );
+            for (row, col, v) in clues() {
+                text.push_str(&format!("{},{},{}\n", v, row + 1, col + 1));
+            }
+            text
+        }
+        Format::Janko => {
+            let mut text = "[problem]\n".to_string();
+            for row in 0..rows {
+                let cells: Vec<String> = grid
+                    .row(row)
+                    .iter()
+                    .map(|state| match state {
+                        CellState::Value(v) => v.to_string(),
+                        _ => "-".to_string(),
+                    })
+                    .collect();
+                text.push_str(&cells.join(" "));
+                text.push('\n');
+            }
+            text
+        }
+        Format::PuzzLink => format!("{}\n", grid.to_puzz_link()),
+        Format::Code => format!("{}\n", code::encode(grid)),
+        // blanks are written as `-` rather than the spaces `Display` uses,
+        // since a row of spaces would get lost
+        Format::Ascii if clues().all(|(_, _, v)| v < 10) => {
+            let mut text = String::new();
+            for row in 0..rows {
+                for state in grid.row(row) {
+                    match state {
+                        CellState::Value(v) => text.push_str(&v.to_string()),
+                        _ => text.push('-'),
+                    }
+                }
+                text.push('\n');
+            }
+            text
+        }
+        Format::Ascii => grid.clues_only().to_string(),
+        Format::Json => {
+            let mut puzzle = JsonPuzzle {
+                grid: vec![vec![0; cols]; rows],
+            };
+            for (row, col, v) in clues() {
+                puzzle.grid[row][col] = v;
+            }
+            let json = serde_json::to_string(&puzzle).expect("a grid of numbers is valid JSON");
+            format!("{}\n", json)
+        }
+    }
+}
+
+/// Reads `text` as a puzzle in whichever format it looks like.
+pub fn parse_any(text: &str) -> Result<Grid, FormatError> {
+    parse(text, Format::detect(text))
+}
+
+/// Whether every character of `line` can be a cell of an ASCII grid.
+fn is_ascii_row(line: &str) -> bool {
+    line.chars().all(|c| ascii_cell(c).is_some())
+}
+
+fn ascii_cell(c: char) -> Option<CellState> {
+    match c {
+        ' ' | '-' | '_' => Some(CellState::Blank),
+        '.' => Some(CellState::Island),
+        'X' | 'x' | '#' => Some(CellState::River),
+        '?' => Some(CellState::Maybe),
+        '1'..='9' => Some(CellState::Value(c as i8 - b'0' as i8)),
+        _ => None,
+    }
+}
+
+/// Reads a grid drawn one character per cell: digits for clues, `.` for an
+/// island, `X` or `#` for river, `?` for a maybe mark and a space or `-` for
+/// a blank. Grids with clues of two or more digits are read as cells
+/// separated by spaces instead, the way `Grid`'s `Display` impl writes them.
+/// The header line written by `Display` gives the size, and short rows are
+/// padded with blanks, since trailing spaces tend to get lost when pasting.
+fn parse_ascii(text: &str) -> Option<Grid> {
+    let mut lines: Vec<&str> = text
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .collect();
+    let header = lines.iter().position(|line| line.contains("rows and"));
+    let size = header.and_then(|header| read_header(lines[header]));
+    if let Some(header) = header {
+        lines.drain(..=header);
+    }
+    // rows of blanks written as spaces can only be told from the lines
+    // around the grid by the size in the header
+    let lines = match size {
+        Some(size) => &lines[..size.rows.min(lines.len())],
+        None => {
+            let start = lines.iter().position(|line| !line.trim().is_empty())?;
+            let end = lines.iter().rposition(|line| !line.trim().is_empty())? + 1;
+            &lines[start..end]
+        }
+    };
+    let wide = is_wide(lines);
+    let cells: Vec<Vec<CellState>> = lines
+        .iter()
+        .map(|line| {
+            if wide {
+                line.split_whitespace()
+                    .map(|token| match token.parse::<i8>() {
+                        Ok(v) if v > 0 => Some(CellState::Value(v)),
+                        _ => {
+                            let mut chars = token.chars();
+                            let cell = ascii_cell(chars.next()?);
+                            chars.next().is_none().then_some(cell)?
+                        }
+                    })
+                    .collect()
+            } else {
+                line.chars().map(ascii_cell).collect()
+            }
+        })
+        .collect::<Option<_>>()?;
+    let rows = size.map_or(cells.len(), |size| size.rows);
+    let cols = cells
+        .iter()
+        .map(Vec::len)
+        .chain(size.map(|size| size.cols))
+        .max()?;
+    if rows == 0 || cols == 0 {
+        return None;
+    }
+    let mut grid = Grid::new(GridSize { rows, cols });
+    for (row, states) in cells.into_iter().enumerate() {
+        for (col, state) in states.into_iter().enumerate() {
+            grid.set(row, col, state);
+        }
+    }
+    Some(grid)
+}
+
+/// The size in a header line like the one `Display` writes, `7 rows and 5
+/// cols`.
+fn read_header(line: &str) -> Option<GridSize> {
+    let (rows, cols) = line
+        .trim()
+        .strip_suffix(" cols")?
+        .split_once(" rows and ")?;
+    let grid_size = GridSize {
+        rows: rows.parse().ok()?,
+        cols: cols.parse().ok()?,
+    };
+    (grid_size.rows.saturating_mul(grid_size.cols) <= code::MAX_CELLS).then_some(grid_size)
+}
+
+/// Whether `lines` are cells separated by spaces, with every cell
+/// right-aligned to the same width of two or more characters. Two digits
+/// side by side aren't enough to tell, since a grid drawn one character per
+/// cell has them wherever two clues are next to each other.
+fn is_wide(lines: &[&str]) -> bool {
+    // each cell with the position its column ends at
+    let rows: Vec<Vec<(usize, &str)>> = lines
+        .iter()
+        .map(|line| {
+            let mut cells = Vec::new();
+            let mut start = None;
+            for (i, c) in line.char_indices().chain([(line.len(), ' ')]) {
+                match (c.is_whitespace(), start) {
+                    (false, None) => start = Some(i),
+                    (true, Some(first)) => {
+                        cells.push((i, &line[first..i]));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+            cells
+        })
+        .collect();
+    let width = rows.iter().flatten().map(|(_, cell)| cell.len()).max();
+    let Some(width) = width.filter(|&width| width >= 2) else {
+        return false;
+    };
+    let cols = rows[0].len();
+    rows.iter().all(|cells| {
+        cells.len() == cols
+            && cells
+                .iter()
+                .all(|(_, cell)| cell.len() == 1 || cell.parse::<i8>().is_ok())
+            && cells
+                .windows(2)
+                .all(|pair| pair[1].0 == pair[0].0 + width + 1)
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonPuzzle {
+    grid: Vec<Vec<i8>>,
+}
+
+fn parse_json(text: &str) -> Result<Grid, FormatError> {
+    let puzzle: JsonPuzzle =
+        serde_json::from_str(text).map_err(|err| FormatError::Json(err.to_string()))?;
+    let rows = puzzle.grid.len();
+    let cols = puzzle.grid.first().map_or(0, Vec::len);
+    if cols == 0 || puzzle.grid.iter().any(|row| row.len() != cols) {
+        return Err(FormatError::Json("rows have different lengths".to_string()));
+    }
+    let mut grid = Grid::new(GridSize { rows, cols });
+    for (row, values) in puzzle.grid.iter().enumerate() {
+        for (col, &value) in values.iter().enumerate() {
+            match value {
+                0 => {}
+                1.. => grid.set(row, col, CellState::Value(value)),
+                _ => return Err(FormatError::Json(format!("invalid clue {}", value))),
+            }
+        }
+    }
+    Ok(grid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_with_clues(grid_size: GridSize, clues: &[(usize, usize, i8)]) -> Grid {
+        let mut grid = Grid::new(grid_size);
+        for &(row, col, v) in clues {
+            grid.set(row, col, CellState::Value(v));
+        }
+        grid
+    }
+
+    fn assert_same(read: &Grid, grid: &Grid) {
+        assert_eq!(read.grid_size, grid.grid_size);
+        assert_eq!(read.cells(), grid.cells());
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        let grid = grid_with_clues(
+            GridSize { rows: 4, cols: 6 },
+            &[(0, 0, 3), (0, 1, 4), (2, 5, 17), (3, 2, 1)],
+        );
+        let formats = [
+            Format::Legacy,
+            Format::Janko,
+            Format::PuzzLink,
+            Format::Code,
+            Format::Ascii,
+            Format::Json,
+        ];
+        for format in formats {
+            let text = write(&grid, format);
+            assert_eq!(Format::detect(&text), format, "{:?}", text);
+            assert_same(&parse(&text, format).unwrap(), &grid);
+        }
+    }
+
+    #[test]
+    fn json_rejects_ragged_rows() {
+        assert!(matches!(
+            parse(r#"{"grid": [[2, 0], [1]]}"#, Format::Json),
+            Err(FormatError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn ascii_reads_back_clues_side_by_side() {
+        let grid = grid_with_clues(
+            GridSize { rows: 3, cols: 5 },
+            &[(0, 0, 3), (0, 1, 4), (2, 4, 1)],
+        );
+        assert_same(&parse(&grid.to_string(), Format::Ascii).unwrap(), &grid);
+        assert_same(&parse_any(&write(&grid, Format::Ascii)).unwrap(), &grid);
+    }
+
+    #[test]
+    fn ascii_reads_back_wide_clues() {
+        let grid = grid_with_clues(
+            GridSize { rows: 3, cols: 4 },
+            &[(0, 0, 12), (0, 1, 3), (1, 3, 1), (2, 2, 10)],
+        );
+        assert_same(&parse(&grid.to_string(), Format::Ascii).unwrap(), &grid);
+    }
+
+    #[test]
+    fn ascii_keeps_blank_rows_at_the_edges() {
+        let grid = grid_with_clues(GridSize { rows: 4, cols: 3 }, &[(1, 1, 2)]);
+        assert_same(&parse(&grid.to_string(), Format::Ascii).unwrap(), &grid);
+    }
+}
@@ -0,0 +1,132 @@
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    grid::{Grid, GridSize},
+    solver, CellState,
+};
+
+/// The largest island the generator will create, so every clue fits in a
+/// single tile.
+const MAX_ISLAND_SIZE: usize = 9;
+
+/// The widest and tallest board the generator takes on. Checking that a
+/// puzzle has one solution gets slow quickly past this, and boards a few
+/// cells bigger may never finish.
+pub const MAX_GENERATED_SIZE: usize = 12;
+/// How many random boards are tried before giving up. Boards up to
+/// `MAX_GENERATED_SIZE` rarely need more than a hundred.
+const MAX_ATTEMPTS: usize = 500;
+
+/// Generates a random puzzle of the given size that has exactly one solution.
+/// The returned grid only contains clues. Returns `None` for a board bigger
+/// than `MAX_GENERATED_SIZE`, a board too narrow to hold a clue, or if no
+/// puzzle turned up in `MAX_ATTEMPTS` tries.
+pub fn generate(grid_size: GridSize, rng: &mut impl Rng) -> Option<Grid> {
+    let GridSize { rows, cols } = grid_size;
+    // islands are only added to break up pools, so a single row or column
+    // longer than one cell would be all sea
+    let can_hold_clue = rows * cols == 1 || rows.min(cols) >= 2;
+    if !can_hold_clue || rows.max(cols) > MAX_GENERATED_SIZE {
+        return None;
+    }
+    for _ in 0..MAX_ATTEMPTS {
+        let Some(solution) = random_solution(grid_size, rng) else {
+            continue;
+        };
+        let puzzle = place_clues(&solution, rng);
+        if solver::count_solutions(&puzzle, 2) == 1 {
+            return Some(puzzle);
+        }
+    }
+    None
+}
+
+/// Builds a random filled grid that follows the rules, minus the clues: the
+/// board starts as all sea and island cells are added until no pools remain.
+fn random_solution(grid_size: GridSize, rng: &mut impl Rng) -> Option<Grid> {
+    let GridSize { rows, cols } = grid_size;
+    let mut grid = Grid::new(grid_size);
+    for (_, _, state) in grid.iter_mut() {
+        *state = CellState::River;
+    }
+    // a 1x1 board can't hold any sea at all
+    if rows * cols == 1 {
+        grid.set(0, 0, CellState::Island);
+        return Some(grid);
+    }
+
+    loop {
+        let pools: Vec<(usize, usize)> = (0..rows.saturating_sub(1))
+            .flat_map(|row| (0..cols.saturating_sub(1)).map(move |col| (row, col)))
+            .filter(|&(row, col)| {
+                block(row, col)
+                    .iter()
+                    .all(|&(r, c)| grid.get(r, c) == CellState::River)
+            })
+            .collect();
+        let Some(&(row, col)) = pools.choose(rng) else {
+            break;
+        };
+        let mut candidates = block(row, col);
+        candidates.shuffle(rng);
+        let (r, c) = candidates
+            .into_iter()
+            .find(|&(r, c)| can_add_island(&grid, r, c))?;
+        grid.set(r, c, CellState::Island);
+    }
+    Some(grid)
+}
+
+fn block(row: usize, col: usize) -> [(usize, usize); 4] {
+    [
+        (row, col),
+        (row + 1, col),
+        (row, col + 1),
+        (row + 1, col + 1),
+    ]
+}
+
+/// Whether turning the river cell at `(row, col)` into island keeps every
+/// island small enough and the sea in one piece.
+fn can_add_island(grid: &Grid, row: usize, col: usize) -> bool {
+    let mut trial = grid.clone();
+    trial.set(row, col, CellState::Island);
+    // joining onto existing islands is fine as long as the result stays small
+    if trial.region_of(row, col).len() > MAX_ISLAND_SIZE {
+        return false;
+    }
+
+    let Some((r, c, _)) = trial
+        .orthogonal_neighbors(row, col)
+        .find(|&(_, _, state)| state == CellState::River)
+    else {
+        return true;
+    };
+    let sea = trial.region_of(r, c);
+    let total = trial
+        .iter()
+        .filter(|&(_, _, state)| state == CellState::River)
+        .count();
+    sea.len() == total
+}
+
+/// Places one clue in a random cell of each island of `solution`.
+fn place_clues(solution: &Grid, rng: &mut impl Rng) -> Grid {
+    let GridSize { rows, cols } = solution.grid_size;
+    let mut puzzle = Grid::new(solution.grid_size);
+    let mut seen = vec![vec![false; cols]; rows];
+    for row in 0..rows {
+        for col in 0..cols {
+            if seen[row][col] || solution.get(row, col) != CellState::Island {
+                continue;
+            }
+            let island = solution.region_of(row, col);
+            for &(r, c) in &island {
+                seen[r][c] = true;
+            }
+            let &(r, c) = island.choose(rng).unwrap();
+            puzzle.set(r, c, CellState::Value(island.len() as i8));
+        }
+    }
+    puzzle
+}
@@ -0,0 +1,898 @@
+use core::fmt;
+use std::{error::Error, str::Chars};
+
+use crate::code;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct GridSize {
+    pub rows: usize,
+    pub cols: usize,
+}
+
+#[derive(Clone)]
+pub struct Grid {
+    pub grid_size: GridSize,
+    /// Every cell, row by row from row 0.
+    cells: Vec<CellState>,
+    /// The cells the player has locked so they can't be changed by mistake,
+    /// in the same order as `cells`.
+    locked: Vec<bool>,
+}
+
+/// A group of island and clue cells connected to each other, as the player
+/// has marked them. Unlike `deduction::Island`, it can hold any number of
+/// clues, so broken islands can be pointed out.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Island {
+    /// The cells of the island, in the order they were found.
+    pub cells: Vec<(usize, usize)>,
+    /// The numbers of the clues inside the island.
+    pub clues: Vec<usize>,
+    /// Whether a blank cell touches the island, so it could still grow.
+    pub can_grow: bool,
+}
+
+impl Island {
+    /// Whether the island already breaks the rules: it is bigger than its
+    /// clue, has more than one clue, or has no clue and no room to grow.
+    pub fn is_broken(&self) -> bool {
+        match self.clues[..] {
+            [] => !self.can_grow,
+            [size] => self.cells.len() > size,
+            _ => true,
+        }
+    }
+
+    /// Whether the island is finished: it is the size of its only clue and
+    /// walled in by river.
+    pub fn is_complete(&self) -> bool {
+        self.clues == [self.cells.len()] && !self.can_grow
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum CellState {
+    Blank,
+    Island,
+    River,
+    Value(i8),
+    /// A pencil mark for a cell the player isn't sure about yet. It counts
+    /// as blank everywhere but on screen.
+    Maybe,
+}
+
+impl CellState {
+    pub fn next(&self) -> CellState {
+        match self {
+            CellState::Blank | CellState::Maybe => CellState::River,
+            CellState::Island => CellState::Blank,
+            CellState::River => CellState::Island,
+            _ => *self,
+        }
+    }
+
+    /// Puts a maybe mark on a cell that isn't a clue, or takes it off again.
+    pub fn next_maybe(&self) -> CellState {
+        match self {
+            CellState::Maybe => CellState::Blank,
+            CellState::Value(_) => *self,
+            _ => CellState::Maybe,
+        }
+    }
+
+    /// Whether the cell is still undecided, with or without a maybe mark.
+    pub fn is_blank(&self) -> bool {
+        matches!(self, CellState::Blank | CellState::Maybe)
+    }
+
+    pub fn is_same(&self, other: CellState) -> bool {
+        match self {
+            CellState::Blank | CellState::Value(_) | CellState::Island | CellState::Maybe => {
+                other != CellState::River
+            }
+            CellState::River => other == CellState::River,
+        }
+    }
+}
+
+impl fmt::Display for CellState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let c = match self {
+            CellState::Blank => ' ',
+            CellState::Island => '.',
+            CellState::River => 'X',
+            CellState::Maybe => '?',
+            CellState::Value(v) => return write!(f, "{}", v),
+        };
+        write!(f, "{}", c)
+    }
+}
+
+/// Why a puzz.link URL couldn't be read as a Nurikabe puzzle.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PuzzLinkError {
+    /// The URL is for some other kind of puzzle, or isn't a puzz.link URL.
+    NotNurikabe,
+    /// The width or height is missing, isn't a positive number, or makes a
+    /// grid too big to play.
+    BadSize,
+    /// The clue data uses a character the encoding doesn't have, or ends in
+    /// the middle of a clue.
+    BadClues,
+    /// A clue is a question mark, zero, or too big to play.
+    UnsupportedClue,
+}
+
+impl fmt::Display for PuzzLinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            PuzzLinkError::NotNurikabe => "not a puzz.link Nurikabe URL",
+            PuzzLinkError::BadSize => "missing or invalid grid size",
+            PuzzLinkError::BadClues => "invalid clue data",
+            PuzzLinkError::UnsupportedClue => "unknown, zero or oversized clue",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl Error for PuzzLinkError {}
+
+/// Why a janko.at puzzle couldn't be read.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum JankoError {
+    /// There is no problem block, or it has no rows.
+    NoProblem,
+    /// The rows of the problem aren't all the same length.
+    RaggedRows,
+    /// A cell in the problem is neither a clue nor a blank.
+    BadCell(String),
+}
+
+impl fmt::Display for JankoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JankoError::NoProblem => write!(f, "no problem block"),
+            JankoError::RaggedRows => write!(f, "problem rows have different lengths"),
+            JankoError::BadCell(cell) => write!(f, "invalid cell {:?} in the problem", cell),
+        }
+    }
+}
+
+impl Error for JankoError {}
+
+/// Draws the grid one row per line, starting with row 0. While every clue is
+/// a single digit each cell is one character; otherwise the cells are lined
+/// up in columns as wide as the longest clue, with `-` for a blank.
+impl fmt::Display for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} rows and {} cols",
+            &self.grid_size.rows, &self.grid_size.cols
+        )?;
+        let width = self
+            .cells
+            .iter()
+            .filter_map(|state| match state {
+                CellState::Value(v) => Some(v.to_string().len()),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(1);
+        for row in 0..self.grid_size.rows {
+            for (col, state) in self.row(row).iter().enumerate() {
+                if width == 1 {
+                    write!(f, "{}", state)?;
+                    continue;
+                }
+                if col > 0 {
+                    write!(f, " ")?;
+                }
+                let cell = match state {
+                    CellState::Blank => "-".to_string(),
+                    state => state.to_string(),
+                };
+                write!(f, "{:>width$}", cell)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Grid {
+    /// Creates a grid of the given size with every cell blank.
+    pub fn new(grid_size: GridSize) -> Self {
+        Grid::from_cells(
+            grid_size,
+            vec![CellState::Blank; grid_size.rows * grid_size.cols],
+        )
+    }
+
+    /// Creates a grid from its cells, listed row by row from row 0.
+    fn from_cells(grid_size: GridSize, cells: Vec<CellState>) -> Self {
+        debug_assert_eq!(cells.len(), grid_size.rows * grid_size.cols);
+        Grid {
+            grid_size,
+            locked: vec![false; cells.len()],
+            cells,
+        }
+    }
+
+    /// Reads a puzzle in the game's own file format, or returns `None` if the
+    /// text isn't in that format.
+    pub fn from_puzzle_string(str: &str) -> Option<Self> {
+        let mut lines = str.lines();
+        lines.next();
+        let mut numbers = lines
+            .next()?
+            .split(",")
+            .map(|n| n.trim().parse::<usize>().ok());
+        let grid_size = GridSize {
+            cols: numbers.next()??,
+            rows: numbers.next()??,
+        };
+        if grid_size.rows == 0 || grid_size.cols == 0 {
+            return None;
+        }
+        let mut grid = Grid::new(grid_size);
+        let lines = lines.skip(2).filter(|line| !line.trim().is_empty());
+        for line in lines {
+            let mut numbers = line.split(",").map(|n| n.trim().parse::<i8>().ok());
+            let size = numbers.next()??;
+            let row = usize::try_from(numbers.next()?? - 1).ok()?;
+            let col = usize::try_from(numbers.next()?? - 1).ok()?;
+            if row >= grid_size.rows || col >= grid_size.cols {
+                return None;
+            }
+            grid.set(row, col, CellState::Value(size));
+        }
+        Some(grid)
+    }
+
+    /// Reads a puzzle in the janko.at text format. Only the problem block is
+    /// used: one row per line, with cells separated by spaces and `-` for a
+    /// blank. Both `[problem]` sections and the older bare `problem` line are
+    /// understood, and the solution and other blocks are skipped.
+    pub fn from_janko_string(str: &str) -> Result<Self, JankoError> {
+        let mut grid: Vec<Vec<CellState>> = Vec::new();
+        let mut in_problem = false;
+        for line in str.lines() {
+            let line = line.trim();
+            let section = if line.starts_with('[') && line.ends_with(']') {
+                Some(&line[1..line.len() - 1])
+            } else {
+                ["begin", "problem", "solution", "moves", "end"]
+                    .contains(&line)
+                    .then_some(line)
+            };
+            if let Some(section) = section {
+                in_problem = section == "problem";
+                continue;
+            }
+            if !in_problem || line.is_empty() {
+                continue;
+            }
+            let row = line
+                .split_whitespace()
+                .map(|cell| match cell {
+                    "-" | "." => Ok(CellState::Blank),
+                    _ => cell
+                        .parse::<i8>()
+                        .ok()
+                        .filter(|&clue| clue > 0)
+                        .map(CellState::Value)
+                        .ok_or_else(|| JankoError::BadCell(cell.to_string())),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            grid.push(row);
+        }
+        let cols = grid.first().map_or(0, Vec::len);
+        if cols == 0 {
+            return Err(JankoError::NoProblem);
+        }
+        if grid.iter().any(|row| row.len() != cols) {
+            return Err(JankoError::RaggedRows);
+        }
+        let grid_size = GridSize {
+            rows: grid.len(),
+            cols,
+        };
+        Ok(Grid::from_cells(grid_size, grid.concat()))
+    }
+
+    /// Reads a puzzle from a puzz.link (pzprv3) URL such as
+    /// `https://puzz.link/p?nurikabe/5/5/g2h3...`, where the numbers are the
+    /// width and height and the rest lists the clues row by row from the top.
+    /// Like puzzle files, the first row of the URL becomes row 0.
+    pub fn from_puzz_link(url: &str) -> Result<Self, PuzzLinkError> {
+        let (_, query) = url
+            .trim()
+            .split_once('?')
+            .ok_or(PuzzLinkError::NotNurikabe)?;
+        let mut parts = query.split('/');
+        if parts.next() != Some("nurikabe") {
+            return Err(PuzzLinkError::NotNurikabe);
+        }
+        let mut size = || {
+            parts
+                .next()
+                .and_then(|n| n.parse::<usize>().ok())
+                .filter(|&n| n > 0)
+                .ok_or(PuzzLinkError::BadSize)
+        };
+        let grid_size = GridSize {
+            cols: size()?,
+            rows: size()?,
+        };
+        if grid_size.rows.saturating_mul(grid_size.cols) > code::MAX_CELLS {
+            return Err(PuzzLinkError::BadSize);
+        }
+        let body = parts.next().unwrap_or("");
+
+        let mut grid = Grid::new(grid_size);
+        let cells = grid_size.rows * grid_size.cols;
+        let mut cell = 0;
+        let mut chars = body.chars();
+        while cell < cells {
+            let Some(c) = chars.next() else {
+                break;
+            };
+            let clue = match c {
+                '0'..='9' | 'a'..='f' => c.to_digit(16),
+                '-' => Some(read_hex(&mut chars, 2)?),
+                '+' => Some(read_hex(&mut chars, 3)?),
+                '=' => Some(read_hex(&mut chars, 3)? + 4096),
+                '%' => Some(read_hex(&mut chars, 3)? + 8192),
+                '.' => return Err(PuzzLinkError::UnsupportedClue),
+                // g skips one cell, h two, and so on up to z
+                'g'..='z' => {
+                    cell += c.to_digit(36).unwrap() as usize - 16;
+                    None
+                }
+                _ => return Err(PuzzLinkError::BadClues),
+            };
+            if let Some(clue) = clue {
+                let clue = i8::try_from(clue)
+                    .ok()
+                    .filter(|&clue| clue > 0)
+                    .ok_or(PuzzLinkError::UnsupportedClue)?;
+                grid.cells[cell] = CellState::Value(clue);
+            }
+            cell += 1;
+        }
+        Ok(grid)
+    }
+
+    /// Writes the clues as a puzz.link URL that `from_puzz_link` reads back.
+    pub fn to_puzz_link(&self) -> String {
+        let mut body = String::new();
+        let mut blanks = 0;
+        for state in &self.cells {
+            let clue = match *state {
+                CellState::Value(v) if v < 16 => format!("{:x}", v),
+                CellState::Value(v) => format!("-{:x}", v),
+                _ => {
+                    blanks += 1;
+                    if blanks < 20 {
+                        continue;
+                    }
+                    String::new()
+                }
+            };
+            if blanks > 0 {
+                body.push(char::from_digit(15 + blanks, 36).unwrap());
+                blanks = 0;
+            }
+            body.push_str(&clue);
+        }
+        if blanks > 0 {
+            body.push(char::from_digit(15 + blanks, 36).unwrap());
+        }
+        format!(
+            "https://puzz.link/p?nurikabe/{}/{}/{}",
+            self.grid_size.cols, self.grid_size.rows, body
+        )
+    }
+
+    /// Writes the player's marks one character per cell, row by row from row
+    /// 0: `.` for an island, `X` for river, `?` for a maybe mark, and `-` for
+    /// a blank or a clue.
+    pub fn marks_string(&self) -> String {
+        self.cells
+            .iter()
+            .map(|state| match state {
+                CellState::Island => '.',
+                CellState::River => 'X',
+                CellState::Maybe => '?',
+                CellState::Blank | CellState::Value(_) => '-',
+            })
+            .collect()
+    }
+
+    /// Puts back marks written by `marks_string`, leaving the clues alone.
+    /// Returns `false` without changing anything if the marks don't fit the
+    /// grid.
+    pub fn apply_marks(&mut self, marks: &str) -> bool {
+        let states: Option<Vec<CellState>> = marks
+            .chars()
+            .map(|c| match c {
+                '.' => Some(CellState::Island),
+                'X' => Some(CellState::River),
+                '?' => Some(CellState::Maybe),
+                '-' => Some(CellState::Blank),
+                _ => None,
+            })
+            .collect();
+        let Some(states) = states.filter(|states| states.len() == self.cells.len()) else {
+            return false;
+        };
+        for (cell, state) in self.cells.iter_mut().zip(states) {
+            if !matches!(cell, CellState::Value(_)) {
+                *cell = state;
+            }
+        }
+        true
+    }
+
+    /// Whether the player has marked any cell.
+    pub fn has_marks(&self) -> bool {
+        self.cells.iter().any(|state| {
+            matches!(
+                state,
+                CellState::Island | CellState::River | CellState::Maybe
+            )
+        })
+    }
+
+    /// Writes which cells are locked one character per cell, in the same
+    /// order as `marks_string`: `L` for a locked cell and `-` for the rest.
+    pub fn locks_string(&self) -> String {
+        self.locked
+            .iter()
+            .map(|&locked| if locked { 'L' } else { '-' })
+            .collect()
+    }
+
+    /// Puts back locks written by `locks_string`. Returns `false` without
+    /// changing anything if the locks don't fit the grid.
+    pub fn apply_locks(&mut self, locks: &str) -> bool {
+        let locked: Option<Vec<bool>> = locks
+            .chars()
+            .map(|c| match c {
+                'L' => Some(true),
+                '-' => Some(false),
+                _ => None,
+            })
+            .collect();
+        let Some(locked) = locked.filter(|locked| locked.len() == self.locked.len()) else {
+            return false;
+        };
+        self.locked = locked;
+        true
+    }
+
+    /// Whether the player has locked any cell.
+    pub fn has_locks(&self) -> bool {
+        self.locked.iter().any(|&locked| locked)
+    }
+
+    pub fn is_locked(&self, row: usize, col: usize) -> bool {
+        self.locked[self.index(row, col)]
+    }
+
+    pub fn set_locked(&mut self, row: usize, col: usize, locked: bool) {
+        let index = self.index(row, col);
+        self.locked[index] = locked;
+    }
+
+    /// Returns a copy of the grid with every cell but the clues blank and
+    /// nothing locked.
+    pub fn clues_only(&self) -> Grid {
+        let mut grid = self.clone();
+        for state in grid.cells.iter_mut() {
+            if !matches!(state, CellState::Value(_)) {
+                *state = CellState::Blank;
+            }
+        }
+        grid.locked.fill(false);
+        grid
+    }
+
+    /// Where the cell at `(row, col)` is kept in `cells` and `locked`.
+    fn index(&self, row: usize, col: usize) -> usize {
+        assert!(
+            row < self.grid_size.rows && col < self.grid_size.cols,
+            "cell ({}, {}) is outside the grid",
+            row,
+            col
+        );
+        row * self.grid_size.cols + col
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> CellState {
+        self.cells[self.index(row, col)]
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, value: CellState) {
+        let index = self.index(row, col);
+        self.cells[index] = value;
+    }
+
+    /// Every cell of the grid, row by row from row 0.
+    pub fn cells(&self) -> &[CellState] {
+        &self.cells
+    }
+
+    /// Every cell with its row and column, row by row from row 0.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, CellState)> + '_ {
+        let cols = self.grid_size.cols;
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(i, &state)| (i / cols, i % cols, state))
+    }
+
+    /// Every cell with its row and column like `iter`, to be changed in
+    /// place.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, usize, &mut CellState)> + '_ {
+        let cols = self.grid_size.cols;
+        self.cells
+            .iter_mut()
+            .enumerate()
+            .map(move |(i, state)| (i / cols, i % cols, state))
+    }
+
+    /// The cells of one row, from column 0.
+    pub fn row(&self, row: usize) -> &[CellState] {
+        let start = self.index(row, 0);
+        &self.cells[start..start + self.grid_size.cols]
+    }
+
+    /// The cells of one column, from row 0.
+    pub fn column(&self, col: usize) -> impl Iterator<Item = CellState> + '_ {
+        let start = self.index(0, col);
+        self.cells
+            .iter()
+            .skip(start)
+            .step_by(self.grid_size.cols)
+            .copied()
+    }
+
+    /// Returns the in-bounds coordinates orthogonally adjacent to `(row, col)`.
+    pub fn neighbors(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+        self.orthogonal_neighbors(row, col)
+            .map(|(r, c, _)| (r, c))
+            .collect()
+    }
+
+    /// The in-bounds cells orthogonally adjacent to `(row, col)` with their
+    /// states: above, below, left, then right.
+    pub fn orthogonal_neighbors(
+        &self,
+        row: usize,
+        col: usize,
+    ) -> impl Iterator<Item = (usize, usize, CellState)> + '_ {
+        self.cells_around(row, col, [(-1, 0), (1, 0), (0, -1), (0, 1)])
+    }
+
+    /// The in-bounds cells diagonally adjacent to `(row, col)` with their
+    /// states: above left, above right, below left, then below right.
+    pub fn diagonal_neighbors(
+        &self,
+        row: usize,
+        col: usize,
+    ) -> impl Iterator<Item = (usize, usize, CellState)> + '_ {
+        self.cells_around(row, col, [(-1, -1), (-1, 1), (1, -1), (1, 1)])
+    }
+
+    /// The cells `offsets` away from `(row, col)` that are on the grid, in
+    /// the order of `offsets`.
+    fn cells_around(
+        &self,
+        row: usize,
+        col: usize,
+        offsets: [(isize, isize); 4],
+    ) -> impl Iterator<Item = (usize, usize, CellState)> + '_ {
+        offsets.into_iter().filter_map(move |(dr, dc)| {
+            let r = row.checked_add_signed(dr)?;
+            let c = col.checked_add_signed(dc)?;
+            (r < self.grid_size.rows && c < self.grid_size.cols).then(|| (r, c, self.get(r, c)))
+        })
+    }
+
+    /// Returns every river cell that is part of a 2x2 block of river, in
+    /// row-major order.
+    pub fn pools(&self) -> Vec<(usize, usize)> {
+        let GridSize { rows, cols } = self.grid_size;
+        let is_river = |row: usize, col: usize| self.get(row, col) == CellState::River;
+        let mut in_pool = vec![vec![false; cols]; rows];
+        for row in 0..rows.saturating_sub(1) {
+            for col in 0..cols.saturating_sub(1) {
+                if is_river(row, col)
+                    && is_river(row + 1, col)
+                    && is_river(row, col + 1)
+                    && is_river(row + 1, col + 1)
+                {
+                    for (r, c) in [
+                        (row, col),
+                        (row + 1, col),
+                        (row, col + 1),
+                        (row + 1, col + 1),
+                    ] {
+                        in_pool[r][c] = true;
+                    }
+                }
+            }
+        }
+        (0..rows)
+            .flat_map(|row| (0..cols).map(move |col| (row, col)))
+            .filter(|&(row, col)| in_pool[row][col])
+            .collect()
+    }
+
+    /// Finds every island on the board. Only island and clue cells count as
+    /// part of an island here, since blank cells could still become river.
+    pub fn islands(&self) -> Vec<Island> {
+        self.components_where(is_marked_island)
+            .into_iter()
+            .map(|cells| self.collect_island(cells))
+            .collect()
+    }
+
+    /// The island the cell at `(row, col)` is part of, or `None` if it isn't
+    /// an island or clue cell.
+    pub fn island_at(&self, row: usize, col: usize) -> Option<Island> {
+        if !self.is_marked_island(row, col) {
+            return None;
+        }
+        let GridSize { rows, cols } = self.grid_size;
+        let mut visited = vec![vec![false; cols]; rows];
+        let cells = self.flood_fill(row, col, &mut visited, &is_marked_island);
+        Some(self.collect_island(cells))
+    }
+
+    /// Returns every cell of the islands that already break the rules, in
+    /// row-major order.
+    pub fn bad_islands(&self) -> Vec<(usize, usize)> {
+        let mut bad: Vec<(usize, usize)> = self
+            .islands()
+            .into_iter()
+            .filter(Island::is_broken)
+            .flat_map(|island| island.cells)
+            .collect();
+        bad.sort_unstable();
+        bad
+    }
+
+    fn is_marked_island(&self, row: usize, col: usize) -> bool {
+        is_marked_island(self.get(row, col))
+    }
+
+    /// Gathers the clues of the island made of `cells`, and whether it has
+    /// room to grow.
+    fn collect_island(&self, cells: Vec<(usize, usize)>) -> Island {
+        let clues = cells
+            .iter()
+            .filter_map(|&(row, col)| match self.get(row, col) {
+                CellState::Value(v) => Some(v as usize),
+                _ => None,
+            })
+            .collect();
+        let can_grow = cells.iter().any(|&(row, col)| {
+            self.orthogonal_neighbors(row, col)
+                .any(|(_, _, state)| state.is_blank())
+        });
+        Island {
+            cells,
+            clues,
+            can_grow,
+        }
+    }
+
+    /// Returns the river cells that can no longer join up with the rest of
+    /// the sea, in row-major order. River cells are split into groups that
+    /// are connected through river or blank cells, and every group but the
+    /// one with the most river cells is cut off.
+    pub fn cut_off_sea(&self) -> Vec<(usize, usize)> {
+        let seas: Vec<Vec<(usize, usize)>> = self
+            .components_where(|state| {
+                matches!(
+                    state,
+                    CellState::River | CellState::Blank | CellState::Maybe
+                )
+            })
+            .into_iter()
+            .map(|open| {
+                open.into_iter()
+                    .filter(|&(row, col)| self.get(row, col) == CellState::River)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|sea| !sea.is_empty())
+            .collect();
+        let Some(largest) = (0..seas.len()).max_by_key(|&i| seas[i].len()) else {
+            return Vec::new();
+        };
+        let mut cut_off: Vec<(usize, usize)> = seas
+            .into_iter()
+            .enumerate()
+            .filter(|&(i, _)| i != largest)
+            .flat_map(|(_, sea)| sea)
+            .collect();
+        cut_off.sort_unstable();
+        cut_off
+    }
+
+    /// Checks the grid against the rules of Nurikabe rather than a stored
+    /// solution. Any cell that isn't river is treated as part of an island.
+    pub fn validate_rules(&self) -> bool {
+        // no 2x2 pools
+        if !self.pools().is_empty() {
+            return false;
+        }
+        // the sea must be a single connected region
+        if self.components(CellState::River).len() > 1 {
+            return false;
+        }
+        // each island holds exactly one clue, equal to its size
+        self.components(CellState::Island).iter().all(|region| {
+            let mut clues = region.iter().filter_map(|&(r, c)| match self.get(r, c) {
+                CellState::Value(v) => Some(v as usize),
+                _ => None,
+            });
+            matches!((clues.next(), clues.next()), (Some(size), None) if size == region.len())
+        })
+    }
+
+    /// The orthogonally connected region of cells that are the same kind as
+    /// the cell at `(row, col)`, river or not river, starting with that cell
+    /// and working outwards.
+    pub fn region_of(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+        let GridSize { rows, cols } = self.grid_size;
+        let mut visited = vec![vec![false; cols]; rows];
+        let kind = self.get(row, col);
+        self.flood_fill(row, col, &mut visited, &|state| kind.is_same(state))
+    }
+
+    /// Splits the cells of the same kind as `kind` into orthogonally
+    /// connected regions: the seas for `CellState::River`, and the islands,
+    /// counting unmarked cells, for anything else. Regions are found in
+    /// row-major order of their first cell.
+    pub fn components(&self, kind: CellState) -> Vec<Vec<(usize, usize)>> {
+        self.components_where(|state| kind.is_same(state))
+    }
+
+    /// Splits the cells whose state `include` holds for into orthogonally
+    /// connected regions, in row-major order of their first cell.
+    pub fn components_where(
+        &self,
+        include: impl Fn(CellState) -> bool,
+    ) -> Vec<Vec<(usize, usize)>> {
+        let GridSize { rows, cols } = self.grid_size;
+        let mut visited = vec![vec![false; cols]; rows];
+        let mut components = Vec::new();
+        for (row, col, state) in self.iter() {
+            if !visited[row][col] && include(state) {
+                components.push(self.flood_fill(row, col, &mut visited, &include));
+            }
+        }
+        components
+    }
+
+    /// Collects the orthogonally connected region of cells `include` holds
+    /// for, starting at `(row, col)`, nearest first. The generator picks
+    /// clue cells by their place in the region, so the order has to stay the
+    /// same for the daily puzzles to come out the same.
+    fn flood_fill(
+        &self,
+        row: usize,
+        col: usize,
+        visited: &mut [Vec<bool>],
+        include: &impl Fn(CellState) -> bool,
+    ) -> Vec<(usize, usize)> {
+        let mut region = vec![(row, col)];
+        visited[row][col] = true;
+        let mut i = 0;
+        while let Some(&(r, c)) = region.get(i) {
+            for (nr, nc, state) in self.orthogonal_neighbors(r, c) {
+                if !visited[nr][nc] && include(state) {
+                    visited[nr][nc] = true;
+                    region.push((nr, nc));
+                }
+            }
+            i += 1;
+        }
+        region
+    }
+}
+
+/// Whether a cell has been marked as part of an island, as an island cell
+/// or a clue. Blank cells could still go either way.
+fn is_marked_island(state: CellState) -> bool {
+    matches!(state, CellState::Island | CellState::Value(_))
+}
+
+/// Reads a clue written as `len` hex digits.
+fn read_hex(chars: &mut Chars, len: usize) -> Result<u32, PuzzLinkError> {
+    let digits: String = chars.take(len).collect();
+    if digits.len() != len {
+        return Err(PuzzLinkError::BadClues);
+    }
+    u32::from_str_radix(&digits, 16).map_err(|_| PuzzLinkError::BadClues)
+}
+
+// grid = vec![vec!;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{self, Format};
+
+    /// Clues of one and two hex digits, with a run of more than twenty
+    /// blanks between them.
+    fn sample() -> Grid {
+        let mut grid = Grid::new(GridSize { rows: 5, cols: 6 });
+        grid.set(0, 0, CellState::Value(3));
+        grid.set(0, 5, CellState::Value(17));
+        grid.set(4, 4, CellState::Value(12));
+        grid
+    }
+
+    #[test]
+    fn puzz_link_round_trip() {
+        let grid = sample();
+        let read = Grid::from_puzz_link(&grid.to_puzz_link()).unwrap();
+        assert_eq!(read.grid_size, grid.grid_size);
+        assert_eq!(read.cells(), grid.cells());
+    }
+
+    #[test]
+    fn puzz_link_reads_the_first_row_as_row_0() {
+        let grid = Grid::from_puzz_link("https://puzz.link/p?nurikabe/3/2/2h1g").unwrap();
+        assert_eq!(grid.grid_size, GridSize { rows: 2, cols: 3 });
+        assert_eq!(grid.get(0, 0), CellState::Value(2));
+        assert_eq!(grid.get(1, 0), CellState::Value(1));
+        assert_eq!(
+            grid.cells()
+                .iter()
+                .filter(|&&state| state == CellState::Blank)
+                .count(),
+            4
+        );
+    }
+
+    #[test]
+    fn puzz_link_rejects_other_puzzles() {
+        assert_eq!(
+            Grid::from_puzz_link("https://puzz.link/p?heyawake/3/2/2h1g").err(),
+            Some(PuzzLinkError::NotNurikabe)
+        );
+    }
+
+    #[test]
+    fn janko_round_trip() {
+        let grid = sample();
+        let read = Grid::from_janko_string(&format::write(&grid, Format::Janko)).unwrap();
+        assert_eq!(read.grid_size, grid.grid_size);
+        assert_eq!(read.cells(), grid.cells());
+    }
+
+    #[test]
+    fn janko_skips_the_solution() {
+        let text = "[problem]\n2 -\n- -\n[solution]\n2 2\nx x\n";
+        let grid = Grid::from_janko_string(text).unwrap();
+        assert_eq!(grid.grid_size, GridSize { rows: 2, cols: 2 });
+        assert_eq!(grid.get(0, 0), CellState::Value(2));
+    }
+
+    #[test]
+    fn marks_round_trip() {
+        let mut grid = sample();
+        grid.set(0, 1, CellState::Island);
+        grid.set(1, 1, CellState::River);
+        grid.set(2, 3, CellState::Maybe);
+        let mut read = grid.clues_only();
+        assert!(read.apply_marks(&grid.marks_string()));
+        assert_eq!(read.cells(), grid.cells());
+    }
+}
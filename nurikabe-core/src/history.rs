@@ -0,0 +1,56 @@
+use crate::CellState;
+
+/// A single cell changing from one state to another.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Move {
+    pub row: usize,
+    pub col: usize,
+    pub from: CellState,
+    pub to: CellState,
+}
+
+impl Move {
+    /// The move that takes the cell back to where it was.
+    pub fn inverse(&self) -> Self {
+        Move {
+            from: self.to,
+            to: self.from,
+            ..*self
+        }
+    }
+}
+
+/// Undo and redo stacks for the current puzzle. Each entry is one player
+/// action, which may change several cells at once (like a reset).
+#[derive(Clone, Default, Debug)]
+pub struct History {
+    undo: Vec<Vec<Move>>,
+    redo: Vec<Vec<Move>>,
+}
+
+impl History {
+    /// Records a new action, which also forgets anything that was undone.
+    pub fn record(&mut self, moves: Vec<Move>) {
+        if moves.is_empty() {
+            return;
+        }
+        self.undo.push(moves);
+        self.redo.clear();
+    }
+
+    /// Takes the last action off the undo stack and returns the moves that
+    /// revert it, in the order they should be applied.
+    pub fn undo(&mut self) -> Option<Vec<Move>> {
+        let moves = self.undo.pop()?;
+        let reverted = moves.iter().rev().map(Move::inverse).collect();
+        self.redo.push(moves);
+        Some(reverted)
+    }
+
+    /// Takes the last undone action and returns its moves again.
+    pub fn redo(&mut self) -> Option<Vec<Move>> {
+        let moves = self.redo.pop()?;
+        self.undo.push(moves.clone());
+        Some(moves)
+    }
+}
@@ -0,0 +1,20 @@
+//! The rules of Nurikabe, with no engine attached: the grid and the text
+//! formats it is read from, a rule checker that keeps up with each move, a
+//! solver and hint finder, a checker for newly written puzzles, difficulty
+//! ratings and a puzzle generator, solve replays, and a renderer for saving
+//! boards as PNG images. The game front end lives in the `nurikabe` crate.
+
+pub mod check;
+pub mod code;
+pub mod deduction;
+pub mod difficulty;
+pub mod format;
+pub mod generator;
+pub mod grid;
+pub mod history;
+pub mod render;
+pub mod replay;
+pub mod rules;
+pub mod solver;
+
+pub use grid::CellState;
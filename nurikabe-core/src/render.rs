@@ -0,0 +1,150 @@
+use std::io::Write;
+
+use crate::{
+    grid::{Grid, GridSize},
+    CellState,
+};
+
+const LINE_WIDTH: u32 = 2;
+const LINE_SHADE: u8 = 0x60;
+const BLANK_SHADE: u8 = 0xff;
+const RIVER_SHADE: u8 = 0x22;
+const ISLAND_DOT_SHADE: u8 = 0x80;
+const MAYBE_DOT_SHADE: u8 = 0xc0;
+const CLUE_SHADE: u8 = 0x00;
+
+/// The digits 0 to 9 in a 3x5 pixel font, one byte per row from the top,
+/// with the leftmost pixel in bit 2.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// An 8-bit grayscale picture, stored row by row from the top.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    fn new(width: u32, height: u32, shade: u8) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![shade; (width * height) as usize],
+        }
+    }
+
+    /// Fills a rectangle with `shade`, cut off at the edges of the picture.
+    fn fill(&mut self, x: u32, y: u32, width: u32, height: u32, shade: u8) {
+        if x >= self.width {
+            return;
+        }
+        for row in y..(y + height).min(self.height) {
+            let start = (row * self.width + x) as usize;
+            let end = (row * self.width + (x + width).min(self.width)) as usize;
+            self.pixels[start..end].fill(shade);
+        }
+    }
+
+    /// Encodes the picture as a PNG file.
+    pub fn write_png(&self, writer: impl Write) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)
+    }
+}
+
+/// Draws `grid` the way it looks on the board: clues as numbers, river cells
+/// dark and island cells with a dot, with row 0 at the bottom. Each cell is
+/// `cell_size` pixels across.
+pub fn render(grid: &Grid, cell_size: u32) -> Image {
+    let GridSize { rows, cols } = grid.grid_size;
+    let step = cell_size + LINE_WIDTH;
+    let mut image = Image::new(
+        cols as u32 * step + LINE_WIDTH,
+        rows as u32 * step + LINE_WIDTH,
+        LINE_SHADE,
+    );
+    for row in 0..rows {
+        for col in 0..cols {
+            let x = col as u32 * step + LINE_WIDTH;
+            let y = (rows - 1 - row) as u32 * step + LINE_WIDTH;
+            match grid.get(row, col) {
+                CellState::River => image.fill(x, y, cell_size, cell_size, RIVER_SHADE),
+                CellState::Blank => image.fill(x, y, cell_size, cell_size, BLANK_SHADE),
+                state @ (CellState::Island | CellState::Maybe) => {
+                    image.fill(x, y, cell_size, cell_size, BLANK_SHADE);
+                    let dot = (cell_size / 6).max(1);
+                    let offset = (cell_size - dot) / 2;
+                    let shade = if state == CellState::Island {
+                        ISLAND_DOT_SHADE
+                    } else {
+                        MAYBE_DOT_SHADE
+                    };
+                    image.fill(x + offset, y + offset, dot, dot, shade);
+                }
+                CellState::Value(v) => {
+                    image.fill(x, y, cell_size, cell_size, BLANK_SHADE);
+                    draw_number(&mut image, x, y, cell_size, v.unsigned_abs().into());
+                }
+            }
+        }
+    }
+    image
+}
+
+/// Draws `number` centered in the cell whose top left corner is at `(x, y)`.
+fn draw_number(image: &mut Image, x: u32, y: u32, cell_size: u32, number: u32) {
+    let digits: Vec<usize> = number
+        .to_string()
+        .bytes()
+        .map(|b| (b - b'0') as usize)
+        .collect();
+    // each digit is 3 wide with a gap of 1 between digits, and 5 tall
+    let units_wide = 4 * digits.len() as u32 - 1;
+    let room = cell_size * 3 / 5;
+    let scale = (room / units_wide).min(room / 5).max(1);
+    let left = x + cell_size.saturating_sub(units_wide * scale) / 2;
+    let top = y + cell_size.saturating_sub(5 * scale) / 2;
+    for (i, &digit) in digits.iter().enumerate() {
+        for (dy, bits) in DIGITS[digit].iter().enumerate() {
+            for dx in 0..3 {
+                if bits & (0b100 >> dx) != 0 {
+                    image.fill(
+                        left + (4 * i as u32 + dx) * scale,
+                        top + dy as u32 * scale,
+                        scale,
+                        scale,
+                        CLUE_SHADE,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_clue_in_the_last_column_stays_inside_the_picture() {
+        let mut grid = Grid::new(GridSize { rows: 2, cols: 2 });
+        grid.set(0, 1, CellState::Value(100));
+        for cell_size in 1..=12 {
+            let image = render(&grid, cell_size);
+            assert_eq!(image.pixels.len(), (image.width * image.height) as usize);
+        }
+    }
+}
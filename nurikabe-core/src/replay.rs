@@ -0,0 +1,288 @@
+use core::fmt;
+use std::error::Error;
+
+use crate::{
+    code::{self, CodeError},
+    grid::{Grid, GridSize},
+    history::Move,
+    CellState,
+};
+
+/// The extension of replay files, which hold the text written by `encode`.
+pub const REPLAY_EXTENSION: &str = "replay";
+
+/// Why a shared replay couldn't be read.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReplayError {
+    /// The puzzle or the moves aren't written in the code alphabet, or are
+    /// cut short.
+    Code(CodeError),
+    /// There is no `.` between the puzzle and the moves.
+    NoMoves,
+    /// A mark or a move is off the board, on a clue, or has a state that
+    /// doesn't exist.
+    BadMove,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Code(err) => write!(f, "{}", err),
+            ReplayError::NoMoves => write!(f, "the replay has a puzzle but no moves"),
+            ReplayError::BadMove => write!(f, "the replay has a move that doesn't fit the puzzle"),
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+impl From<CodeError> for ReplayError {
+    fn from(err: CodeError) -> Self {
+        ReplayError::Code(err)
+    }
+}
+
+/// A move made while solving, and how long into the solve it was made.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ReplayMove {
+    pub change: Move,
+    pub seconds: f32,
+}
+
+/// Every cell change made on the way to solving a puzzle, undos and resets
+/// included, so the solve can be watched again from the board it started on.
+#[derive(Clone)]
+pub struct Replay {
+    /// The board when recording started, with any marks it already had.
+    pub start: Grid,
+    pub moves: Vec<ReplayMove>,
+}
+
+impl Replay {
+    pub fn new(start: &Grid) -> Self {
+        Replay {
+            start: start.clone(),
+            moves: Vec::new(),
+        }
+    }
+
+    /// Adds a move made `seconds` into the solve.
+    pub fn record(&mut self, change: Move, seconds: f32) {
+        self.moves.push(ReplayMove { change, seconds });
+    }
+
+    /// Writes the moves as `row,col,from to,seconds`, separated by `;`, with
+    /// the states written the way `Grid::marks_string` writes them.
+    pub fn moves_string(&self) -> String {
+        self.moves
+            .iter()
+            .map(|ReplayMove { change, seconds }| {
+                format!(
+                    "{},{},{}{},{:.2}",
+                    change.row,
+                    change.col,
+                    mark_char(change.from),
+                    mark_char(change.to),
+                    seconds
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+
+    /// Reads moves written by `moves_string` into a replay starting from
+    /// `start`. Returns `None` if any move is malformed or lands off the
+    /// board or on a clue.
+    pub fn from_moves_string(start: &Grid, moves: &str) -> Option<Self> {
+        let mut replay = Replay::new(start);
+        for text in moves.split(';').filter(|text| !text.is_empty()) {
+            let mut parts = text.split(',');
+            let row: usize = parts.next()?.parse().ok()?;
+            let col: usize = parts.next()?.parse().ok()?;
+            let mut states = parts.next()?.chars().map(mark_state);
+            let (from, to) = (states.next()??, states.next()??);
+            let seconds = parts.next()?.parse().ok()?;
+            if parts.next().is_some()
+                || states.next().is_some()
+                || row >= start.grid_size.rows
+                || col >= start.grid_size.cols
+                || matches!(start.get(row, col), CellState::Value(_))
+            {
+                return None;
+            }
+            replay.record(Move { row, col, from, to }, seconds);
+        }
+        Some(replay)
+    }
+
+    /// How long the recorded solve took, up to its last move.
+    pub fn seconds(&self) -> f32 {
+        self.moves.last().map_or(0.0, |last| last.seconds)
+    }
+}
+
+/// Packs `replay` into one line of text for sharing: the share code of the
+/// puzzle, a `.`, then the starting marks and the moves as bytes in the same
+/// alphabet. The marks come as their count and then, for each, the cells
+/// skipped since the one before and its state. Each move after them is its
+/// cell, its two states in one byte, and the hundredths of a second since
+/// the move before.
+pub fn encode(replay: &Replay) -> String {
+    let cols = replay.start.grid_size.cols;
+    let mut bytes = Vec::new();
+    let marks: Vec<(usize, u8)> = replay
+        .start
+        .cells()
+        .iter()
+        .enumerate()
+        .map(|(cell, &state)| (cell, state_bits(state)))
+        .filter(|&(_, bits)| bits != 0)
+        .collect();
+    code::push_varint(&mut bytes, marks.len());
+    let mut last_cell = 0;
+    for (cell, bits) in marks {
+        code::push_varint(&mut bytes, cell - last_cell);
+        bytes.push(bits);
+        last_cell = cell;
+    }
+    let mut last_time = 0;
+    for ReplayMove { change, seconds } in &replay.moves {
+        let time = (seconds * 100.0).round() as usize;
+        code::push_varint(&mut bytes, change.row * cols + change.col);
+        bytes.push(state_bits(change.from) << 2 | state_bits(change.to));
+        code::push_varint(&mut bytes, time.saturating_sub(last_time));
+        last_time = time.max(last_time);
+    }
+    format!("{}.{}", code::encode(&replay.start), code::to_text(&bytes))
+}
+
+/// Reads a replay written by `encode`.
+pub fn decode(text: &str) -> Result<Replay, ReplayError> {
+    let (puzzle, moves) = text.trim().split_once('.').ok_or(ReplayError::NoMoves)?;
+    let mut start = code::decode(puzzle)?;
+    let GridSize { rows, cols } = start.grid_size;
+    let is_playable = |grid: &Grid, cell: usize| {
+        cell < rows * cols && !matches!(grid.get(cell / cols, cell % cols), CellState::Value(_))
+    };
+    let mut bytes = code::from_text(moves)?.into_iter().peekable();
+    let marks = code::read_varint(&mut bytes)?;
+    let mut cell = 0usize;
+    for _ in 0..marks {
+        cell = cell.saturating_add(code::read_varint(&mut bytes)?);
+        let bits = bytes.next().ok_or(CodeError::Truncated)?;
+        if !is_playable(&start, cell) || bits > 3 {
+            return Err(ReplayError::BadMove);
+        }
+        start.set(cell / cols, cell % cols, bits_state(bits));
+    }
+    let mut replay = Replay::new(&start);
+    let mut time = 0usize;
+    while bytes.peek().is_some() {
+        let cell = code::read_varint(&mut bytes)?;
+        let states = bytes.next().ok_or(CodeError::Truncated)?;
+        time = time.saturating_add(code::read_varint(&mut bytes)?);
+        if !is_playable(&start, cell) || states > 0xf {
+            return Err(ReplayError::BadMove);
+        }
+        let change = Move {
+            row: cell / cols,
+            col: cell % cols,
+            from: bits_state(states >> 2),
+            to: bits_state(states & 3),
+        };
+        replay.record(change, time as f32 / 100.0);
+    }
+    Ok(replay)
+}
+
+/// The two bits a state is packed into by `encode`.
+fn state_bits(state: CellState) -> u8 {
+    match state {
+        CellState::Blank | CellState::Value(_) => 0,
+        CellState::Island => 1,
+        CellState::River => 2,
+        CellState::Maybe => 3,
+    }
+}
+
+fn bits_state(bits: u8) -> CellState {
+    match bits {
+        1 => CellState::Island,
+        2 => CellState::River,
+        3 => CellState::Maybe,
+        _ => CellState::Blank,
+    }
+}
+
+fn mark_char(state: CellState) -> char {
+    match state {
+        CellState::Island => '.',
+        CellState::River => 'X',
+        CellState::Maybe => '?',
+        CellState::Blank | CellState::Value(_) => '-',
+    }
+}
+
+fn mark_state(c: char) -> Option<CellState> {
+    match c {
+        '.' => Some(CellState::Island),
+        'X' => Some(CellState::River),
+        '?' => Some(CellState::Maybe),
+        '-' => Some(CellState::Blank),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Replay {
+        let mut start = Grid::new(GridSize { rows: 3, cols: 4 });
+        start.set(0, 0, CellState::Value(2));
+        start.set(2, 3, CellState::Value(1));
+        start.set(1, 1, CellState::River);
+        let mut replay = Replay::new(&start);
+        let moves = [
+            (0, 1, CellState::Blank, CellState::Island, 1.5),
+            (1, 0, CellState::Blank, CellState::Maybe, 2.25),
+            (1, 0, CellState::Maybe, CellState::River, 2.25),
+            (1, 1, CellState::River, CellState::Blank, 10.0),
+        ];
+        for (row, col, from, to, seconds) in moves {
+            replay.record(Move { row, col, from, to }, seconds);
+        }
+        replay
+    }
+
+    fn assert_same(read: &Replay, replay: &Replay) {
+        assert_eq!(read.start.grid_size, replay.start.grid_size);
+        assert_eq!(read.start.cells(), replay.start.cells());
+        assert_eq!(read.moves, replay.moves);
+    }
+
+    #[test]
+    fn round_trip() {
+        let replay = sample();
+        assert_same(&decode(&encode(&replay)).unwrap(), &replay);
+    }
+
+    #[test]
+    fn moves_string_round_trip() {
+        let replay = sample();
+        let read = Replay::from_moves_string(&replay.start, &replay.moves_string()).unwrap();
+        assert_same(&read, &replay);
+    }
+
+    #[test]
+    fn rejects_moves_that_dont_fit() {
+        let replay = sample();
+        assert!(matches!(
+            decode(&code::encode(&replay.start)),
+            Err(ReplayError::NoMoves)
+        ));
+        // a move onto the clue in the corner
+        assert!(Replay::from_moves_string(&replay.start, "0,0,-X,1.00").is_none());
+        assert!(Replay::from_moves_string(&replay.start, "3,0,-X,1.00").is_none());
+    }
+}
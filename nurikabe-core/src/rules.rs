@@ -0,0 +1,363 @@
+use crate::{
+    grid::{Grid, GridSize},
+    CellState,
+};
+
+/// Checks a grid against the rules of Nurikabe like `Grid::validate_rules`,
+/// but is kept up to date one cell at a time instead of walking the whole
+/// board after every change.
+///
+/// The cells are split into connected regions of river and of everything
+/// else, held in a union-find structure. Changing a cell only looks at the
+/// 2x2 blocks around it and the regions it leaves and joins. Joining regions
+/// is a union; a region is only walked again when taking the cell out of it
+/// could split it in two.
+#[derive(Clone)]
+pub struct RuleChecker {
+    grid_size: GridSize,
+    /// Whether each cell is river, row by row from row 0.
+    river: Vec<bool>,
+    /// The clue in each cell, or 0 for none.
+    clues: Vec<usize>,
+    /// The union-find node each cell belongs to. A cell gets a new node
+    /// whenever it changes region, so nodes are never taken apart.
+    node: Vec<usize>,
+    /// The parent of each node, or the node itself for the root of a region.
+    parent: Vec<usize>,
+    /// What is known about the region of each root node.
+    regions: Vec<Region>,
+    /// Marks the cells a walk has reached: a cell is reached when its entry
+    /// equals `stamp`, so nothing has to be cleared between walks.
+    seen: Vec<u32>,
+    stamp: u32,
+    /// How many 2x2 blocks are all river.
+    pools: usize,
+    river_regions: usize,
+    /// How many regions that aren't river don't have exactly one clue equal
+    /// to their size.
+    bad_islands: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Region {
+    river: bool,
+    size: usize,
+    clues: usize,
+    /// The clues of the region added together.
+    clue_total: usize,
+}
+
+impl Region {
+    fn is_bad_island(&self) -> bool {
+        !self.river && (self.clues != 1 || self.clue_total != self.size)
+    }
+}
+
+impl RuleChecker {
+    pub fn new(grid: &Grid) -> Self {
+        let GridSize { rows, cols } = grid.grid_size;
+        let mut checker = RuleChecker {
+            grid_size: grid.grid_size,
+            river: Vec::with_capacity(rows * cols),
+            clues: Vec::with_capacity(rows * cols),
+            node: Vec::new(),
+            parent: Vec::new(),
+            regions: Vec::new(),
+            seen: vec![0; rows * cols],
+            stamp: 0,
+            pools: 0,
+            river_regions: 0,
+            bad_islands: 0,
+        };
+        for &state in grid.cells() {
+            checker.river.push(state == CellState::River);
+            checker.clues.push(clue(state));
+        }
+        checker.rebuild();
+        checker
+    }
+
+    /// Whether the grid follows every rule, with the same answer
+    /// `Grid::validate_rules` would give.
+    pub fn is_solved(&self) -> bool {
+        self.pools == 0 && self.bad_islands == 0 && self.river_regions <= 1
+    }
+
+    /// Brings the checker up to date with the cell at `(row, col)` changing
+    /// to `state`.
+    pub fn set(&mut self, row: usize, col: usize, state: CellState) {
+        let cell = row * self.grid_size.cols + col;
+        let river = state == CellState::River;
+        if self.river[cell] == river && self.clues[cell] == clue(state) {
+            return;
+        }
+        self.count_pools_around(row, col, false);
+        self.remove(cell);
+        self.river[cell] = river;
+        self.clues[cell] = clue(state);
+        self.count_pools_around(row, col, true);
+        self.insert(cell);
+
+        // every change leaves a few nodes behind, so start over once there
+        // are far more nodes than cells
+        if self.parent.len() > 4 * self.river.len() + 64 {
+            self.rebuild();
+        }
+    }
+
+    /// Works out the regions and pools from scratch.
+    fn rebuild(&mut self) {
+        let cells = self.river.len();
+        self.node = (0..cells).collect();
+        self.parent = (0..cells).collect();
+        self.regions = (0..cells)
+            .map(|cell| Region {
+                river: self.river[cell],
+                size: 1,
+                clues: usize::from(self.clues[cell] > 0),
+                clue_total: self.clues[cell],
+            })
+            .collect();
+        self.pools = 0;
+        self.river_regions = 0;
+        self.bad_islands = 0;
+        for cell in 0..cells {
+            self.tally(cell, true);
+        }
+        let GridSize { rows, cols } = self.grid_size;
+        for row in 0..rows {
+            for col in 0..cols {
+                let cell = row * cols + col;
+                if row + 1 < rows && self.river[cell] == self.river[cell + cols] {
+                    self.union(cell, cell + cols);
+                }
+                if col + 1 < cols && self.river[cell] == self.river[cell + 1] {
+                    self.union(cell, cell + 1);
+                }
+                if self.is_pool(row, col) {
+                    self.pools += 1;
+                }
+            }
+        }
+    }
+
+    /// Takes `cell` out of its region, splitting the region up if that cut
+    /// it in two.
+    fn remove(&mut self, cell: usize) {
+        let root = self.find(self.node[cell]);
+        self.tally(root, false);
+        let region = &mut self.regions[root];
+        region.size -= 1;
+        if self.clues[cell] > 0 {
+            region.clues -= 1;
+            region.clue_total -= self.clues[cell];
+        }
+        if region.size == 0 {
+            return;
+        }
+        let kin: Vec<usize> = self
+            .neighbors(cell)
+            .filter(|&neighbor| self.river[neighbor] == self.river[cell])
+            .collect();
+        // a cell with only one neighbor in the region can't be holding it
+        // together
+        if kin.len() <= 1 || self.still_connected(cell, &kin) {
+            self.tally(root, true);
+            return;
+        }
+        self.next_stamp();
+        self.seen[cell] = self.stamp;
+        for &start in &kin {
+            if self.seen[start] == self.stamp {
+                continue;
+            }
+            let piece = self.walk(start, None);
+            let region = Region {
+                river: self.river[start],
+                size: piece.len(),
+                clues: piece.iter().filter(|&&c| self.clues[c] > 0).count(),
+                clue_total: piece.iter().map(|&c| self.clues[c]).sum(),
+            };
+            let node = self.new_node(region);
+            for c in piece {
+                self.node[c] = node;
+            }
+            self.tally(node, true);
+        }
+    }
+
+    /// Puts `cell` in a region of its own, then joins it up with the
+    /// neighbors of the same kind.
+    fn insert(&mut self, cell: usize) {
+        let node = self.new_node(Region {
+            river: self.river[cell],
+            size: 1,
+            clues: usize::from(self.clues[cell] > 0),
+            clue_total: self.clues[cell],
+        });
+        self.node[cell] = node;
+        self.tally(node, true);
+        let kin: Vec<usize> = self
+            .neighbors(cell)
+            .filter(|&neighbor| self.river[neighbor] == self.river[cell])
+            .collect();
+        for neighbor in kin {
+            self.union(cell, neighbor);
+        }
+    }
+
+    /// Whether the cells in `kin` can still reach each other without going
+    /// through `removed`. Stops as soon as they have all been reached, which
+    /// is usually right around the removed cell.
+    fn still_connected(&mut self, removed: usize, kin: &[usize]) -> bool {
+        self.next_stamp();
+        self.seen[removed] = self.stamp;
+        self.walk(kin[0], Some(kin));
+        kin.iter().all(|&cell| self.seen[cell] == self.stamp)
+    }
+
+    /// Collects the cells of the same kind connected to `start` that haven't
+    /// been seen under the current stamp. With `targets`, stops once every
+    /// target has been seen.
+    fn walk(&mut self, start: usize, targets: Option<&[usize]>) -> Vec<usize> {
+        let river = self.river[start];
+        let mut cells = vec![start];
+        let mut stack = vec![start];
+        self.seen[start] = self.stamp;
+        let mut left = targets.map_or(0, |targets| {
+            targets
+                .iter()
+                .filter(|&&target| self.seen[target] != self.stamp)
+                .count()
+        });
+        while let Some(cell) = stack.pop() {
+            if targets.is_some() && left == 0 {
+                break;
+            }
+            let neighbors: Vec<usize> = self.neighbors(cell).collect();
+            for neighbor in neighbors {
+                if self.seen[neighbor] == self.stamp || self.river[neighbor] != river {
+                    continue;
+                }
+                self.seen[neighbor] = self.stamp;
+                if targets.is_some_and(|targets| targets.contains(&neighbor)) {
+                    left -= 1;
+                }
+                cells.push(neighbor);
+                stack.push(neighbor);
+            }
+        }
+        cells
+    }
+
+    fn next_stamp(&mut self) {
+        self.stamp = self.stamp.wrapping_add(1);
+        if self.stamp == 0 {
+            self.seen.fill(0);
+            self.stamp = 1;
+        }
+    }
+
+    fn new_node(&mut self, region: Region) -> usize {
+        let node = self.parent.len();
+        self.parent.push(node);
+        self.regions.push(region);
+        node
+    }
+
+    fn find(&mut self, mut node: usize) -> usize {
+        while self.parent[node] != node {
+            self.parent[node] = self.parent[self.parent[node]];
+            node = self.parent[node];
+        }
+        node
+    }
+
+    /// Joins the regions of two neighboring cells of the same kind.
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(self.node[a]), self.find(self.node[b]));
+        if a == b {
+            return;
+        }
+        self.tally(a, false);
+        self.tally(b, false);
+        let (big, small) = if self.regions[a].size >= self.regions[b].size {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        let small_region = self.regions[small];
+        let region = &mut self.regions[big];
+        region.size += small_region.size;
+        region.clues += small_region.clues;
+        region.clue_total += small_region.clue_total;
+        self.parent[small] = big;
+        self.tally(big, true);
+    }
+
+    /// Adds the region with root `root` to the counts, or takes it off them.
+    fn tally(&mut self, root: usize, add: bool) {
+        let region = self.regions[root];
+        let count = if region.river {
+            &mut self.river_regions
+        } else if region.is_bad_island() {
+            &mut self.bad_islands
+        } else {
+            return;
+        };
+        if add {
+            *count += 1;
+        } else {
+            *count -= 1;
+        }
+    }
+
+    /// Adds the pools among the 2x2 blocks holding `(row, col)` to the
+    /// count, or takes them off it.
+    fn count_pools_around(&mut self, row: usize, col: usize, add: bool) {
+        for top in row.saturating_sub(1)..=row {
+            for left in col.saturating_sub(1)..=col {
+                if !self.is_pool(top, left) {
+                    continue;
+                }
+                if add {
+                    self.pools += 1;
+                } else {
+                    self.pools -= 1;
+                }
+            }
+        }
+    }
+
+    /// Whether the 2x2 block starting at `(row, col)` is all river.
+    fn is_pool(&self, row: usize, col: usize) -> bool {
+        let GridSize { rows, cols } = self.grid_size;
+        if row + 1 >= rows || col + 1 >= cols {
+            return false;
+        }
+        let cell = row * cols + col;
+        [cell, cell + 1, cell + cols, cell + cols + 1]
+            .iter()
+            .all(|&cell| self.river[cell])
+    }
+
+    fn neighbors(&self, cell: usize) -> impl Iterator<Item = usize> {
+        let GridSize { rows, cols } = self.grid_size;
+        let (row, col) = (cell / cols, cell % cols);
+        [
+            (row > 0).then(|| cell - cols),
+            (row + 1 < rows).then(|| cell + cols),
+            (col > 0).then(|| cell - 1),
+            (col + 1 < cols).then(|| cell + 1),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+fn clue(state: CellState) -> usize {
+    match state {
+        CellState::Value(v) if v > 0 => v as usize,
+        _ => 0,
+    }
+}
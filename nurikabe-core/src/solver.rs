@@ -0,0 +1,231 @@
+use crate::{
+    deduction::{self, blank_neighbors, islands, Contradiction, Reason},
+    grid::Grid,
+    CellState,
+};
+
+/// Solves the puzzle described by the clues in `grid` by backtracking,
+/// ignoring any marks the player has made. The returned grid contains no
+/// blank cells.
+pub fn solve(grid: &Grid) -> Option<Grid> {
+    solve_partial(&grid.clues_only())
+}
+
+/// Solves a grid the player has partially filled in, keeping their marks.
+/// Returns `None` if the marks can't be part of any solution.
+pub fn solve_partial(grid: &Grid) -> Option<Grid> {
+    solve_with_stats(grid).0
+}
+
+/// How much guessing a search took.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct SolveStats {
+    /// How many times no deduction applied and a cell had to be guessed.
+    pub guesses: usize,
+    /// How many guesses turned out wrong and were taken back.
+    pub backtracks: usize,
+}
+
+/// Solves a grid like `solve_partial`, also counting the guesses it took.
+pub fn solve_with_stats(grid: &Grid) -> (Option<Grid>, SolveStats) {
+    let mut solution = None;
+    let mut stats = SolveStats::default();
+    search(grid.clone(), &mut stats, &mut |grid| {
+        solution = Some(grid.clone());
+        true
+    });
+    (solution, stats)
+}
+
+/// A cell the player can fill in next. `reason` explains the deduction that
+/// forces it, or is `None` if no technique applies and the answer comes from
+/// a full solution instead.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Hint {
+    pub row: usize,
+    pub col: usize,
+    pub state: CellState,
+    pub reason: Option<Reason>,
+}
+
+impl Hint {
+    /// A sentence telling the player why the hint is right.
+    pub fn explanation(&self) -> String {
+        match self.reason {
+            Some(reason) => reason.to_string(),
+            None => "No simple technique applies here, but trying the other option for this cell eventually leads to a contradiction.".to_string(),
+        }
+    }
+}
+
+/// Finds a cell the player can safely fill in given their marks so far.
+/// Fails if the marks already contain a mistake, and returns `None` once there
+/// are no blank cells left.
+pub fn hint(grid: &Grid) -> Result<Option<Hint>, Contradiction> {
+    if let Some(deduction) = deduction::next_deductions(grid)?.first() {
+        return Ok(Some(Hint {
+            row: deduction.row,
+            col: deduction.col,
+            state: deduction.state,
+            reason: Some(deduction.reason),
+        }));
+    }
+    let solution = solve_partial(grid).ok_or(Contradiction)?;
+    Ok(grid
+        .iter()
+        .find(|(_, _, state)| state.is_blank())
+        .map(|(row, col, _)| Hint {
+            row,
+            col,
+            state: solution.get(row, col),
+            reason: None,
+        }))
+}
+
+/// Counts the distinct solutions of the puzzle described by the clues in
+/// `grid`, stopping once `limit` have been found.
+pub fn count_solutions(grid: &Grid, limit: usize) -> usize {
+    let mut count = 0;
+    if limit > 0 {
+        search(grid.clues_only(), &mut SolveStats::default(), &mut |_| {
+            count += 1;
+            count >= limit
+        });
+    }
+    count
+}
+
+/// Finds up to `limit` distinct solutions of the puzzle described by the
+/// clues in `grid`.
+pub fn solutions(grid: &Grid, limit: usize) -> Vec<Grid> {
+    let mut found = Vec::new();
+    if limit > 0 {
+        search(
+            grid.clues_only(),
+            &mut SolveStats::default(),
+            &mut |solution| {
+                found.push(solution.clone());
+                found.len() >= limit
+            },
+        );
+    }
+    found
+}
+
+/// Explores every completion of `grid`, calling `on_solution` for each valid
+/// one until it returns `true`. Returns whether the search was stopped early.
+fn search(
+    mut grid: Grid,
+    stats: &mut SolveStats,
+    on_solution: &mut impl FnMut(&Grid) -> bool,
+) -> bool {
+    if deduction::propagate(&mut grid).is_err() {
+        return false;
+    }
+    let Some((row, col)) = choose_cell(&grid) else {
+        return grid.validate_rules() && on_solution(&grid);
+    };
+    stats.guesses += 1;
+    for state in [CellState::Island, CellState::River] {
+        let mut next = grid.clone();
+        next.set(row, col, state);
+        if search(next, stats, on_solution) {
+            return true;
+        }
+        stats.backtracks += 1;
+    }
+    false
+}
+
+/// Picks the blank cell to branch on: a neighbor of the unfinished island
+/// with the fewest ways to grow, or any blank cell if there is none.
+fn choose_cell(grid: &Grid) -> Option<(usize, usize)> {
+    let best = islands(grid)
+        .unwrap_or_default()
+        .iter()
+        .filter(|island| island.clue.is_some() && !island.is_complete())
+        .map(|island| blank_neighbors(grid, &island.cells))
+        .filter(|blanks| !blanks.is_empty())
+        .min_by_key(|blanks| blanks.len())
+        .map(|blanks| blanks[0]);
+    best.or_else(|| {
+        grid.iter()
+            .find(|(_, _, state)| state.is_blank())
+            .map(|(row, col, _)| (row, col))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        format::{self, Format},
+        grid::GridSize,
+    };
+
+    /// The first puzzle of the "first steps" pack.
+    const FIRST_STEPS: &str = "# size: width then height\n5,5\n\n# rooms: size, row , column\n1,2,1\n2,2,4\n1,3,5\n2,4,2\n1,5,4\n";
+
+    fn grid_with_clues(grid_size: GridSize, clues: &[(usize, usize, i8)]) -> Grid {
+        let mut grid = Grid::new(grid_size);
+        for &(row, col, v) in clues {
+            grid.set(row, col, CellState::Value(v));
+        }
+        grid
+    }
+
+    #[test]
+    fn solves_a_single_island() {
+        let grid = grid_with_clues(GridSize { rows: 2, cols: 2 }, &[(0, 0, 1)]);
+        let solution = solve(&grid).unwrap();
+        assert_eq!(
+            solution.cells(),
+            [
+                CellState::Value(1),
+                CellState::River,
+                CellState::River,
+                CellState::River
+            ]
+        );
+    }
+
+    #[test]
+    fn solves_a_pack_puzzle() {
+        let grid = format::parse(FIRST_STEPS, Format::Legacy).unwrap();
+        let solution = solve(&grid).unwrap();
+        assert!(solution.validate_rules());
+        assert!(!solution.cells().contains(&CellState::Blank));
+        for (cell, &state) in grid.cells().iter().enumerate() {
+            if let CellState::Value(_) = state {
+                assert_eq!(solution.cells()[cell], state);
+            }
+        }
+        assert_eq!(count_solutions(&grid, 2), 1);
+    }
+
+    #[test]
+    fn keeps_marks_that_fit_and_rejects_ones_that_dont() {
+        let grid = grid_with_clues(GridSize { rows: 2, cols: 2 }, &[(0, 0, 1)]);
+        let mut marked = grid.clone();
+        marked.set(1, 1, CellState::River);
+        assert!(solve_partial(&marked).is_some());
+        marked.set(0, 1, CellState::Island);
+        assert!(solve_partial(&marked).is_none());
+    }
+
+    #[test]
+    fn counts_every_solution() {
+        // the island of 2 can go right or down
+        let grid = grid_with_clues(GridSize { rows: 2, cols: 2 }, &[(0, 0, 2)]);
+        assert_eq!(count_solutions(&grid, 5), 2);
+        assert_eq!(count_solutions(&grid, 1), 1);
+        assert_eq!(solutions(&grid, 5).len(), 2);
+    }
+
+    #[test]
+    fn finds_no_solution_for_touching_clues() {
+        let grid = grid_with_clues(GridSize { rows: 2, cols: 2 }, &[(0, 0, 1), (0, 1, 1)]);
+        assert!(solve(&grid).is_none());
+        assert_eq!(count_solutions(&grid, 1), 0);
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    f64::consts::{PI, TAU},
+    time::Duration,
+};
+
+use bevy::{
+    audio::{AddAudioSource, AudioSourceBundle, Decodable, PitchBundle, Source, Volume},
+    prelude::*,
+};
+
+use crate::{settings::Settings, GameState};
+
+/// How loud the cursor ticks are next to the other sounds, since they play on
+/// every move.
+const CURSOR_MOVE_VOLUME: f32 = 0.3;
+const MUSIC_SAMPLE_RATE: u32 = 44100;
+/// The chords the background music drifts through, as the frequencies of
+/// their notes in hertz: A minor, F, C and G.
+const MUSIC_CHORDS: [[f64; 3]; 4] = [
+    [220.0, 261.63, 329.63],
+    [174.61, 220.0, 261.63],
+    [196.0, 261.63, 329.63],
+    [196.0, 246.94, 293.66],
+];
+/// How long each chord of the background music swells and fades for. Each
+/// chord starts halfway through the one before, so the music never dips.
+const MUSIC_CHORD_SECONDS: f64 = 6.0;
+/// How loud the music is at full volume, kept low so it stays in the
+/// background.
+const MUSIC_LOUDNESS: f64 = 0.15;
+/// How long the music takes to fade out on a win, and back in after.
+const MUSIC_FADE_SECONDS: f32 = 2.0;
+
+/// A sound the game plays in response to the player.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Sound {
+    CursorMove,
+    MarkSea,
+    MarkIsland,
+    /// Trying to change a clue or a locked cell.
+    Invalid,
+    Win,
+}
+
+impl Sound {
+    /// The notes of the sound, played one after another, as a frequency in
+    /// hertz and a length in seconds. The sounds are made up of plain tones,
+    /// so the game doesn't need any audio files.
+    fn notes(&self) -> &'static [(f32, f32)] {
+        match self {
+            Sound::CursorMove => &[(880.0, 0.03)],
+            Sound::MarkSea => &[(330.0, 0.08)],
+            Sound::MarkIsland => &[(523.25, 0.08)],
+            Sound::Invalid => &[(110.0, 0.06), (98.0, 0.12)],
+            Sound::Win => &[
+                (523.25, 0.12),
+                (659.25, 0.12),
+                (783.99, 0.12),
+                (1046.5, 0.3),
+            ],
+        }
+    }
+
+    fn volume(&self) -> f32 {
+        match self {
+            Sound::CursorMove => CURSOR_MOVE_VOLUME,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Sent by the input systems to play a sound.
+#[derive(Event)]
+pub struct PlaySound(pub Sound);
+
+/// The rest of a sound with more than one note, played as each note ends.
+#[derive(Component)]
+struct Melody {
+    sound: Sound,
+    next: usize,
+    timer: Timer,
+}
+
+/// Ambient background music, made up on the fly from soft chords. It never
+/// ends, so it loops without a seam.
+#[derive(Asset, TypePath)]
+struct Music;
+
+impl Decodable for Music {
+    type DecoderItem = f32;
+    type Decoder = MusicDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        MusicDecoder { sample: 0 }
+    }
+}
+
+struct MusicDecoder {
+    sample: u64,
+}
+
+impl Iterator for MusicDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let t = self.sample as f64 / MUSIC_SAMPLE_RATE as f64;
+        self.sample += 1;
+        let half = MUSIC_CHORD_SECONDS / 2.0;
+        let current = (t / half) as usize;
+        let mut value = 0.0;
+        // the chord that started last and the one before it are both sounding
+        for chord in current.saturating_sub(1)..=current {
+            let age = t - chord as f64 * half;
+            if age >= MUSIC_CHORD_SECONDS {
+                continue;
+            }
+            let envelope = (age / MUSIC_CHORD_SECONDS * PI).sin().powi(2);
+            let notes = MUSIC_CHORDS[chord % MUSIC_CHORDS.len()];
+            let tone: f64 = notes
+                .iter()
+                .map(|frequency| (TAU * frequency * t).sin())
+                .sum();
+            value += envelope * tone / notes.len() as f64;
+        }
+        Some((value * MUSIC_LOUDNESS) as f32)
+    }
+}
+
+impl Source for MusicDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        MUSIC_SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// The entity playing the background music, and how far it has faded in.
+#[derive(Component)]
+struct BackgroundMusic {
+    fade: f32,
+}
+
+/// Plays [`PlaySound`] events and the background music at the volumes in
+/// [`Settings`]. Expects a [`Settings`] resource to be present.
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<Music>()
+            .add_event::<PlaySound>()
+            .add_systems(Startup, start_music)
+            .add_systems(
+                Update,
+                (
+                    apply_master_volume.run_if(resource_changed::<Settings>),
+                    (play_sounds, play_melodies).chain(),
+                    fade_music,
+                ),
+            );
+    }
+}
+
+fn start_music(mut commands: Commands, mut music: ResMut<Assets<Music>>) {
+    commands.spawn((
+        AudioSourceBundle {
+            source: music.add(Music),
+            // faded in by `fade_music`
+            settings: PlaybackSettings::LOOP.with_volume(Volume::new(0.0)),
+        },
+        BackgroundMusic { fade: 0.0 },
+    ));
+}
+
+/// Fades the music out while a puzzle is won and back in otherwise, at the
+/// music volume scaled by the master volume.
+fn fade_music(
+    mut music: Query<(&mut BackgroundMusic, &AudioSink)>,
+    game_state: Res<State<GameState>>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    let Ok((mut music, sink)) = music.get_single_mut() else {
+        return;
+    };
+    let target = if *game_state.get() == GameState::Won {
+        0.0
+    } else {
+        1.0
+    };
+    let step = time.delta_seconds() / MUSIC_FADE_SECONDS;
+    music.fade = if music.fade < target {
+        (music.fade + step).min(target)
+    } else {
+        (music.fade - step).max(target)
+    };
+    let volume = if settings.music_muted {
+        0.0
+    } else {
+        settings.master_volume * settings.music_volume * music.fade
+    };
+    if sink.volume() != volume {
+        sink.set_volume(volume);
+    }
+}
+
+/// The master volume scales every sound, through bevy's global volume.
+fn apply_master_volume(settings: Res<Settings>, mut global_volume: ResMut<GlobalVolume>) {
+    global_volume.volume = Volume::new(settings.master_volume);
+}
+
+fn play_sounds(
+    mut commands: Commands,
+    mut sound_ev: EventReader<PlaySound>,
+    mut pitches: ResMut<Assets<Pitch>>,
+    settings: Res<Settings>,
+) {
+    for PlaySound(sound) in sound_ev.read() {
+        if settings.sfx_volume <= 0.0 {
+            continue;
+        }
+        play_note(&mut commands, &mut pitches, &settings, *sound, 0);
+        if let Some(&(_, seconds)) = sound.notes().first() {
+            if sound.notes().len() > 1 {
+                commands.spawn(Melody {
+                    sound: *sound,
+                    next: 1,
+                    timer: Timer::from_seconds(seconds, TimerMode::Once),
+                });
+            }
+        }
+    }
+}
+
+fn play_melodies(
+    mut commands: Commands,
+    mut melodies: Query<(Entity, &mut Melody)>,
+    mut pitches: ResMut<Assets<Pitch>>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    for (entity, mut melody) in &mut melodies {
+        if !melody.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        play_note(
+            &mut commands,
+            &mut pitches,
+            &settings,
+            melody.sound,
+            melody.next,
+        );
+        melody.next += 1;
+        match melody.sound.notes().get(melody.next - 1) {
+            Some(&(_, seconds)) if melody.next < melody.sound.notes().len() => {
+                melody.timer = Timer::from_seconds(seconds, TimerMode::Once);
+            }
+            _ => commands.entity(entity).despawn(),
+        }
+    }
+}
+
+fn play_note(
+    commands: &mut Commands,
+    pitches: &mut Assets<Pitch>,
+    settings: &Settings,
+    sound: Sound,
+    note: usize,
+) {
+    let Some(&(frequency, seconds)) = sound.notes().get(note) else {
+        return;
+    };
+    commands.spawn(PitchBundle {
+        source: pitches.add(Pitch::new(frequency, Duration::from_secs_f32(seconds))),
+        settings: PlaybackSettings::DESPAWN
+            .with_volume(Volume::new(settings.sfx_volume * sound.volume())),
+    });
+}
@@ -0,0 +1,46 @@
+//! Work too slow to do within a frame, like generating a big puzzle or
+//! downloading one, run on one of Bevy's task pools and picked up by a
+//! system once it is done. The browser has no threads to run it on, so there
+//! the work is done at once instead.
+
+use bevy::tasks::TaskPool;
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::tasks::{block_on, futures_lite::future::poll_once, Task};
+
+/// Work running away from the frame, with its result waiting for `poll`.
+pub struct Job<T> {
+    #[cfg(not(target_arch = "wasm32"))]
+    task: Task<T>,
+    #[cfg(target_arch = "wasm32")]
+    result: Option<T>,
+}
+
+impl<T: Send + 'static> Job<T> {
+    /// Starts `work` on `pool`: the async compute pool for work that keeps
+    /// the processor busy, or the IO pool for work that mostly waits.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn(pool: &TaskPool, work: impl FnOnce() -> T + Send + 'static) -> Self {
+        Job {
+            task: pool.spawn(async move { work() }),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn spawn(_pool: &TaskPool, work: impl FnOnce() -> T + Send + 'static) -> Self {
+        Job {
+            result: Some(work()),
+        }
+    }
+
+    /// The result of the work, the first time this is called after it is
+    /// done.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn poll(&mut self) -> Option<T> {
+        block_on(poll_once(&mut self.task))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn poll(&mut self) -> Option<T> {
+        self.result.take()
+    }
+}
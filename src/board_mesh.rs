@@ -0,0 +1,187 @@
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology, VertexAttributeValues},
+        render_asset::RenderAssetUsages,
+    },
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
+use nurikabe_core::grid::GridSize;
+
+use crate::{
+    cell_position, settings::Settings, start_puzzle, theme::Theme, tile_index, BoardSize, Cell,
+    GridComponent, TileSheet,
+};
+
+/// Boards with at least this many cells are drawn as a single mesh instead of
+/// a sprite per cell, which keeps 50x50 boards and up smooth.
+const BATCHED_BOARD_CELLS: usize = 2500;
+
+/// Whether a board of `grid_size` is drawn as one mesh. The cells of such a
+/// board are still entities with a transform, so everything placed on top of
+/// them and every system finding cells by their grid position work the same,
+/// but they have no sprite of their own.
+pub fn is_batched(grid_size: &GridSize) -> bool {
+    grid_size.rows * grid_size.cols >= BATCHED_BOARD_CELLS
+}
+
+/// The mesh the tiles of a large board are drawn with, one quad per cell in
+/// row-major order.
+#[derive(Component)]
+struct BoardMesh;
+
+/// Draws large boards as one mesh, kept in step with the cells. Expects the
+/// board rendering resources to be present.
+pub struct BoardMeshPlugin;
+
+impl Plugin for BoardMeshPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                spawn_board_mesh.after(start_puzzle).run_if(
+                    resource_changed::<BoardSize>
+                        .or_else(resource_changed::<Settings>)
+                        .or_else(resource_changed::<TileSheet>),
+                ),
+                update_board_mesh,
+                apply_board_mesh_theme
+                    .run_if(resource_changed::<Theme>.or_else(resource_changed::<TileSheet>)),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Builds the mesh for the board from scratch whenever the board, the cell
+/// size or the tile sheet changes.
+fn spawn_board_mesh(
+    mut commands: Commands,
+    assets: (ResMut<Assets<Mesh>>, ResMut<Assets<ColorMaterial>>),
+    layouts: Res<Assets<TextureAtlasLayout>>,
+    board: (Res<BoardSize>, Res<TileSheet>, Res<Theme>, Res<Settings>),
+    cells: Query<(&Cell, &GridComponent)>,
+    old_meshes: Query<Entity, With<BoardMesh>>,
+) {
+    let (mut meshes, mut materials) = assets;
+    let (grid_size, tile_sheet, theme, settings) = board;
+    for entity in &old_meshes {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !is_batched(&grid_size) {
+        return;
+    }
+    let Some(layout) = layouts.get(&tile_sheet.layout) else {
+        return;
+    };
+    let GridSize { rows, cols } = **grid_size;
+    let half = settings.cell_size / 2.0;
+    let mut positions = Vec::with_capacity(rows * cols * 4);
+    let mut indices = Vec::with_capacity(rows * cols * 6);
+    for row in 0..rows {
+        for col in 0..cols {
+            let center = cell_position(&grid_size, settings.cell_size, row, col);
+            let first = positions.len() as u32;
+            for corner in [
+                Vec2::new(-half, -half),
+                Vec2::new(half, -half),
+                Vec2::new(half, half),
+                Vec2::new(-half, half),
+            ] {
+                positions.push((center + corner).extend(0.0).to_array());
+            }
+            indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+        }
+    }
+    let mut uvs = vec![[0.0; 2]; rows * cols * 4];
+    for (cell, location) in &cells {
+        set_tile_uvs(&mut uvs, layout, cols, location, cell);
+    }
+    let mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        vec![[0.0, 0.0, 1.0]; positions.len()],
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_indices(Indices::U32(indices));
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: Mesh2dHandle(meshes.add(mesh)),
+            material: materials.add(ColorMaterial {
+                color: theme.tile,
+                texture: Some(tile_sheet.texture_for(&theme).clone()),
+            }),
+            ..default()
+        },
+        BoardMesh,
+    ));
+}
+
+/// Redraws the tiles of the cells that changed.
+fn update_board_mesh(
+    mut meshes: ResMut<Assets<Mesh>>,
+    layouts: Res<Assets<TextureAtlasLayout>>,
+    tile_sheet: Res<TileSheet>,
+    grid_size: Res<BoardSize>,
+    board_mesh: Query<&Mesh2dHandle, With<BoardMesh>>,
+    cells: Query<(&Cell, &GridComponent), Changed<Cell>>,
+) {
+    if cells.is_empty() {
+        return;
+    }
+    let Ok(handle) = board_mesh.get_single() else {
+        return;
+    };
+    let (Some(mesh), Some(layout)) = (meshes.get_mut(&handle.0), layouts.get(&tile_sheet.layout))
+    else {
+        return;
+    };
+    let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0)
+    else {
+        return;
+    };
+    for (cell, location) in &cells {
+        set_tile_uvs(uvs, layout, grid_size.cols, location, cell);
+    }
+}
+
+/// Tints the board mesh and picks its tile sheet for the theme.
+fn apply_board_mesh_theme(
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    tile_sheet: Res<TileSheet>,
+    theme: Res<Theme>,
+    board_mesh: Query<&Handle<ColorMaterial>, With<BoardMesh>>,
+) {
+    for handle in &board_mesh {
+        if let Some(material) = materials.get_mut(handle) {
+            material.color = theme.tile;
+            material.texture = Some(tile_sheet.texture_for(&theme).clone());
+        }
+    }
+}
+
+/// Points the quad of the cell at `location` at the tile for its state.
+fn set_tile_uvs(
+    uvs: &mut [[f32; 2]],
+    layout: &TextureAtlasLayout,
+    cols: usize,
+    location: &GridComponent,
+    cell: &Cell,
+) {
+    let rect = layout.textures[tile_index(cell.0)].as_rect();
+    let size = layout.size.as_vec2();
+    let (min, max) = (rect.min / size, rect.max / size);
+    let first = (location.row * cols + location.col) * 4;
+    // the image has its top at v = 0, while the quad's corners go up from
+    // the bottom left
+    uvs[first..first + 4].copy_from_slice(&[
+        [min.x, max.y],
+        [max.x, max.y],
+        [max.x, min.y],
+        [min.x, min.y],
+    ]);
+}
@@ -0,0 +1,283 @@
+//! The daily puzzle calendar: a month at a time, showing which days' puzzles
+//! have been solved, and playing the puzzle of any day up to today.
+
+use bevy::{prelude::*, sprite::Anchor};
+
+use crate::{
+    daily::{DailyProgress, Date},
+    difficulty::DifficultyCache,
+    hide_panel,
+    input::{self, Action, Actions},
+    show_panel, GameState, PuzzleGeneration, MENU_SIZE, UI_LAYER,
+};
+
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const WEEKDAY_NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+/// The distance between the middles of two days on the calendar.
+const CALENDAR_STEP: f32 = 70.0;
+const CALENDAR_DAY_SIZE: f32 = 62.0;
+
+/// The day picked on the calendar. The calendar shows its month.
+#[derive(Resource)]
+struct Calendar {
+    selected: Date,
+}
+
+impl Default for Calendar {
+    fn default() -> Self {
+        Calendar {
+            selected: Date::today(),
+        }
+    }
+}
+
+/// The backdrop of the calendar, shown while it is open.
+#[derive(Component)]
+struct CalendarPanel;
+
+/// A day of the calendar or its text, redrawn whenever the selection or the
+/// progress changes.
+#[derive(Component)]
+struct CalendarDay;
+
+/// The calendar page, opened from the main menu.
+pub struct CalendarPlugin;
+
+impl Plugin for CalendarPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Calendar>()
+            .add_systems(Startup, spawn_calendar_panel)
+            .add_systems(
+                Update,
+                (
+                    calendar_input
+                        .after(input::read_actions)
+                        .run_if(in_state(GameState::Calendar)),
+                    update_calendar.run_if(
+                        resource_changed::<Calendar>.or_else(resource_changed::<DailyProgress>),
+                    ),
+                ),
+            )
+            .add_systems(
+                OnEnter(GameState::Calendar),
+                (open_calendar, show_panel::<CalendarPanel>),
+            )
+            .add_systems(OnExit(GameState::Calendar), hide_panel::<CalendarPanel>);
+    }
+}
+
+/// The weekday of `date`, counting from Monday as 0.
+fn weekday(date: Date) -> i64 {
+    // 1970-01-01 was a Thursday
+    (date.days() + 3).rem_euclid(7)
+}
+
+/// The first day of the month `date` is in.
+fn first_of_month(date: Date) -> Date {
+    Date { day: 1, ..date }
+}
+
+fn days_in_month(date: Date) -> i64 {
+    let next_month = if date.month == 12 {
+        Date {
+            year: date.year + 1,
+            month: 1,
+            day: 1,
+        }
+    } else {
+        Date {
+            month: date.month + 1,
+            day: 1,
+            ..date
+        }
+    };
+    next_month.days() - first_of_month(date).days()
+}
+
+fn spawn_calendar_panel(mut commands: Commands) {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::srgba(0.0, 0.0, 0.0, 0.85),
+                custom_size: Some(MENU_SIZE),
+                ..default()
+            },
+            transform: Transform::from_translation(Vec3::new(0.0, 0.0, 4.0)),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        CalendarPanel,
+        UI_LAYER,
+    ));
+}
+
+fn open_calendar(mut calendar: ResMut<Calendar>) {
+    calendar.selected = Date::today();
+}
+
+/// Moves the selected day with the movement keys, a week at a time up and
+/// down, and plays its puzzle with Enter. Days after today can't be picked.
+fn calendar_input(
+    actions: Res<Actions>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    difficulty_cache: Res<DifficultyCache>,
+    mut calendar: ResMut<Calendar>,
+    mut generation: ResMut<PuzzleGeneration>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if actions.triggered(Action::Quit) {
+        next_state.set(GameState::Menu);
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Enter) || actions.triggered(Action::Toggle) {
+        generation.start_daily(calendar.selected, &difficulty_cache);
+        return;
+    }
+    let times = |action| actions.times(action) as i64;
+    let step = times(Action::MoveRight) - times(Action::MoveLeft)
+        + 7 * (times(Action::MoveDown) - times(Action::MoveUp));
+    if step == 0 {
+        return;
+    }
+    let today = Date::today().days();
+    let selected = (calendar.selected.days() + step).min(today);
+    calendar.selected = Date::from_days(selected);
+}
+
+fn update_calendar(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    calendar: Res<Calendar>,
+    daily_progress: Res<DailyProgress>,
+    panel: Query<Entity, With<CalendarPanel>>,
+    days: Query<Entity, With<CalendarDay>>,
+) {
+    for entity in &days {
+        commands.entity(entity).despawn_recursive();
+    }
+    let selected = calendar.selected;
+    let today = Date::today();
+    let first = first_of_month(selected);
+    let length = days_in_month(selected);
+    let weeks = (weekday(first) + length + 6) / 7;
+    // the calendar sits in the middle of the panel, with the text above and
+    // below it
+    let top_left = Vec2::new(
+        -3.0 * CALENDAR_STEP,
+        (weeks - 1) as f32 * CALENDAR_STEP / 2.0,
+    );
+    let font = asset_server.load("FiraSans-Regular.ttf");
+    let label = |text: &str, size: f32| Text2dBundle {
+        text: Text::from_section(
+            text,
+            TextStyle {
+                font: font.clone(),
+                font_size: size,
+                ..default()
+            },
+        )
+        .with_justify(JustifyText::Center),
+        transform: Transform::from_translation(Vec3::Z),
+        ..default()
+    };
+    let status = if daily_progress.is_completed(selected) {
+        "solved"
+    } else if selected == today {
+        "today's puzzle, not solved yet"
+    } else {
+        "not solved yet"
+    };
+    let header = format!(
+        "Daily puzzles\n{} {}",
+        MONTH_NAMES[selected.month as usize - 1],
+        selected.year
+    );
+    let footer = format!(
+        "{}: {}\nArrows to choose a day, Enter to play it, Esc to go back",
+        selected, status
+    );
+    commands.entity(panel.single()).with_children(|builder| {
+        builder.spawn((
+            Text2dBundle {
+                text_anchor: Anchor::BottomCenter,
+                transform: Transform::from_xyz(0.0, top_left.y + CALENDAR_STEP * 1.2, 1.0),
+                ..label(&header, 30.0)
+            },
+            CalendarDay,
+            UI_LAYER,
+        ));
+        builder.spawn((
+            Text2dBundle {
+                text_anchor: Anchor::TopCenter,
+                transform: Transform::from_xyz(0.0, -top_left.y - CALENDAR_STEP * 0.8, 1.0),
+                ..label(&footer, 24.0)
+            },
+            CalendarDay,
+            UI_LAYER,
+        ));
+        for (column, name) in WEEKDAY_NAMES.iter().enumerate() {
+            let position = top_left + Vec2::new(column as f32 * CALENDAR_STEP, CALENDAR_STEP * 0.7);
+            builder.spawn((
+                Text2dBundle {
+                    transform: Transform::from_translation(position.extend(1.0)),
+                    ..label(name, 20.0)
+                },
+                CalendarDay,
+                UI_LAYER,
+            ));
+        }
+        for offset in 0..length {
+            let date = Date::from_days(first.days() + offset);
+            let cell = weekday(first) + offset;
+            let position = top_left
+                + Vec2::new(
+                    (cell % 7) as f32 * CALENDAR_STEP,
+                    -((cell / 7) as f32) * CALENDAR_STEP,
+                );
+            let color = if date > today {
+                Color::srgba(0.3, 0.3, 0.3, 0.3)
+            } else if daily_progress.is_completed(date) {
+                Color::srgb(0.2, 0.6, 0.3)
+            } else if date == today {
+                Color::srgb(0.3, 0.4, 0.8)
+            } else {
+                Color::srgb(0.35, 0.35, 0.35)
+            };
+            let size = if date == selected {
+                CALENDAR_DAY_SIZE + 8.0
+            } else {
+                CALENDAR_DAY_SIZE
+            };
+            builder
+                .spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color,
+                            custom_size: Some(Vec2::splat(size)),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(position.extend(1.0)),
+                        ..default()
+                    },
+                    CalendarDay,
+                    UI_LAYER,
+                ))
+                .with_children(|builder| {
+                    builder.spawn((label(&date.day.to_string(), 24.0), UI_LAYER));
+                });
+        }
+    });
+}
@@ -0,0 +1,84 @@
+use std::{
+    io::{self, Write},
+    process::{Command, Output, Stdio},
+};
+
+/// Programs that put their input on the system clipboard, tried in order
+/// until one of them is installed: macOS, Windows, Wayland, then X11.
+const COPY_COMMANDS: [(&str, &[&str]); 5] = [
+    ("pbcopy", &[]),
+    ("clip", &[]),
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+];
+
+/// Programs that print the contents of the system clipboard, in the same
+/// order as `COPY_COMMANDS`.
+const PASTE_COMMANDS: [(&str, &[&str]); 5] = [
+    ("pbpaste", &[]),
+    ("powershell", &["-NoProfile", "-Command", "Get-Clipboard"]),
+    ("wl-paste", &["--no-newline"]),
+    ("xclip", &["-selection", "clipboard", "-out"]),
+    ("xsel", &["--clipboard", "--output"]),
+];
+
+/// Puts `text` on the system clipboard. Bevy can't reach the clipboard, so
+/// this hands the text to whichever clipboard program is available.
+pub fn copy(text: &str) -> io::Result<()> {
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no clipboard program found");
+    for (program, args) in COPY_COMMANDS {
+        match run_with_input(program, args, text) {
+            Ok(()) => return Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => last_err = err,
+        }
+    }
+    Err(last_err)
+}
+
+/// Reads the text on the system clipboard, the same way `copy` writes it.
+pub fn paste() -> io::Result<String> {
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no clipboard program found");
+    for (program, args) in PASTE_COMMANDS {
+        let output = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output();
+        match output {
+            Ok(Output { status, stdout, .. }) if status.success() => {
+                return Ok(String::from_utf8_lossy(&stdout).into_owned());
+            }
+            Ok(Output { status, .. }) => {
+                last_err = io::Error::other(format!("{} failed with {}", program, status));
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => last_err = err,
+        }
+    }
+    Err(last_err)
+}
+
+fn run_with_input(program: &str, args: &[&str], input: &str) -> io::Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(input.as_bytes())?;
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{} failed with {}",
+            program, status
+        )))
+    }
+}
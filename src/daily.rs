@@ -0,0 +1,141 @@
+use core::fmt;
+use std::{collections::BTreeSet, str::FromStr};
+
+// bevy's `SystemTime` also works in the browser, where the standard one
+// panics
+use bevy::{prelude::*, utils::SystemTime};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use nurikabe_core::{
+    generator,
+    grid::{Grid, GridSize},
+};
+
+use crate::{profile, storage};
+
+pub const DAILY_GRID_SIZE: GridSize = GridSize { rows: 7, cols: 7 };
+/// How many times the generator is run on the daily board before giving up.
+const MAX_DAILY_RUNS: usize = 10;
+const DAILY_PROGRESS_FILE: &str = "daily.txt";
+
+/// A calendar day in UTC.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    pub fn today() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        Date::from_days((seconds / 86_400) as i64)
+    }
+
+    /// Converts a number of days since 1970-01-01 into a date.
+    pub fn from_days(days: i64) -> Self {
+        // Howard Hinnant's civil_from_days
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400 + i64::from(month <= 2)) as i32;
+        Date { year, month, day }
+    }
+
+    /// The number of days between 1970-01-01 and this date.
+    pub fn days(&self) -> i64 {
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let month = i64::from(self.month);
+        let mp = if month > 2 { month - 3 } else { month + 9 };
+        let doy = (153 * mp + 2) / 5 + i64::from(self.day) - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl FromStr for Date {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(3, '-');
+        let mut next = || parts.next().ok_or(());
+        let year = next()?.parse().map_err(|_| ())?;
+        let month = next()?.parse().map_err(|_| ())?;
+        let day = next()?.parse().map_err(|_| ())?;
+        let date = Date { year, month, day };
+        // reject dates like 2024-02-31 that don't survive a round trip
+        if Date::from_days(date.days()) == date {
+            Ok(date)
+        } else {
+            Err(())
+        }
+    }
+}
+
+/// Generates the puzzle for `date`. Every player gets the same puzzle on the
+/// same day, since the generator is seeded from the date alone, with a
+/// random number generator that gives the same numbers on every platform and
+/// version. Returns `None` if none of `MAX_DAILY_RUNS` runs turned one up.
+pub fn daily_puzzle(date: Date) -> Option<Grid> {
+    let mut rng = ChaCha8Rng::seed_from_u64(date.days() as u64);
+    // the daily board is small enough that the first run always turns up a
+    // puzzle, but carrying on with the same generator keeps the puzzle the
+    // same if it ever didn't
+    (0..MAX_DAILY_RUNS).find_map(|_| generator::generate(DAILY_GRID_SIZE, &mut rng))
+}
+
+/// The days whose daily puzzle the player has solved, saved to disk.
+#[derive(Resource, Default)]
+pub struct DailyProgress {
+    completed: BTreeSet<Date>,
+}
+
+impl DailyProgress {
+    pub fn load() -> Self {
+        let completed = storage::read(profile::path(DAILY_PROGRESS_FILE))
+            .map(|contents| {
+                contents
+                    .lines()
+                    .filter_map(|line| line.parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        DailyProgress { completed }
+    }
+
+    pub fn is_completed(&self, date: Date) -> bool {
+        self.completed.contains(&date)
+    }
+
+    /// Marks `date` as solved and writes the progress to disk.
+    pub fn complete(&mut self, date: Date) {
+        if !self.completed.insert(date) {
+            return;
+        }
+        let contents: String = self
+            .completed
+            .iter()
+            .map(|date| format!("{}\n", date))
+            .collect();
+        let path = profile::path(DAILY_PROGRESS_FILE);
+        if let Err(err) = storage::write(path, &contents) {
+            warn!("failed to save daily progress: {}", err);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use bevy::prelude::*;
+pub use nurikabe_core::difficulty::Difficulty;
+use nurikabe_core::{code, difficulty::rate, grid::Grid};
+
+use crate::storage;
+
+const DIFFICULTY_CACHE_PATH: &str = "./saves/difficulty.txt";
+
+/// Ratings of previously seen puzzles, keyed by their share code and saved
+/// to disk so they aren't recomputed on every launch. Clones share the same
+/// ratings, so puzzles rated on another thread end up in the cache too.
+#[derive(Resource, Clone, Default)]
+pub struct DifficultyCache {
+    ratings: Arc<Mutex<HashMap<String, Difficulty>>>,
+}
+
+impl DifficultyCache {
+    pub fn load() -> Self {
+        let mut ratings = HashMap::new();
+        if let Ok(contents) = storage::read(DIFFICULTY_CACHE_PATH) {
+            for line in contents.lines() {
+                if let Some((key, difficulty)) = line.split_once(',') {
+                    if let Ok(difficulty) = difficulty.parse() {
+                        ratings.insert(key.to_string(), difficulty);
+                    }
+                }
+            }
+        }
+        DifficultyCache {
+            ratings: Arc::new(Mutex::new(ratings)),
+        }
+    }
+
+    /// The rating of `grid` if the puzzle has been rated before.
+    pub fn get(&self, grid: &Grid) -> Option<Difficulty> {
+        let key = code::encode(&grid.clues_only());
+        self.ratings.lock().unwrap().get(&key).copied()
+    }
+
+    /// Returns the rating of `grid`, computing and saving it if the puzzle
+    /// hasn't been rated before. Returns `None` if it has no solution.
+    pub fn rate(&self, grid: &Grid) -> Option<Difficulty> {
+        if let Some(difficulty) = self.get(grid) {
+            return Some(difficulty);
+        }
+        // rated without holding the lock, since it can take a while
+        let difficulty = rate(grid)?;
+        let mut ratings = self.ratings.lock().unwrap();
+        ratings.insert(code::encode(&grid.clues_only()), difficulty);
+        save(&ratings);
+        Some(difficulty)
+    }
+}
+
+fn save(ratings: &HashMap<String, Difficulty>) {
+    let contents: String = ratings
+        .iter()
+        .map(|(key, difficulty)| format!("{},{}\n", key, difficulty))
+        .collect();
+    let path = Path::new(DIFFICULTY_CACHE_PATH);
+    if let Err(err) = storage::write(path, &contents) {
+        warn!("failed to save difficulty ratings: {}", err);
+    }
+}
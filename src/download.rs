@@ -0,0 +1,174 @@
+//! Downloads puzzle files and pack manifests from the web into
+//! `assets/puzzles`, where they are kept and found with the rest the next
+//! time the game starts.
+
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::process::{Command, Output, Stdio};
+
+use nurikabe_core::format::{self, Format, PACK_MANIFEST, PUZZLE_EXTENSIONS};
+
+use crate::{packs::Pack, puzzle_files};
+
+/// How long a download may take before it is given up on.
+#[cfg(not(target_arch = "wasm32"))]
+const DOWNLOAD_TIMEOUT_SECONDS: &str = "20";
+
+/// What a download added to `assets/puzzles`.
+pub enum Downloaded {
+    /// A puzzle file, at this path.
+    Puzzle(PathBuf),
+    /// A pack, with its manifest and puzzles in this folder.
+    Pack(PathBuf),
+}
+
+/// Downloads the puzzle file or pack manifest at `url`. The puzzles of a
+/// pack are fetched from next to its manifest, and the pack is kept in a
+/// folder named after the one it came from. Files that are there already
+/// are replaced.
+pub fn download(url: &str) -> io::Result<Downloaded> {
+    let url = url.trim();
+    // the query and fragment aren't part of the file name
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let (base, name) = path
+        .rsplit_once('/')
+        .ok_or_else(|| invalid(format!("{:?} doesn't name a file", url)))?;
+    let text = fetch(url)?;
+    if name != PACK_MANIFEST {
+        let name = file_name(name)?;
+        check_puzzle(&name, &text)?;
+        return puzzle_files::save(&name, &text).map(Downloaded::Puzzle);
+    }
+    let folder = base
+        .rsplit('/')
+        .next()
+        .and_then(|folder| file_name(folder).ok())
+        .ok_or_else(|| invalid(format!("{:?} isn't in a folder", url)))?;
+    let pack = Pack::parse(Path::new(""), &text);
+    if pack.puzzles.is_empty() {
+        return Err(invalid(format!("the pack at {:?} has no puzzles", url)));
+    }
+    // fetch every puzzle before saving any, so a pack is never left half
+    // downloaded
+    let mut puzzles = Vec::new();
+    for puzzle in &pack.puzzles {
+        let name = file_name(&puzzle.to_string_lossy())?;
+        let puzzle_text = fetch(&format!("{}/{}", base, name.display()))?;
+        check_puzzle(&name, &puzzle_text)?;
+        puzzles.push((folder.join(name), puzzle_text));
+    }
+    for (path, puzzle_text) in puzzles {
+        puzzle_files::save(&path, &puzzle_text)?;
+    }
+    let manifest = puzzle_files::save(&folder.join(PACK_MANIFEST), &text)?;
+    let dir = manifest.parent().map(Path::to_path_buf).unwrap_or_default();
+    Ok(Downloaded::Pack(dir))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// `name` as a path, as long as it is a plain file name that can't reach
+/// outside the folder it is saved in.
+fn file_name(name: &str) -> io::Result<PathBuf> {
+    let path = PathBuf::from(name);
+    let mut components = path.components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(path),
+        _ => Err(invalid(format!("{:?} isn't a file name", name))),
+    }
+}
+
+/// Fails unless `text` is a puzzle that can be read from a file called
+/// `name`.
+fn check_puzzle(name: &Path, text: &str) -> io::Result<()> {
+    let known = name
+        .extension()
+        .is_some_and(|extension| PUZZLE_EXTENSIONS.iter().any(|ext| extension == *ext));
+    if !known {
+        return Err(invalid(format!("{:?} isn't a puzzle file", name)));
+    }
+    format::parse(text, Format::for_file(name, text))
+        .map(|_| ())
+        .map_err(|err| invalid(format!("can't read {:?}: {}", name, err)))
+}
+
+/// Whether `text` is a web address that can be downloaded.
+pub fn is_url(text: &str) -> bool {
+    let text = text.trim();
+    (text.starts_with("https://") || text.starts_with("http://"))
+        && !text.contains(char::is_whitespace)
+}
+
+/// Fetches the text at `url`. Like the clipboard, this hands the work to
+/// whichever download program is installed: curl, wget, then PowerShell.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn fetch(url: &str) -> io::Result<String> {
+    let commands: [(&str, Vec<&str>); 3] = [
+        (
+            "curl",
+            vec![
+                "--fail",
+                "--silent",
+                "--location",
+                "--max-time",
+                DOWNLOAD_TIMEOUT_SECONDS,
+                url,
+            ],
+        ),
+        (
+            "wget",
+            vec![
+                "--quiet",
+                "--timeout",
+                DOWNLOAD_TIMEOUT_SECONDS,
+                "--output-document",
+                "-",
+                url,
+            ],
+        ),
+        (
+            "powershell",
+            vec![
+                "-NoProfile",
+                "-Command",
+                "(Invoke-WebRequest -UseBasicParsing -Uri $args[0]).Content",
+                url,
+            ],
+        ),
+    ];
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no download program found");
+    for (program, args) in commands {
+        let output = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output();
+        match output {
+            Ok(Output { status, stdout, .. }) if status.success() => {
+                return Ok(String::from_utf8_lossy(&stdout).into_owned());
+            }
+            Ok(Output { status, .. }) => {
+                last_err = io::Error::other(format!("{} failed with {}", program, status));
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => last_err = err,
+        }
+    }
+    Err(last_err)
+}
+
+/// The browser won't let the game run programs, and downloaded puzzles
+/// would have nowhere to be kept.
+#[cfg(target_arch = "wasm32")]
+pub fn fetch(_url: &str) -> io::Result<String> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "puzzles can't be downloaded in the browser",
+    ))
+}
@@ -0,0 +1,774 @@
+use bevy::{
+    input::{
+        common_conditions::input_just_pressed,
+        keyboard::{Key, KeyboardInput},
+        ButtonState,
+    },
+    prelude::*,
+    tasks::AsyncComputeTaskPool,
+};
+use std::ops::{Range, RangeInclusive};
+
+use nurikabe_core::{
+    check::{self, Problem},
+    format::{self, Format},
+    generator,
+    grid::{Grid, GridSize},
+    CellState,
+};
+
+use crate::{
+    background::Job,
+    ctrl_held,
+    difficulty::DifficultyCache,
+    hide_panel,
+    input::{self, Action, Actions},
+    move_cursor, puzzle_files,
+    settings::Settings,
+    show_panel, spawn_grid,
+    theme::Theme,
+    Cell, Cursor, GameState, GridComponent, Puzzle, PuzzlePaths, PuzzleSource, ScaledText,
+    ScreenAnchor, StartPuzzle, ThemedText, TileSheet, UI_LAYER,
+};
+
+/// The smallest and largest boards the editor makes.
+const MIN_EDITOR_SIZE: usize = 2;
+const MAX_EDITOR_SIZE: usize = 50;
+const EDITOR_KEYS: &str = "Number keys: clue   Backspace: delete   T: test-solve\nCtrl+arrow: add a line on that side   Ctrl+Shift+arrow: take one off\nTab: symmetry   B: select a box   G: fill it with generated clues   X: crop to it\nEnter: save and play   Esc: back to the menu";
+
+/// Which cell each clue is paired with, so a setter can keep the clues of a
+/// puzzle symmetric. The editor marks the partner of every clue still
+/// waiting for its own.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+enum Symmetry {
+    #[default]
+    None,
+    /// Turned half way round the middle of the board.
+    Rotational,
+    /// Mirrored from left to right.
+    LeftRight,
+    /// Mirrored from top to bottom.
+    TopBottom,
+}
+
+impl Symmetry {
+    fn next(self) -> Self {
+        match self {
+            Symmetry::None => Symmetry::Rotational,
+            Symmetry::Rotational => Symmetry::LeftRight,
+            Symmetry::LeftRight => Symmetry::TopBottom,
+            Symmetry::TopBottom => Symmetry::None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Symmetry::None => "none",
+            Symmetry::Rotational => "rotational",
+            Symmetry::LeftRight => "mirrored left to right",
+            Symmetry::TopBottom => "mirrored top to bottom",
+        }
+    }
+
+    /// The cell paired with `(row, col)`, or `None` if it has no partner or
+    /// is its own.
+    fn peer(self, row: usize, col: usize, grid_size: GridSize) -> Option<(usize, usize)> {
+        let GridSize { rows, cols } = grid_size;
+        let peer = match self {
+            Symmetry::None => return None,
+            Symmetry::Rotational => (rows - 1 - row, cols - 1 - col),
+            Symmetry::LeftRight => (row, cols - 1 - col),
+            Symmetry::TopBottom => (rows - 1 - row, col),
+        };
+        (peer != (row, col)).then_some(peer)
+    }
+}
+
+/// The puzzle being drawn up in the editor. It is kept when the editor is
+/// closed, so the player can come back to it.
+#[derive(Resource)]
+pub struct PuzzleEditor {
+    grid: Grid,
+    /// The cell whose clue is being typed, so the next digit adds on to it
+    /// rather than starting over.
+    typing: Option<GridComponent>,
+    /// What the last test-solve found, or why the last save failed.
+    message: String,
+    symmetry: Symmetry,
+    /// The corner a box selection was started from. The cursor is the other
+    /// corner.
+    selection: Option<GridComponent>,
+    /// A puzzle being generated to seed a box with, off the frame.
+    seeding: Option<Seeding>,
+}
+
+/// The box `seed_selected` was pressed on, and the puzzle being generated
+/// to fill it.
+struct Seeding {
+    rows: RangeInclusive<usize>,
+    cols: RangeInclusive<usize>,
+    job: Job<Option<Grid>>,
+}
+
+impl Default for PuzzleEditor {
+    fn default() -> Self {
+        PuzzleEditor {
+            grid: Grid::new(GridSize { rows: 7, cols: 7 }),
+            typing: None,
+            message: String::new(),
+            symmetry: Symmetry::None,
+            selection: None,
+            seeding: None,
+        }
+    }
+}
+
+impl PuzzleEditor {
+    fn has_clues(&self) -> bool {
+        self.grid
+            .cells()
+            .iter()
+            .any(|state| matches!(state, CellState::Value(_)))
+    }
+
+    /// Changes the size of the board, keeping the clues that still fit.
+    fn resize(&mut self, grid_size: GridSize) {
+        self.reframe(0..grid_size.rows as isize, 0..grid_size.cols as isize);
+    }
+
+    /// Makes the board the `rows` and `cols` of the current one, which may
+    /// run past its edges to add blank lines on either side. Clues inside
+    /// the new frame keep their place relative to each other.
+    fn reframe(&mut self, rows: Range<isize>, cols: Range<isize>) {
+        let grid_size = GridSize {
+            rows: (rows.end - rows.start) as usize,
+            cols: (cols.end - cols.start) as usize,
+        };
+        let GridSize {
+            rows: old_rows,
+            cols: old_cols,
+        } = self.grid.grid_size;
+        let mut grid = Grid::new(grid_size);
+        for row in 0..grid_size.rows {
+            for col in 0..grid_size.cols {
+                let (old_row, old_col) = (rows.start + row as isize, cols.start + col as isize);
+                if (0..old_rows as isize).contains(&old_row)
+                    && (0..old_cols as isize).contains(&old_col)
+                {
+                    grid.set(row, col, self.grid.get(old_row as usize, old_col as usize));
+                }
+            }
+        }
+        self.grid = grid;
+        self.typing = None;
+        self.selection = None;
+        // the box it was meant for has moved
+        self.seeding = None;
+    }
+
+    /// The rows and columns of the box selected between its first corner
+    /// and `cursor`, if a box is being selected.
+    fn selected(
+        &self,
+        cursor: GridComponent,
+    ) -> Option<(RangeInclusive<usize>, RangeInclusive<usize>)> {
+        let corner = self.selection?;
+        Some((
+            corner.row.min(cursor.row)..=corner.row.max(cursor.row),
+            corner.col.min(cursor.col)..=corner.col.max(cursor.col),
+        ))
+    }
+
+    /// Sets every cell of the selected box to `fill(row, col)` and ends the
+    /// selection. Returns whether there was a box to fill.
+    fn fill_selected(
+        &mut self,
+        cursor: GridComponent,
+        fill: impl Fn(usize, usize) -> CellState,
+    ) -> bool {
+        let Some((rows, cols)) = self.selected(cursor) else {
+            return false;
+        };
+        self.fill_box(rows, cols, fill);
+        self.selection = None;
+        true
+    }
+
+    /// Sets every cell of the box at `rows` and `cols` to `fill(row, col)`.
+    fn fill_box(
+        &mut self,
+        rows: RangeInclusive<usize>,
+        cols: RangeInclusive<usize>,
+        fill: impl Fn(usize, usize) -> CellState,
+    ) {
+        for row in rows {
+            for col in cols.clone() {
+                self.grid.set(row, col, fill(row, col));
+            }
+        }
+        self.typing = None;
+        self.message.clear();
+    }
+}
+
+/// Shows the size of the draft and the editor's keys along the bottom.
+#[derive(Component)]
+struct EditorText;
+
+/// Marks a cell the last test-solve found at fault: a clue that breaks the
+/// puzzle, or a cell its solutions disagree on.
+#[derive(Component)]
+struct ProblemHighlight;
+
+/// Shades the selected box, and dots the partner of each clue that doesn't
+/// have a clue of its own yet under the current symmetry.
+#[derive(Component)]
+struct EditorMark;
+
+/// Drawing up new puzzles: opened from the menu, with clues typed onto the
+/// board under the cursor and saved as a file in `assets/puzzles`.
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PuzzleEditor>()
+            .add_systems(Startup, spawn_editor_text)
+            .add_systems(
+                Update,
+                (
+                    type_clues,
+                    (
+                        move_cursor.run_if(not(ctrl_held)),
+                        resize_draft.run_if(ctrl_held),
+                        cycle_symmetry.run_if(input_just_pressed(KeyCode::Tab)),
+                        toggle_selection.run_if(input_just_pressed(KeyCode::KeyB)),
+                        clear_selected.run_if(
+                            input_just_pressed(KeyCode::Delete)
+                                .or_else(input_just_pressed(KeyCode::Backspace)),
+                        ),
+                        seed_selected.run_if(input_just_pressed(KeyCode::KeyG)),
+                        finish_seeding,
+                        crop_to_selected.run_if(input_just_pressed(KeyCode::KeyX)),
+                        test_draft.run_if(input_just_pressed(KeyCode::KeyT)),
+                        // before the board can be swapped back by closing the
+                        // editor, so no marks end up on the puzzle
+                        show_editor_marks,
+                        save_draft.run_if(input_just_pressed(KeyCode::Enter)),
+                        close_editor.run_if(quit_requested),
+                        update_editor_text,
+                    )
+                        .chain()
+                        .run_if(in_state(GameState::Editor)),
+                )
+                    .after(input::read_actions),
+            )
+            .add_systems(
+                OnEnter(GameState::Editor),
+                (open_editor, show_panel::<EditorText>),
+            )
+            .add_systems(OnExit(GameState::Editor), hide_panel::<EditorText>);
+    }
+}
+
+fn spawn_editor_text(mut commands: Commands, asset_server: Res<AssetServer>, theme: Res<Theme>) {
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("FiraSans-Regular.ttf"),
+                    font_size: 24.0,
+                    color: theme.text,
+                },
+            )
+            .with_justify(JustifyText::Center),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        EditorText,
+        ScreenAnchor::new(Vec2::new(0.0, -1.0), Vec2::new(0.0, 110.0)),
+        ThemedText,
+        ScaledText::new(24.0),
+        UI_LAYER,
+    ));
+}
+
+/// Puts `grid` on the board in place of whatever was there.
+fn show_grid(
+    commands: &mut Commands,
+    cells: &Query<Entity, With<Cell>>,
+    grid: &Grid,
+    board: (&TileSheet, &Theme, &Settings),
+) {
+    let (tile_sheet, theme, settings) = board;
+    for entity in cells {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_grid(commands, grid, tile_sheet, theme, settings.cell_size);
+}
+
+/// Swaps the board for the draft. A draft with no clues yet takes on the
+/// size of the puzzle being played.
+fn open_editor(
+    mut commands: Commands,
+    puzzle: Res<Puzzle>,
+    mut editor: ResMut<PuzzleEditor>,
+    cells: Query<Entity, With<Cell>>,
+    mut cursor: Query<&mut GridComponent, With<Cursor>>,
+    board: (Res<TileSheet>, Res<Theme>, Res<Settings>),
+) {
+    if !editor.has_clues() {
+        editor.resize(puzzle.game_grid.grid_size);
+    }
+    editor.message.clear();
+    let (tile_sheet, theme, settings) = board;
+    show_grid(
+        &mut commands,
+        &cells,
+        &editor.grid,
+        (&tile_sheet, &theme, &settings),
+    );
+    *cursor.single_mut() = GridComponent::splat(0);
+}
+
+/// Whether the player asked to leave the editor.
+fn quit_requested(actions: Res<Actions>) -> bool {
+    actions.triggered(Action::Quit)
+}
+
+/// Types clues into the cell under the cursor. Digits typed one after the
+/// other make up one clue, Backspace takes off the last digit and Delete
+/// clears the cell.
+fn type_clues(
+    mut commands: Commands,
+    game_state: Res<State<GameState>>,
+    mut keyboard_input_ev: EventReader<KeyboardInput>,
+    mut editor: ResMut<PuzzleEditor>,
+    mut cells: Query<(&mut Cell, &GridComponent)>,
+    cursor: Query<Ref<GridComponent>, With<Cursor>>,
+    highlights: Query<Entity, With<ProblemHighlight>>,
+) {
+    // read the events every frame, so keys pressed before the editor opened
+    // aren't typed into it
+    let events: Vec<&KeyboardInput> = keyboard_input_ev
+        .read()
+        .filter(|ev| ev.state == ButtonState::Pressed)
+        .collect();
+    if *game_state != GameState::Editor {
+        return;
+    }
+    let cursor = cursor.single();
+    // moving the cursor finishes the clue being typed
+    if cursor.is_changed() {
+        editor.typing = None;
+    }
+    let location = *cursor;
+    let GridSize { rows, cols } = editor.grid.grid_size;
+    let max_clue = (rows * cols).min(i8::MAX as usize);
+    for ev in events {
+        let clue = match editor.grid.get(location.row, location.col) {
+            CellState::Value(clue) => clue as usize,
+            _ => 0,
+        };
+        let clue = match &ev.logical_key {
+            Key::Character(chars) => {
+                let Some(digit) = chars.chars().find_map(|c| c.to_digit(10)) else {
+                    continue;
+                };
+                let digit = digit as usize;
+                let typed = if editor.typing == Some(location) {
+                    clue * 10 + digit
+                } else {
+                    digit
+                };
+                // a clue too big for the board starts over from this digit
+                if typed > max_clue {
+                    digit
+                } else {
+                    typed
+                }
+            }
+            Key::Backspace => clue / 10,
+            Key::Delete => 0,
+            _ => continue,
+        };
+        let state = if clue == 0 {
+            CellState::Blank
+        } else {
+            CellState::Value(clue as i8)
+        };
+        editor.grid.set(location.row, location.col, state);
+        editor.typing = Some(location);
+        // the last test-solve no longer holds
+        editor.message.clear();
+        for entity in &highlights {
+            commands.entity(entity).despawn_recursive();
+        }
+        if let Some((mut cell, _)) = cells.iter_mut().find(|(_, loc)| **loc == location) {
+            cell.0 = state;
+        }
+    }
+}
+
+/// Adds a row or column to the side of the draft Ctrl and an arrow key
+/// point at, or takes the outermost one off that side with Shift held too.
+fn resize_draft(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut editor: ResMut<PuzzleEditor>,
+    cells: Query<Entity, With<Cell>>,
+    mut cursor: Query<&mut GridComponent, With<Cursor>>,
+    board: (Res<TileSheet>, Res<Theme>, Res<Settings>),
+) {
+    let step = if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        -1
+    } else {
+        1
+    };
+    let GridSize { rows, cols } = editor.grid.grid_size;
+    let (mut new_rows, mut new_cols) = (0..rows as isize, 0..cols as isize);
+    // rows count up from the bottom of the board
+    if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        new_rows.end += step;
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        new_rows.start -= step;
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+        new_cols.end += step;
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+        new_cols.start -= step;
+    }
+    let fits = |range: &Range<isize>| {
+        (MIN_EDITOR_SIZE as isize..=MAX_EDITOR_SIZE as isize).contains(&(range.end - range.start))
+    };
+    if (new_rows == (0..rows as isize) && new_cols == (0..cols as isize))
+        || !fits(&new_rows)
+        || !fits(&new_cols)
+    {
+        return;
+    }
+    let mut location = cursor.single_mut();
+    // the cursor stays on the same clue where it can
+    let moved = GridComponent::new(
+        (location.row as isize - new_rows.start).max(0) as usize,
+        (location.col as isize - new_cols.start).max(0) as usize,
+    );
+    editor.reframe(new_rows, new_cols);
+    *location = moved.clamp(&editor.grid.grid_size);
+    let (tile_sheet, theme, settings) = board;
+    show_grid(
+        &mut commands,
+        &cells,
+        &editor.grid,
+        (&tile_sheet, &theme, &settings),
+    );
+}
+
+/// Cuts the draft down to the selected box.
+fn crop_to_selected(
+    mut commands: Commands,
+    mut editor: ResMut<PuzzleEditor>,
+    cells: Query<Entity, With<Cell>>,
+    mut cursor: Query<&mut GridComponent, With<Cursor>>,
+    board: (Res<TileSheet>, Res<Theme>, Res<Settings>),
+) {
+    let mut location = cursor.single_mut();
+    let Some((rows, cols)) = editor.selected(*location) else {
+        editor.message = "Press B to select a box to crop to first".to_string();
+        return;
+    };
+    if rows.end() - rows.start() + 1 < MIN_EDITOR_SIZE
+        || cols.end() - cols.start() + 1 < MIN_EDITOR_SIZE
+    {
+        editor.message = format!("Puzzles need at least {} rows and columns", MIN_EDITOR_SIZE);
+        return;
+    }
+    *location = GridComponent::new(location.row - rows.start(), location.col - cols.start());
+    editor.reframe(
+        *rows.start() as isize..*rows.end() as isize + 1,
+        *cols.start() as isize..*cols.end() as isize + 1,
+    );
+    let (tile_sheet, theme, settings) = board;
+    show_grid(
+        &mut commands,
+        &cells,
+        &editor.grid,
+        (&tile_sheet, &theme, &settings),
+    );
+}
+
+/// Runs the solver on the draft and reports whether it has exactly one
+/// solution and how hard it is. When it doesn't, the clues at fault, or the
+/// cells its solutions disagree on, are highlighted.
+fn test_draft(
+    mut commands: Commands,
+    mut editor: ResMut<PuzzleEditor>,
+    cells: Query<(Entity, &GridComponent), With<Cell>>,
+    highlights: Query<Entity, With<ProblemHighlight>>,
+) {
+    for entity in &highlights {
+        commands.entity(entity).despawn_recursive();
+    }
+    let report = check::check(&editor.grid);
+    editor.message = if report.problems.is_empty() {
+        match report.difficulty {
+            Some(difficulty) => format!("Solvable with one solution, rated {}", difficulty),
+            None => "Solvable with one solution".to_string(),
+        }
+    } else {
+        let problems: Vec<String> = report.problems.iter().map(Problem::to_string).collect();
+        format!("Not a valid puzzle: {}", problems.join("; "))
+    };
+    for (entity, location) in &cells {
+        if report
+            .cells
+            .binary_search(&(location.row, location.col))
+            .is_err()
+        {
+            continue;
+        }
+        commands.entity(entity).with_children(|builder| {
+            builder.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::srgba(0.9, 0.1, 0.1, 0.4),
+                        custom_size: Some(Vec2::splat(16.0)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(Vec3::Z * 0.25),
+                    ..default()
+                },
+                ProblemHighlight,
+            ));
+        });
+    }
+}
+
+fn cycle_symmetry(mut editor: ResMut<PuzzleEditor>) {
+    editor.symmetry = editor.symmetry.next();
+}
+
+/// Starts a box selection at the cursor, or drops the one being made.
+fn toggle_selection(mut editor: ResMut<PuzzleEditor>, cursor: Query<&GridComponent, With<Cursor>>) {
+    editor.selection = match editor.selection {
+        Some(_) => None,
+        None => Some(*cursor.single()),
+    };
+}
+
+/// Sets every cell to its state in `draft`, clearing the test-solve
+/// highlights since the draft has changed.
+fn show_draft(
+    commands: &mut Commands,
+    draft: &Grid,
+    cells: &mut Query<(&mut Cell, &GridComponent)>,
+    highlights: &Query<Entity, With<ProblemHighlight>>,
+) {
+    for (mut cell, location) in cells {
+        let state = draft.get(location.row, location.col);
+        if cell.0 != state {
+            cell.0 = state;
+        }
+    }
+    for entity in highlights {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Takes every clue out of the selected box.
+fn clear_selected(
+    mut commands: Commands,
+    mut editor: ResMut<PuzzleEditor>,
+    cursor: Query<&GridComponent, With<Cursor>>,
+    mut cells: Query<(&mut Cell, &GridComponent)>,
+    highlights: Query<Entity, With<ProblemHighlight>>,
+) {
+    if editor.fill_selected(*cursor.single(), |_, _| CellState::Blank) {
+        show_draft(&mut commands, &editor.grid, &mut cells, &highlights);
+    }
+}
+
+/// Starts generating a puzzle the size of the selected box, for
+/// `finish_seeding` to fill it with as a starting point to build on.
+fn seed_selected(mut editor: ResMut<PuzzleEditor>, cursor: Query<&GridComponent, With<Cursor>>) {
+    let Some((rows, cols)) = editor.selected(*cursor.single()) else {
+        editor.message = "Press B to select a box to fill first".to_string();
+        return;
+    };
+    let grid_size = GridSize {
+        rows: rows.end() - rows.start() + 1,
+        cols: cols.end() - cols.start() + 1,
+    };
+    if grid_size.rows.max(grid_size.cols) > generator::MAX_GENERATED_SIZE
+        || grid_size.rows.min(grid_size.cols) < 2
+    {
+        editor.message = format!(
+            "Boxes to fill need to be from 2x2 up to {0}x{0}",
+            generator::MAX_GENERATED_SIZE
+        );
+        return;
+    }
+    let job = Job::spawn(AsyncComputeTaskPool::get(), move || {
+        generator::generate(grid_size, &mut rand::thread_rng())
+    });
+    editor.selection = None;
+    editor.message = "Generating clues for the box...".to_string();
+    editor.seeding = Some(Seeding { rows, cols, job });
+}
+
+/// Fills the box `seed_selected` was pressed on once its puzzle is ready.
+fn finish_seeding(
+    mut commands: Commands,
+    mut editor: ResMut<PuzzleEditor>,
+    mut cells: Query<(&mut Cell, &GridComponent)>,
+    highlights: Query<Entity, With<ProblemHighlight>>,
+) {
+    let Some(seeding) = editor.bypass_change_detection().seeding.as_mut() else {
+        return;
+    };
+    let Some(seed) = seeding.job.poll() else {
+        return;
+    };
+    let Seeding { rows, cols, .. } = editor.seeding.take().unwrap();
+    let Some(seed) = seed else {
+        editor.message = "Couldn't generate clues for the box, try again".to_string();
+        return;
+    };
+    let (top, left) = (*rows.start(), *cols.start());
+    editor.fill_box(rows, cols, |row, col| seed.get(row - top, col - left));
+    show_draft(&mut commands, &editor.grid, &mut cells, &highlights);
+}
+
+/// Redraws the selected box and the symmetry marks whenever the draft or
+/// the cursor changes.
+fn show_editor_marks(
+    mut commands: Commands,
+    editor: Res<PuzzleEditor>,
+    cursor: Query<Ref<GridComponent>, With<Cursor>>,
+    cells: Query<(Entity, &GridComponent), With<Cell>>,
+    marks: Query<Entity, With<EditorMark>>,
+) {
+    let cursor = cursor.single();
+    if !editor.is_changed() && !cursor.is_changed() {
+        return;
+    }
+    for entity in &marks {
+        commands.entity(entity).despawn_recursive();
+    }
+    let selected = editor.selected(*cursor);
+    let grid = &editor.grid;
+    for (entity, location) in &cells {
+        let (row, col) = (location.row, location.col);
+        let in_box = selected
+            .as_ref()
+            .is_some_and(|(rows, cols)| rows.contains(&row) && cols.contains(&col));
+        let wants_clue = !matches!(grid.get(row, col), CellState::Value(_))
+            && editor
+                .symmetry
+                .peer(row, col, grid.grid_size)
+                .is_some_and(|(r, c)| matches!(grid.get(r, c), CellState::Value(_)));
+        let (color, size) = if in_box {
+            (Color::srgba(0.2, 0.5, 1.0, 0.3), 16.0)
+        } else if wants_clue {
+            (Color::srgba(0.1, 0.7, 0.4, 0.8), 6.0)
+        } else {
+            continue;
+        };
+        commands.entity(entity).with_children(|builder| {
+            builder.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(Vec2::splat(size)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(Vec3::Z * 0.25),
+                    ..default()
+                },
+                EditorMark,
+            ));
+        });
+    }
+}
+
+/// Saves the draft as a new puzzle file and starts playing it.
+fn save_draft(
+    mut editor: ResMut<PuzzleEditor>,
+    mut puzzle_paths: ResMut<PuzzlePaths>,
+    difficulty_cache: Res<DifficultyCache>,
+    mut start_puzzle_ev: EventWriter<StartPuzzle>,
+) {
+    if !editor.has_clues() {
+        editor.message = "Place some clues first".to_string();
+        return;
+    }
+    let text = format::write(&editor.grid, Format::Legacy);
+    let path = match puzzle_files::save_new(editor.grid.grid_size, &text) {
+        Ok(path) => path,
+        Err(err) => {
+            editor.message = format!("Couldn't save the puzzle: {}", err);
+            return;
+        }
+    };
+    info!("Saved the puzzle to {:?}", path);
+    puzzle_paths.0.push(path.clone());
+    puzzle_paths.0.sort();
+    let game_grid = std::mem::take(&mut *editor).grid;
+    let difficulty = difficulty_cache.rate(&game_grid);
+    start_puzzle_ev.send(StartPuzzle(Puzzle::new(
+        game_grid,
+        PuzzleSource::File(path),
+        difficulty,
+    )));
+}
+
+/// Goes back to the menu, putting the puzzle being played back on the
+/// board.
+fn close_editor(
+    mut commands: Commands,
+    mut puzzle: ResMut<Puzzle>,
+    cells: Query<Entity, With<Cell>>,
+    mut cursor: Query<&mut GridComponent, With<Cursor>>,
+    board: (Res<TileSheet>, Res<Theme>, Res<Settings>),
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let (tile_sheet, theme, settings) = board;
+    show_grid(
+        &mut commands,
+        &cells,
+        &puzzle.game_grid,
+        (&tile_sheet, &theme, &settings),
+    );
+    // so the highlights and overlays are put back on the new cells
+    puzzle.set_changed();
+    *cursor.single_mut() = GridComponent::splat(0);
+    next_state.set(GameState::Menu);
+}
+
+fn update_editor_text(
+    editor: Res<PuzzleEditor>,
+    mut editor_text: Query<&mut Text, With<EditorText>>,
+) {
+    let GridSize { rows, cols } = editor.grid.grid_size;
+    let clues = editor
+        .grid
+        .cells()
+        .iter()
+        .filter(|state| matches!(state, CellState::Value(_)))
+        .count();
+    let status = format!(
+        "Puzzle editor    {}x{}    {} clues    symmetry: {}\n{}\n{}",
+        cols,
+        rows,
+        clues,
+        editor.symmetry.name(),
+        EDITOR_KEYS,
+        editor.message
+    );
+    let mut text = editor_text.single_mut();
+    if text.sections[0].value != status {
+        text.sections[0].value = status;
+    }
+}
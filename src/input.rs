@@ -0,0 +1,447 @@
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{profile, settings::Settings, storage};
+
+const KEY_BINDINGS_FILE: &str = "keybindings.txt";
+/// How far the left stick has to be pushed before it counts as a press.
+const STICK_THRESHOLD: f32 = 0.5;
+/// The largest count prefix the vim keys accept.
+const MAX_VIM_COUNT: usize = 999;
+const DIGIT_KEYS: [KeyCode; 10] = [
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+/// The keys that can be named in the key bindings file.
+pub const BINDABLE_KEYS: [KeyCode; 78] = [
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::ArrowUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+    KeyCode::Space,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Delete,
+    KeyCode::Escape,
+    KeyCode::Insert,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Backslash,
+    KeyCode::Semicolon,
+    KeyCode::Quote,
+    KeyCode::Backquote,
+    KeyCode::Minus,
+    KeyCode::Equal,
+    KeyCode::BracketLeft,
+    KeyCode::BracketRight,
+    KeyCode::Numpad0,
+    KeyCode::Numpad1,
+    KeyCode::Numpad2,
+    KeyCode::Numpad3,
+    KeyCode::Numpad4,
+    KeyCode::Numpad5,
+    KeyCode::Numpad6,
+    KeyCode::Numpad7,
+    KeyCode::Numpad8,
+    KeyCode::Numpad9,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+];
+
+/// Something the player can do, independent of the device used to do it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Toggle,
+    MarkIsland,
+    Clear,
+    Reset,
+    Menu,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 10] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Toggle,
+        Action::MarkIsland,
+        Action::Clear,
+        Action::Reset,
+        Action::Menu,
+        Action::Quit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Toggle => "Toggle cell",
+            Action::MarkIsland => "Mark island",
+            Action::Clear => "Clear cell",
+            Action::Reset => "Reset puzzle",
+            Action::Menu => "Menu",
+            Action::Quit => "Quit",
+        }
+    }
+
+    fn is_movement(&self) -> bool {
+        matches!(
+            self,
+            Action::MoveUp | Action::MoveDown | Action::MoveLeft | Action::MoveRight
+        )
+    }
+}
+
+/// A short name for `key`, like `A` for `KeyCode::KeyA`.
+pub fn key_name(key: KeyCode) -> String {
+    let name = format!("{:?}", key);
+    match name
+        .strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+    {
+        Some(short) => short.to_string(),
+        None => name,
+    }
+}
+
+/// The keyboard keys bound to each action, saved to disk when changed.
+#[derive(Resource, Clone)]
+pub struct KeyBindings {
+    bindings: HashMap<Action, Vec<KeyCode>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let bindings = Action::ALL
+            .into_iter()
+            .map(|action| {
+                let keys = match action {
+                    Action::MoveUp => vec![KeyCode::ArrowUp, KeyCode::KeyW],
+                    Action::MoveDown => vec![KeyCode::ArrowDown, KeyCode::KeyS],
+                    Action::MoveLeft => vec![KeyCode::ArrowLeft, KeyCode::KeyA],
+                    Action::MoveRight => vec![KeyCode::ArrowRight, KeyCode::KeyD],
+                    Action::Toggle => vec![KeyCode::Space],
+                    Action::MarkIsland => vec![KeyCode::KeyI],
+                    Action::Clear => vec![KeyCode::Backspace, KeyCode::Delete],
+                    Action::Reset => vec![KeyCode::KeyR],
+                    Action::Menu => vec![KeyCode::KeyM],
+                    Action::Quit => vec![KeyCode::KeyQ, KeyCode::Escape],
+                };
+                (action, keys)
+            })
+            .collect();
+        KeyBindings { bindings }
+    }
+}
+
+impl KeyBindings {
+    /// Reads the bindings file, one `Action: Key, Key` line per action.
+    /// Actions missing from the file keep their default keys.
+    pub fn load() -> Self {
+        let mut key_bindings = KeyBindings::default();
+        let Ok(contents) = storage::read(profile::path(KEY_BINDINGS_FILE)) else {
+            return key_bindings;
+        };
+        for line in contents.lines() {
+            let Some((action, keys)) = line.split_once(':') else {
+                continue;
+            };
+            let Some(action) = Action::ALL
+                .into_iter()
+                .find(|a| format!("{:?}", a) == action.trim())
+            else {
+                warn!("unknown action {:?} in key bindings", action);
+                continue;
+            };
+            let keys = keys
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .filter_map(|key| {
+                    let found = BINDABLE_KEYS
+                        .into_iter()
+                        .find(|k| format!("{:?}", k) == key);
+                    if found.is_none() {
+                        warn!("unknown key {:?} in key bindings", key);
+                    }
+                    found
+                })
+                .collect();
+            key_bindings.bindings.insert(action, keys);
+        }
+        key_bindings
+    }
+
+    pub fn keys(&self, action: Action) -> Vec<KeyCode> {
+        self.bindings.get(&action).cloned().unwrap_or_default()
+    }
+
+    /// Binds `key` to `action` alone, taking it away from any other action.
+    pub fn rebind(&mut self, action: Action, key: KeyCode) {
+        for keys in self.bindings.values_mut() {
+            keys.retain(|&k| k != key);
+        }
+        self.bindings.insert(action, vec![key]);
+        self.save();
+    }
+
+    fn save(&self) {
+        let contents: String = Action::ALL
+            .into_iter()
+            .map(|action| {
+                let keys: Vec<String> = self
+                    .keys(action)
+                    .iter()
+                    .map(|key| format!("{:?}", key))
+                    .collect();
+                format!("{:?}: {}\n", action, keys.join(", "))
+            })
+            .collect();
+        let path = profile::path(KEY_BINDINGS_FILE);
+        if let Err(err) = storage::write(path, &contents) {
+            warn!("failed to save key bindings: {}", err);
+        }
+    }
+}
+
+/// The actions triggered this frame by the keyboard or any gamepad, and how
+/// many times each one fires.
+#[derive(Resource, Default)]
+pub struct Actions {
+    triggered: HashMap<Action, usize>,
+    /// The movement actions currently held down, each with the time left
+    /// until it fires again.
+    repeating: HashMap<Action, Timer>,
+    /// The digits typed so far in front of a vim movement key, like the 5 in
+    /// `5l`.
+    vim_count: Option<usize>,
+}
+
+impl Actions {
+    pub fn triggered(&self, action: Action) -> bool {
+        self.times(action) > 0
+    }
+
+    /// How many times `action` fires this frame. Only movement with a vim
+    /// count prefix fires more than once.
+    pub fn times(&self, action: Action) -> usize {
+        self.triggered.get(&action).copied().unwrap_or(0)
+    }
+}
+
+/// Turns keyboard and gamepad input into [`Actions`] each frame. Expects a
+/// [`Settings`] resource to be present.
+pub struct ActionsPlugin;
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(KeyBindings::load())
+            .init_resource::<Actions>()
+            .add_systems(Update, read_actions);
+    }
+}
+
+#[derive(SystemParam)]
+pub struct GamepadInput<'w> {
+    gamepads: Res<'w, Gamepads>,
+    buttons: Res<'w, ButtonInput<GamepadButton>>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+}
+
+/// Collects this frame's actions. Movement fires as soon as a key, button or
+/// the stick is pressed, and keeps firing while it is held, unless Alt is
+/// held to pan the board. With vim keys on,
+/// h/j/k/l move as well, and a number typed first moves that many cells.
+pub fn read_actions(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    key_bindings: Res<KeyBindings>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepad_input: GamepadInput,
+    mut actions: ResMut<Actions>,
+) {
+    let mut triggered = HashMap::new();
+    let mut held = HashSet::new();
+    // with Alt held the movement keys pan the board instead
+    let panning = keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    for action in Action::ALL {
+        let keys = key_bindings.keys(action);
+        if action.is_movement() {
+            if !panning && keyboard_input.any_pressed(keys) {
+                held.insert(action);
+            }
+        } else if keyboard_input.any_just_pressed(keys) {
+            triggered.insert(action, 1);
+        }
+    }
+    let vim_keys = [
+        (Action::MoveLeft, KeyCode::KeyH),
+        (Action::MoveRight, KeyCode::KeyL),
+        (Action::MoveUp, KeyCode::KeyK),
+        (Action::MoveDown, KeyCode::KeyJ),
+    ];
+    if settings.vim_keys {
+        for (action, key) in vim_keys {
+            if keyboard_input.pressed(key) {
+                held.insert(action);
+            }
+        }
+        for key in keyboard_input.get_just_pressed() {
+            let moves = vim_keys.iter().any(|(_, vim_key)| vim_key == key)
+                || Action::ALL
+                    .iter()
+                    .any(|action| action.is_movement() && key_bindings.keys(*action).contains(key));
+            if let Some(digit) = DIGIT_KEYS.iter().position(|digit| digit == key) {
+                // a leading zero doesn't start a count
+                if digit > 0 || actions.vim_count.is_some() {
+                    let count = actions.vim_count.unwrap_or(0) * 10 + digit;
+                    actions.vim_count = Some(count.min(MAX_VIM_COUNT));
+                }
+            } else if !moves {
+                actions.vim_count = None;
+            }
+        }
+    } else {
+        actions.vim_count = None;
+    }
+
+    let movement_buttons = [
+        (Action::MoveLeft, GamepadButtonType::DPadLeft),
+        (Action::MoveRight, GamepadButtonType::DPadRight),
+        (Action::MoveUp, GamepadButtonType::DPadUp),
+        (Action::MoveDown, GamepadButtonType::DPadDown),
+    ];
+    let button_pressed = [
+        (Action::Toggle, GamepadButtonType::South),
+        (Action::Reset, GamepadButtonType::East),
+        (Action::MarkIsland, GamepadButtonType::West),
+        (Action::Clear, GamepadButtonType::North),
+        (Action::Menu, GamepadButtonType::Start),
+    ];
+    for gamepad in gamepad_input.gamepads.iter() {
+        for (action, button_type) in movement_buttons {
+            if gamepad_input
+                .buttons
+                .pressed(GamepadButton::new(gamepad, button_type))
+            {
+                held.insert(action);
+            }
+        }
+        for (action, button_type) in button_pressed {
+            if gamepad_input
+                .buttons
+                .just_pressed(GamepadButton::new(gamepad, button_type))
+            {
+                triggered.insert(action, 1);
+            }
+        }
+
+        let axis = |axis_type| {
+            gamepad_input
+                .axes
+                .get(GamepadAxis::new(gamepad, axis_type))
+                .unwrap_or(0.0)
+        };
+        let x = axis(GamepadAxisType::LeftStickX);
+        let y = axis(GamepadAxisType::LeftStickY);
+        if x < -STICK_THRESHOLD {
+            held.insert(Action::MoveLeft);
+        } else if x > STICK_THRESHOLD {
+            held.insert(Action::MoveRight);
+        }
+        if y < -STICK_THRESHOLD {
+            held.insert(Action::MoveDown);
+        } else if y > STICK_THRESHOLD {
+            held.insert(Action::MoveUp);
+        }
+    }
+
+    // the first frame an action is held it fires straight away, then again
+    // after the repeat delay, then every repeat interval
+    actions.repeating.retain(|action, _| held.contains(action));
+    for action in held {
+        match actions.repeating.get_mut(&action) {
+            Some(timer) => {
+                if timer.tick(time.delta()).just_finished() {
+                    triggered.insert(action, 1);
+                    *timer = Timer::from_seconds(settings.key_repeat_interval, TimerMode::Once);
+                }
+            }
+            None => {
+                let times = actions.vim_count.take().unwrap_or(1);
+                triggered.insert(action, times);
+                actions.repeating.insert(
+                    action,
+                    Timer::from_seconds(settings.key_repeat_delay, TimerMode::Once),
+                );
+            }
+        }
+    }
+
+    actions.triggered = triggered;
+}
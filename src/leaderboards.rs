@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use nurikabe_core::{code, grid::Grid};
+
+use crate::{daily::Date, profile, progress, storage};
+
+const LEADERBOARDS_FILE: &str = "leaderboards.txt";
+/// Bumped whenever the layout of the leaderboards file changes. Files with a
+/// different version are ignored rather than misread.
+const LEADERBOARDS_VERSION: u32 = 1;
+/// How many of the best solves are kept for each puzzle.
+const LEADERBOARD_SIZE: usize = 10;
+
+/// One solve on a leaderboard.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Entry {
+    pub seconds: f32,
+    pub moves: u32,
+    pub date: Date,
+}
+
+/// The fastest solves of each puzzle the player has finished, keyed by the
+/// share code of the clues like `Stats`, and saved to disk with the rest of
+/// the profile.
+#[derive(Resource, Default)]
+pub struct Leaderboards {
+    boards: BTreeMap<String, Vec<Entry>>,
+}
+
+impl Leaderboards {
+    /// Reads the leaderboards file: a `version` line, then one line per
+    /// puzzle holding its code and its solves as `seconds,moves,date`,
+    /// fastest first.
+    pub fn load() -> Self {
+        let Ok(contents) = storage::read(profile::path(LEADERBOARDS_FILE)) else {
+            return Leaderboards::default();
+        };
+        let mut lines = contents.lines();
+        let version = lines
+            .next()
+            .and_then(|line| line.strip_prefix("version "))
+            .and_then(|version| version.trim().parse::<u32>().ok());
+        if version != Some(LEADERBOARDS_VERSION) {
+            warn!(
+                "ignoring leaderboards with version {:?}, expected {}",
+                version, LEADERBOARDS_VERSION
+            );
+            return Leaderboards::default();
+        }
+        let boards = lines
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let code = parts.next()?.to_string();
+                let entries = parts
+                    .filter_map(|entry| {
+                        let mut fields = entry.split(',');
+                        let seconds = fields.next()?.parse().ok()?;
+                        let moves = fields.next()?.parse().ok()?;
+                        let date = fields.next()?.parse().ok()?;
+                        Some(Entry {
+                            seconds,
+                            moves,
+                            date,
+                        })
+                    })
+                    .collect();
+                Some((code, entries))
+            })
+            .collect();
+        Leaderboards { boards }
+    }
+
+    /// The best solves of the puzzle with the clues of `grid`, fastest
+    /// first.
+    pub fn top(&self, grid: &Grid) -> &[Entry] {
+        self.boards
+            .get(&code::encode(&grid.clues_only()))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Adds a solve of the puzzle with the clues of `grid`, and writes the
+    /// leaderboards to disk if it made the board. Returns its place, counting
+    /// from 0, or `None` if it was too slow.
+    pub fn record(&mut self, grid: &Grid, entry: Entry) -> Option<usize> {
+        let board = self
+            .boards
+            .entry(code::encode(&grid.clues_only()))
+            .or_default();
+        // ties go to the solve that came first
+        let place = board
+            .iter()
+            .position(|other| entry.seconds < other.seconds)
+            .unwrap_or(board.len());
+        if place >= LEADERBOARD_SIZE {
+            return None;
+        }
+        board.insert(place, entry);
+        board.truncate(LEADERBOARD_SIZE);
+        self.save();
+        Some(place)
+    }
+
+    fn save(&self) {
+        let mut contents = format!("version {}\n", LEADERBOARDS_VERSION);
+        for (code, entries) in &self.boards {
+            contents.push_str(code);
+            for entry in entries {
+                contents.push_str(&format!(
+                    " {},{},{}",
+                    entry.seconds, entry.moves, entry.date
+                ));
+            }
+            contents.push('\n');
+        }
+        let path = profile::path(LEADERBOARDS_FILE);
+        if let Err(err) = storage::write(path, &contents) {
+            warn!("failed to save leaderboards: {}", err);
+        }
+    }
+}
+
+/// Writes the first `count` entries one to a line, like
+/// `1. 0:42  18 moves  2024-05-01`.
+pub fn lines(entries: &[Entry], count: usize) -> String {
+    entries
+        .iter()
+        .take(count)
+        .enumerate()
+        .map(|(i, entry)| {
+            format!(
+                "{}. {}  {} moves  {}\n",
+                i + 1,
+                progress::format_time(entry.seconds),
+                entry.moves,
+                entry.date
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,4663 @@
+//! The game itself, as `NurikabePlugin`, which the `nurikabe` binary adds to
+//! a Bevy app.
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use audio::{PlaySound, Sound, SoundPlugin};
+use background::Job;
+use bevy::{
+    ecs::query::QueryFilter,
+    input::{
+        common_conditions::input_just_pressed,
+        keyboard::{Key, KeyboardInput},
+        mouse::{MouseScrollUnit, MouseWheel},
+        ButtonState,
+    },
+    prelude::*,
+    render::{
+        texture::{ImageLoaderSettings, ImageSampler},
+        view::RenderLayers,
+    },
+    sprite::Anchor,
+    tasks::{AsyncComputeTaskPool, IoTaskPool},
+    text::{BreakLineOn, Text2dBounds},
+    time::Stopwatch,
+    window::{PrimaryWindow, WindowResized},
+};
+use board_mesh::BoardMeshPlugin;
+use calendar::CalendarPlugin;
+use daily::{DailyProgress, Date};
+use difficulty::{Difficulty, DifficultyCache};
+use download::Downloaded;
+use editor::{EditorPlugin, PuzzleEditor};
+use input::{Action, Actions, ActionsPlugin, KeyBindings};
+use leaderboards::Leaderboards;
+use multiplayer::MultiplayerPlugin;
+use nurikabe_core::{
+    code, deduction,
+    format::{self, Format},
+    generator,
+    grid::{Grid, GridSize, Island},
+    history::{History, Move},
+    render,
+    replay::{self, Replay, REPLAY_EXTENSION},
+    rules::RuleChecker,
+    solver::{self, Hint},
+    CellState,
+};
+use packs::{Packs, PacksPlugin};
+use profile::Profiles;
+use progress::{PuzzleProgress, Record};
+use rand::Rng;
+use replay_viewer::{ReplayViewerPlugin, SharedReplay};
+use replays::Replays;
+use saved_games::SavedGames;
+use settings::{Setting, Settings};
+use stats::{PuzzleStats, Stats};
+use theme::Theme;
+use tutorial::{Goal, Tutorial};
+
+const SPACE_BETWEEN_CELLS: f32 = 5.0;
+const PAINT_BUTTONS: [MouseButton; 3] =
+    [MouseButton::Left, MouseButton::Right, MouseButton::Middle];
+const HINT_HIGHLIGHT_SECONDS: f32 = 3.0;
+const MISTAKE_FLASH_SECONDS: f32 = 1.0;
+const CONFETTI_PIECES: usize = 80;
+const CONFETTI_SECONDS: f32 = 1.5;
+/// How fast confetti falls, in pixels per second squared.
+const CONFETTI_GRAVITY: f32 = 900.0;
+const WIN_PANEL_SIZE: Vec2 = Vec2::new(340.0, 340.0);
+const WIN_BUTTON_SIZE: Vec2 = Vec2::new(150.0, 40.0);
+/// How long a cell takes to pop after it changes.
+const CELL_POP_SECONDS: f32 = 0.15;
+/// How much bigger a cell gets at the height of its pop.
+const CELL_POP_GROWTH: f32 = 0.15;
+const AUTO_SOLVE_DELAY_SECONDS: f32 = 0.25;
+const AUTO_SOLVE_MIN_DELAY_SECONDS: f32 = 0.01;
+const AUTO_SOLVE_MAX_DELAY_SECONDS: f32 = 4.0;
+const PUZZLE_WATCH_SECONDS: f32 = 1.0;
+/// How long the board has to sit untouched before it is autosaved.
+const AUTOSAVE_DELAY_SECONDS: f32 = 0.5;
+const MAX_CODE_LENGTH: usize = 200;
+/// How far a cell's outline sticks out past the cell, in tile pixels.
+const CELL_OUTLINE_WIDTH: f32 = 1.0;
+/// The colors cells can be annotated with, picked with the number keys 1 to 4.
+const ANNOTATION_COLORS: [Color; 4] = [
+    Color::srgba(0.9, 0.2, 0.2, 0.35),
+    Color::srgba(0.2, 0.4, 0.9, 0.35),
+    Color::srgba(0.2, 0.7, 0.3, 0.35),
+    Color::srgba(0.9, 0.8, 0.1, 0.35),
+];
+const ANNOTATION_KEYS: [KeyCode; 4] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+];
+const EXPORT_DIR: &str = "./exports";
+const EXPORT_CELL_SIZE: u32 = 48;
+const TUTORIAL_PANEL_SIZE: Vec2 = Vec2::new(720.0, 110.0);
+/// How far the tutorial's glow sticks out past the cell it is explaining.
+const TUTORIAL_HIGHLIGHT_MARGIN: f32 = 8.0;
+/// How many times a second the tutorial's glow pulses.
+const TUTORIAL_HIGHLIGHT_PULSE: f32 = 1.5;
+/// How far the middle of the instructions is from the left edge of the window.
+const INSTRUCTIONS_OFFSET: f32 = 140.0;
+/// Where the instructions sit while hidden, far enough left to be off screen.
+const INSTRUCTIONS_HIDDEN_OFFSET: f32 = -140.0;
+const INSTRUCTIONS_SLIDE_SECONDS: f32 = 0.25;
+/// The size of the strip along the top. It is stretched to the width of the
+/// window.
+const HUD_SIZE: Vec2 = Vec2::new(1280.0, 40.0);
+const HUD_FONT_SIZE: f32 = 24.0;
+/// The layer drawn by the camera for text, menus and panels, which stays put
+/// while the board camera zooms and pans.
+const UI_LAYER: RenderLayers = RenderLayers::layer(1);
+/// How much one notch of the mouse wheel zooms the board in or out.
+const ZOOM_STEP: f32 = 1.1;
+const MIN_ZOOM_SCALE: f32 = 0.25;
+const MAX_ZOOM_SCALE: f32 = 8.0;
+/// The room kept clear around the board when the camera is fitted to it: the
+/// instructions and the text on either side, and the strip along the top.
+const FIT_MARGIN: Vec2 = Vec2::new(520.0, 120.0);
+/// How fast Alt with the movement keys pans the board, in pixels per second
+/// at the normal zoom.
+const PAN_SPEED: f32 = 600.0;
+/// How far past the edge of the grid the board camera can be panned.
+const PAN_MARGIN: f32 = 100.0;
+const MENU_SIZE: Vec2 = Vec2::new(960.0, 700.0);
+/// The largest side of the puzzle preview in the main menu.
+const MENU_PREVIEW_SIZE: f32 = 280.0;
+
+mod audio;
+mod background;
+mod board_mesh;
+mod calendar;
+mod clipboard;
+mod daily;
+mod difficulty;
+mod download;
+mod editor;
+mod input;
+mod leaderboards;
+mod multiplayer;
+mod network;
+mod packs;
+mod profile;
+mod progress;
+mod puzzle_files;
+mod replay_viewer;
+mod replays;
+mod saved_games;
+mod settings;
+mod stats;
+mod storage;
+mod theme;
+mod tutorial;
+
+#[derive(Resource)]
+pub struct PuzzlePaths(pub Vec<PathBuf>);
+
+#[derive(Resource, Clone)]
+pub struct Puzzle {
+    pub game_grid: Grid,
+    pub source: PuzzleSource,
+    pub difficulty: Option<Difficulty>,
+    pub history: History,
+    /// How many cells the player has changed, counting each cell of a drag
+    /// but not undos or cells filled in by hints.
+    pub moves: u32,
+    /// Whether the solver has filled in a cell, through a hint or
+    /// auto-solve. A solve it helped with doesn't count towards the records.
+    pub solver_helped: bool,
+    /// The annotation color of each cell, as an index into
+    /// `ANNOTATION_COLORS`. Annotations are kept apart from the cell states,
+    /// so the player can paint over island and river alike.
+    pub annotations: Vec<Vec<Option<usize>>>,
+    /// Keeps track of whether `game_grid` is solved as cells change, so the
+    /// whole board isn't checked after every move. Cells should be changed
+    /// through `set` to keep it in step.
+    rules: RuleChecker,
+    /// Every change made to the board since the puzzle started, for watching
+    /// the solve again.
+    pub replay: Replay,
+    /// Moves made through `set` that haven't been given their time in the
+    /// replay yet, which `stamp_replay_moves` does at the end of the frame.
+    unstamped: Vec<Move>,
+}
+
+impl Puzzle {
+    pub fn new(game_grid: Grid, source: PuzzleSource, difficulty: Option<Difficulty>) -> Self {
+        let GridSize { rows, cols } = game_grid.grid_size;
+        Puzzle {
+            rules: RuleChecker::new(&game_grid),
+            replay: Replay::new(&game_grid),
+            unstamped: Vec::new(),
+            game_grid,
+            source,
+            difficulty,
+            history: History::default(),
+            moves: 0,
+            solver_helped: false,
+            annotations: vec![vec![None; cols]; rows],
+        }
+    }
+
+    /// Changes a cell of the grid.
+    pub fn set(&mut self, row: usize, col: usize, state: CellState) {
+        let from = self.game_grid.get(row, col);
+        if from != state {
+            self.unstamped.push(Move {
+                row,
+                col,
+                from,
+                to: state,
+            });
+        }
+        self.game_grid.set(row, col, state);
+        self.rules.set(row, col, state);
+    }
+
+    /// Whether the grid follows every rule of Nurikabe.
+    pub fn is_solved(&self) -> bool {
+        self.rules.is_solved()
+    }
+
+    /// Fills in the marks saved for this puzzle, if there are any. The
+    /// replay starts over from the restored board.
+    pub fn restore(&mut self, saved_games: &SavedGames) {
+        saved_games.restore(&mut self.game_grid);
+        self.rules = RuleChecker::new(&self.game_grid);
+        self.replay = Replay::new(&self.game_grid);
+        self.unstamped.clear();
+    }
+
+    /// Adds the moves made since the last call to the replay, as made
+    /// `seconds` into the solve.
+    pub fn stamp_moves(&mut self, seconds: f32) {
+        for change in self.unstamped.drain(..) {
+            self.replay.record(change, seconds);
+        }
+    }
+
+    /// A short code for the clues of this puzzle, for sharing it in chat.
+    pub fn to_code(&self) -> String {
+        code::encode(&self.game_grid)
+    }
+}
+
+/// Where the current puzzle came from.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PuzzleSource {
+    File(PathBuf),
+    /// A puzz.link URL passed on the command line.
+    Url(String),
+    /// A share code typed into the "enter code" dialog.
+    Code(String),
+    /// Text pasted from the clipboard.
+    Clipboard,
+    Generated,
+    Daily(Date),
+    /// The puzzle the tutorial walks the player through.
+    Tutorial,
+    /// A puzzle someone else started in a network game.
+    Network,
+}
+
+impl PuzzleSource {
+    /// What the puzzle is called on screen.
+    pub fn name(&self) -> String {
+        match self {
+            PuzzleSource::File(path) => file_name(path),
+            PuzzleSource::Url(_) => "puzz.link puzzle".to_string(),
+            PuzzleSource::Code(_) => "Puzzle from a code".to_string(),
+            PuzzleSource::Clipboard => "Pasted puzzle".to_string(),
+            PuzzleSource::Generated => "Random puzzle".to_string(),
+            PuzzleSource::Daily(date) => format!("Daily puzzle for {}", date),
+            PuzzleSource::Tutorial => "Tutorial".to_string(),
+            PuzzleSource::Network => "Shared puzzle".to_string(),
+        }
+    }
+}
+
+/// The name of a puzzle file without its extension.
+fn file_name(path: &Path) -> String {
+    path.file_stem().map_or_else(
+        || format!("{:?}", path),
+        |stem| stem.to_string_lossy().to_string(),
+    )
+}
+
+/// Checks the current puzzle file for changes every so often, so edits made
+/// while the game is running show up on the board.
+#[derive(Resource)]
+pub struct PuzzleWatch {
+    timer: Timer,
+    /// The file being watched and when it was last modified.
+    modified: Option<(PathBuf, SystemTime)>,
+}
+
+impl Default for PuzzleWatch {
+    fn default() -> Self {
+        PuzzleWatch {
+            timer: Timer::from_seconds(PUZZLE_WATCH_SECONDS, TimerMode::Repeating),
+            modified: None,
+        }
+    }
+}
+
+/// Saves the board shortly after the player stops changing it, so a crash
+/// or closing the window loses nothing without writing the file on every
+/// cell of a paint stroke.
+#[derive(Resource)]
+pub struct Autosave {
+    timer: Timer,
+    /// Whether the board changed since it was last saved.
+    pending: bool,
+}
+
+impl Default for Autosave {
+    fn default() -> Self {
+        Autosave {
+            timer: Timer::from_seconds(AUTOSAVE_DELAY_SECONDS, TimerMode::Once),
+            pending: false,
+        }
+    }
+}
+
+/// Sent to replace the board with a different puzzle.
+#[derive(Event)]
+pub struct StartPuzzle(Puzzle);
+
+/// Sent when the player clicks the next puzzle button on the win panel.
+#[derive(Event)]
+pub struct NextPuzzle;
+
+/// Sent when the player changes a cell themselves, rather than through undo
+/// or a hint.
+#[derive(Event)]
+pub struct CellMarked {
+    location: GridComponent,
+    state: CellState,
+}
+
+/// Checks the player's marks against the solution while assist mode is on.
+#[derive(Resource, Default)]
+pub struct Assist {
+    /// The code of the puzzle `solution` was worked out for.
+    code: String,
+    solution: Option<Grid>,
+    /// The wrong marks made on the current puzzle.
+    mistakes: u32,
+}
+
+/// The textures and atlas layout shared by every cell sprite. Every texture
+/// uses the same layout.
+#[derive(Resource)]
+pub struct TileSheet {
+    pub texture: Handle<Image>,
+    /// The tiles with patterns on island and river, for the colorblind
+    /// friendly setting.
+    pub patterned: Handle<Image>,
+    /// The skin picked in the settings and its file name. It takes the place
+    /// of both built-in textures.
+    pub skin: Option<(String, Handle<Image>)>,
+    pub layout: Handle<TextureAtlasLayout>,
+}
+
+impl TileSheet {
+    /// The texture the cells are drawn from under `theme`.
+    pub fn texture_for(&self, theme: &Theme) -> &Handle<Image> {
+        if let Some((_, skin)) = &self.skin {
+            skin
+        } else if theme.patterned_tiles {
+            &self.patterned
+        } else {
+            &self.texture
+        }
+    }
+}
+
+#[derive(States, Default, PartialEq, Eq, Hash, Clone, Debug)]
+pub enum GameState {
+    /// Waiting for the startup systems to build the board.
+    #[default]
+    Loading,
+    Playing,
+    Won,
+    Menu,
+    Settings,
+    EnterCode,
+    Stats,
+    /// Watching a recorded solve, with the board following along.
+    Replay,
+    /// Drawing up a new puzzle, with the draft on the board.
+    Editor,
+    /// Choosing a puzzle pack, or a puzzle in one.
+    Packs,
+    /// Picking a day to play the daily puzzle of.
+    Calendar,
+    /// Hosting or joining a game played over the network.
+    Lobby,
+    /// Watching a game played over the network, with the board following
+    /// one of the players.
+    Spectating,
+}
+
+#[derive(Component)]
+pub struct Cursor;
+
+/// A piece of confetti thrown up when the puzzle is solved, falling and
+/// fading out until its timer runs out.
+#[derive(Component)]
+pub struct Confetti {
+    velocity: Vec2,
+    spin: f32,
+    timer: Timer,
+}
+
+/// A button on the panel shown once the puzzle is solved.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WinButton {
+    NextPuzzle,
+    Menu,
+}
+
+impl WinButton {
+    fn label(&self) -> &'static str {
+        match self {
+            WinButton::NextPuzzle => "Next puzzle",
+            WinButton::Menu => "Menu",
+        }
+    }
+}
+
+/// The strip along the top of the window naming the puzzle, with its size,
+/// difficulty and the time spent on it.
+#[derive(Component)]
+pub struct HudText;
+
+/// The size of the island under the cursor, on the right of the board.
+#[derive(Component)]
+pub struct IslandText;
+
+/// The number of mistakes made in assist mode, in the top right corner under
+/// the strip along the top.
+#[derive(Component)]
+pub struct MistakeText;
+
+/// Text that grows and shrinks with the UI scale setting, holding the sizes it
+/// was spawned with at a scale of 100%.
+#[derive(Component)]
+pub struct ScaledText {
+    font_size: f32,
+    /// The box the text wraps in, for text that wraps.
+    bounds: Option<Vec2>,
+}
+
+impl ScaledText {
+    fn new(font_size: f32) -> Self {
+        ScaledText {
+            font_size,
+            bounds: None,
+        }
+    }
+
+    fn wrapped(font_size: f32, bounds: Vec2) -> Self {
+        ScaledText {
+            font_size,
+            bounds: Some(bounds),
+        }
+    }
+}
+
+/// The box holding the instructions, which slides off the left edge of the
+/// window when hidden.
+#[derive(Component)]
+pub struct InstructionsPanel {
+    shown: bool,
+}
+
+/// The camera that draws the board. It zooms with the mouse wheel and pans
+/// with Alt and the movement keys, or by dragging the middle button off the
+/// grid.
+#[derive(Component)]
+pub struct BoardCamera;
+
+/// The camera that draws the text and panels over the board.
+#[derive(Component)]
+pub struct UiCamera;
+
+/// Where the pointer was last frame during a middle-button drag of the board.
+#[derive(Resource, Default)]
+pub struct CameraDrag(Option<Vec2>);
+
+/// Keeps text or a panel the same distance from an edge of the window as it
+/// is resized. `edge` picks the edge or corner, from -1 for the left or
+/// bottom to 1 for the right or top, and `offset` is the distance from it.
+#[derive(Component, Clone, Copy)]
+pub struct ScreenAnchor {
+    edge: Vec2,
+    offset: Vec2,
+}
+
+impl ScreenAnchor {
+    fn new(edge: Vec2, offset: Vec2) -> Self {
+        ScreenAnchor { edge, offset }
+    }
+}
+
+#[derive(Component)]
+pub struct HudStrip;
+
+/// Text drawn straight on the background, colored to suit the theme.
+#[derive(Component)]
+pub struct ThemedText;
+
+/// The backdrop of the main menu, shown while it is open.
+#[derive(Component)]
+pub struct MenuPanel;
+
+#[derive(Component)]
+pub struct MenuText;
+
+/// A cell of the preview of the puzzle selected in the main menu.
+#[derive(Component)]
+pub struct MenuPreview;
+
+/// The best solves of the puzzle selected in the main menu, under its
+/// preview.
+#[derive(Component)]
+pub struct MenuLeaderboard;
+
+/// How long the player has spent on the current puzzle, not counting time
+/// spent in menus.
+#[derive(Resource, Default)]
+pub struct SolveTimer(Stopwatch);
+
+/// How the last solve went, for the win screen.
+#[derive(Resource, Default)]
+pub struct LastSolve {
+    seconds: f32,
+    moves: u32,
+    /// Whether it beat the best time on the puzzle.
+    new_record: bool,
+    /// Where it landed on the puzzle's leaderboard, counting from 0.
+    place: Option<usize>,
+    /// Whether the solver filled in cells, which keeps it off the records.
+    solver_helped: bool,
+}
+
+/// Where the player is in the main menu.
+#[derive(Resource, Default)]
+pub struct MainMenu {
+    selected: usize,
+    /// The state to go back to when the menu is closed without picking a
+    /// new puzzle.
+    previous: Option<GameState>,
+}
+
+/// An entry in the main menu.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum MenuItem {
+    Resume,
+    File(PathBuf),
+    /// Opens the page of puzzle packs.
+    Packs,
+    Daily,
+    /// Opens the calendar of daily puzzles.
+    Calendar,
+    Generate,
+    /// Opens the lobby for playing over the network.
+    Multiplayer,
+    Tutorial,
+    Stats,
+    Editor,
+    /// Switches to the next player profile.
+    Profile,
+    NewProfile,
+}
+
+/// Sent when the player picks a puzzle from the main menu.
+#[derive(Event)]
+pub struct ChooseMenuItem(MenuItem);
+
+/// The backdrop of the settings page, shown while it is open.
+#[derive(Component)]
+pub struct SettingsPanel;
+
+#[derive(Component)]
+pub struct SettingsText;
+
+/// Where the player is on the settings page.
+#[derive(Resource, Default)]
+pub struct SettingsPage {
+    /// The index into `Action::ALL` of the selected action.
+    selected: usize,
+    /// Whether the next key pressed becomes the selected action's binding.
+    rebinding: bool,
+    /// The state to go back to when the page is closed.
+    previous: Option<GameState>,
+}
+
+/// The backdrop of the "enter code" dialog, shown while it is open.
+#[derive(Component)]
+pub struct CodePanel;
+
+#[derive(Component)]
+pub struct CodeText;
+
+/// The backdrop of the stats page, shown while it is open.
+#[derive(Component)]
+pub struct StatsPanel;
+
+#[derive(Component)]
+pub struct StatsText;
+
+/// What has been typed into the "enter code" dialog.
+#[derive(Resource, Default)]
+pub struct CodeEntry {
+    text: String,
+    /// Why the last code couldn't be played.
+    error: Option<String>,
+    /// The state to go back to when the dialog is closed.
+    previous: Option<GameState>,
+}
+
+/// Lets the solver play the puzzle, one cell every time the timer fires.
+#[derive(Resource)]
+pub struct AutoSolve {
+    pub active: bool,
+    pub timer: Timer,
+}
+
+impl Default for AutoSolve {
+    fn default() -> Self {
+        AutoSolve {
+            active: false,
+            timer: Timer::from_seconds(AUTO_SOLVE_DELAY_SECONDS, TimerMode::Repeating),
+        }
+    }
+}
+
+/// The cell under the mouse pointer, if any.
+#[derive(Resource, Default)]
+pub struct HoveredCell(Option<GridComponent>);
+
+/// The cells visited by the mouse since a paint button was pressed, and the
+/// state they are being painted with.
+#[derive(Resource, Default)]
+pub struct PaintStroke {
+    button: Option<MouseButton>,
+    state: Option<CellState>,
+    visited: Vec<GridComponent>,
+    moves: Vec<Move>,
+}
+
+/// The outcome of the last time the player asked for a hint.
+#[derive(Resource, Default)]
+pub enum ActiveHint {
+    #[default]
+    None,
+    Hint(Hint),
+    /// The player's marks already contain a mistake.
+    Mistake,
+}
+
+#[derive(Component)]
+pub struct HintText;
+
+/// The backdrop of the tutorial's text, shown while the tutorial is running.
+#[derive(Component)]
+pub struct TutorialPanel;
+
+#[derive(Component)]
+pub struct TutorialText;
+
+/// The glow around the cell the tutorial is explaining.
+#[derive(Component)]
+pub struct TutorialHighlight;
+
+/// Marks the cell picked by the last hint. Spawned as a child of the cell and
+/// fades out once the timer runs out.
+#[derive(Component)]
+pub struct HintHighlight(Timer);
+
+/// The small number in the corner of a clue cell, counting the island cells
+/// its island still needs.
+#[derive(Component)]
+pub struct RemainingText;
+
+/// The number written on a clue cell. Drawn as text rather than from the tile
+/// sheet, so clues of any size fit.
+#[derive(Component)]
+pub struct ClueText;
+
+/// Dims every cell of a finished island.
+#[derive(Component)]
+pub struct CompleteIslandShade;
+
+/// Lightens every cell of the island under the cursor.
+#[derive(Component)]
+pub struct CursorIslandHighlight;
+
+/// Tints a cell that breaks the rules, like one in a 2x2 pool of river, for
+/// as long as it does.
+#[derive(Component)]
+pub struct RuleHighlight;
+
+/// The border around a cell in high contrast mode.
+#[derive(Component)]
+pub struct CellOutline;
+
+/// The small square in the corner of a cell the player locked.
+#[derive(Component)]
+pub struct LockIndicator;
+
+/// Tints a cell with the annotation color the player gave it. Every cell has
+/// one, left clear until the cell is annotated.
+#[derive(Component)]
+pub struct AnnotationOverlay;
+
+/// Flashes a cell the player marked wrongly in assist mode, fading out like
+/// `HintHighlight`.
+#[derive(Component)]
+pub struct MistakeFlash(Timer);
+
+#[derive(Component)]
+pub struct Cell(CellState);
+
+/// Makes a cell that just changed grow and shrink back, timed by the timer.
+#[derive(Component)]
+pub struct CellPop(Timer);
+
+#[derive(Component, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct GridComponent {
+    pub row: usize,
+    pub col: usize,
+}
+
+/// The index of the tile for `state` in the tile sheet. Clues sit on a blank
+/// tile, with their number written over it as [`ClueText`].
+fn tile_index(state: CellState) -> usize {
+    match state {
+        CellState::Blank | CellState::Value(_) => 0,
+        CellState::Island => 10,
+        CellState::River => 11,
+        CellState::Maybe => 12,
+    }
+}
+
+/// The size of the grid on the board, kept as a resource for the systems that
+/// map between cells and screen positions.
+#[derive(Resource, Deref)]
+pub struct BoardSize(GridSize);
+
+impl GridComponent {
+    pub fn new(row: usize, col: usize) -> Self {
+        GridComponent { row, col }
+    }
+
+    pub fn splat(val: usize) -> Self {
+        GridComponent { row: val, col: val }
+    }
+
+    pub fn clamp(&self, grid_size: &GridSize) -> GridComponent {
+        GridComponent::new(
+            self.row.clamp(0, grid_size.rows - 1),
+            self.col.clamp(0, grid_size.cols - 1),
+        )
+    }
+}
+
+/// The world position of the center of the cell at `(row, col)`.
+fn cell_position(grid_size: &GridSize, cell_size: f32, row: usize, col: usize) -> Vec2 {
+    get_offset(grid_size, cell_size)
+        + Vec2::new(col as f32, row as f32) * (cell_size + SPACE_BETWEEN_CELLS)
+}
+
+/// The cell under the world position `position`, if there is one. Points in
+/// the gaps between cells don't belong to any cell.
+fn cell_at(grid_size: &GridSize, cell_size: f32, position: Vec2) -> Option<GridComponent> {
+    let step = cell_size + SPACE_BETWEEN_CELLS;
+    let relative = (position - get_offset(grid_size, cell_size)) / step;
+    let (col, row) = (relative.x.round(), relative.y.round());
+    if col < 0.0 || row < 0.0 || col >= grid_size.cols as f32 || row >= grid_size.rows as f32 {
+        return None;
+    }
+    let location = GridComponent::new(row as usize, col as usize);
+    let distance =
+        (position - cell_position(grid_size, cell_size, location.row, location.col)).abs();
+    (distance.x <= cell_size / 2.0 && distance.y <= cell_size / 2.0).then_some(location)
+}
+
+fn get_offset(grid_size: &GridSize, cell_size: f32) -> Vec2 {
+    -Vec2::new(
+        (grid_size.cols - 1) as f32 / 2.0 * (cell_size + SPACE_BETWEEN_CELLS),
+        (grid_size.rows - 1) as f32 / 2.0 * (cell_size + SPACE_BETWEEN_CELLS),
+    )
+}
+
+/// Whether the game runs as a desktop app rather than in a browser. The web
+/// build has no files to watch or export to, and no window to close.
+fn on_desktop() -> bool {
+    cfg!(not(target_arch = "wasm32"))
+}
+
+/// Close the focused window when a quit key is pressed.
+fn close_on_esc(
+    mut commands: Commands,
+    focused_windows: Query<(Entity, &Window)>,
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+) {
+    for (window, focus) in focused_windows.iter() {
+        if !focus.focused {
+            continue;
+        }
+        // only on the press, so the Escape that closes a dialog doesn't also
+        // close the window on the next frame
+        if keys.any_just_pressed(key_bindings.keys(Action::Quit)) {
+            commands.entity(window).despawn();
+        }
+    }
+}
+
+fn load_puzzle(
+    mut commands: Commands,
+    difficulty_cache: Res<DifficultyCache>,
+    settings: Res<Settings>,
+    saved_games: Res<SavedGames>,
+    mut import: ResMut<PuzzleImport>,
+) {
+    if let Ok(puzzles) = puzzle_files::list() {
+        for path in &puzzles {
+            if let Some(mut puzzle) = read_puzzle(path, &difficulty_cache) {
+                puzzle.restore(&saved_games);
+                if settings.prefill_sea {
+                    prefill_sea(&mut puzzle);
+                }
+                commands.insert_resource(puzzle);
+                break;
+            }
+        }
+        // dbg!(puzzles.clone());
+        commands.insert_resource(PuzzlePaths(puzzles));
+    }
+    // a puzz.link URL on the command line is played instead of the files,
+    // once it is checked for a solution
+    if let Some(url) = env::args().nth(1) {
+        match Grid::from_puzz_link(&url) {
+            Ok(game_grid) => import.start(
+                game_grid,
+                PuzzleSource::Url(url.clone()),
+                &format!("{:?} has no solution", url),
+                &difficulty_cache,
+            ),
+            Err(err) => warn!("can't read {:?}: {}", url, err),
+        }
+    }
+}
+
+/// Reads the grid in the puzzle file at `path`, in the format its extension
+/// calls for, or whatever format it looks like if the extension is unknown.
+fn read_grid(path: &Path) -> Option<Grid> {
+    let puzzle_str = puzzle_files::read(path).ok()?;
+    format::parse(&puzzle_str, Format::for_file(path, &puzzle_str))
+        .map_err(|err| warn!("can't read {:?}: {}", path, err))
+        .ok()
+}
+
+/// Reads the puzzle file at `path`, or returns `None` if it can't be read or
+/// has no solution.
+fn read_puzzle(path: &Path, difficulty_cache: &DifficultyCache) -> Option<Puzzle> {
+    let game_grid = read_grid(path)?;
+    match solver::count_solutions(&game_grid, 2) {
+        0 => {
+            warn!("{:?} has no solution, skipping it", path);
+            return None;
+        }
+        1 => {}
+        _ => warn!("{:?} has more than one solution", path),
+    }
+    println!("{:?}", path);
+    let difficulty = difficulty_cache.rate(&game_grid);
+    Some(Puzzle::new(
+        game_grid,
+        PuzzleSource::File(path.to_path_buf()),
+        difficulty,
+    ))
+}
+
+fn setup(
+    mut commands: Commands,
+    // mut meshes: ResMut<Assets<Mesh>>,
+    puzzle: Res<Puzzle>,
+    settings: Res<Settings>,
+    theme: Res<Theme>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let grid = &puzzle.game_grid;
+    // cameras: one for the board, and one drawing the text and panels on
+    // top of it that doesn't zoom or pan
+    commands.spawn((Camera2dBundle::default(), BoardCamera));
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                order: 1,
+                clear_color: ClearColorConfig::None,
+                ..default()
+            },
+            ..default()
+        },
+        UiCamera,
+        UI_LAYER,
+    ));
+
+    // instructions
+    let font = asset_server.load("FiraSans-Regular.ttf");
+    let text_style = TextStyle {
+        font: font.clone(),
+        font_size: 30.0,
+        ..default()
+    };
+    let instruction_text ="Move the cursor with WASD/arrow keys (hold to keep moving), and press space or click to toggle the selected cell. Ctrl+space marks a cell you aren't sure about yet with a light dot, which isn't counted when checking the board, and Shift+space locks a marked cell so it can't be changed or reset by accident. The number keys 1 to 4 paint the selected cell with a color of its own, to keep track of guesses. Right-click or press I to mark an island, and middle-click or press backspace to clear a cell. Drag to paint several cells. Press Z to undo, Y to redo, H for a hint (Shift+H to fill it in), N for a new puzzle, or T for today's daily puzzle. U copies the puzzle as a puzz.link URL, and C opens a box for typing in a puzzle code. Ctrl+C copies the board as text, Ctrl+V plays a puzzle from the clipboard or downloads the puzzle file or pack at a copied link, and puzzle files can be dropped onto the window. P saves a picture of the board. M opens the menu, and Enter goes on to the next puzzle once this one is solved. Tab and Shift+Tab jump between clues. Scroll the mouse wheel to zoom, and hold Alt with the movement keys or drag with the middle button from outside the grid to pan. F1 opens the settings and key bindings, and F4 or ? hides these instructions or brings them back, and F6 watches your last solve of the puzzle again, where E shares it as a replay that can be pasted or dropped onto the window. F2 makes the cursor wrap around the edges, and F3 switches on vim keys: h/j/k/l move, a number in front moves that many cells, and ? gives a hint. On a gamepad, move with the D-pad or left stick, toggle with A, mark an island with X, clear with Y, reset with B and open the menu with Start.\nEach numbered cell is an island cell, the number in it is the number of cells in that island.\nEach island must contain exactly one numbered cell.\nThere must be only one sea, which is not allowed to contain \"pools\", i.e. 2x2 areas of black cells.";
+    let box_size = Vec2::new(240.0, 1200.0);
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgba(0.0, 0.0, 0.0, 0.0),
+                    custom_size: Some(Vec2::new(box_size.x, box_size.y)),
+                    ..default()
+                },
+                ..default()
+            },
+            InstructionsPanel { shown: true },
+            ScreenAnchor::new(Vec2::new(-1.0, 0.0), Vec2::new(INSTRUCTIONS_OFFSET, 0.0)),
+            UI_LAYER,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                Text2dBundle {
+                    text: Text {
+                        sections: vec![TextSection::new(instruction_text, text_style.clone())],
+                        justify: JustifyText::Left,
+                        linebreak_behavior: BreakLineOn::WordBoundary,
+                    },
+                    text_2d_bounds: Text2dBounds {
+                        // Wrap text in the rectangle
+                        size: box_size,
+                    },
+                    // ensure the text is drawn on top of the box
+                    transform: Transform::from_translation(Vec3::Z),
+                    ..default()
+                },
+                ThemedText,
+                ScaledText::wrapped(text_style.font_size, box_size),
+                UI_LAYER,
+            ));
+        });
+
+    // puzzle name, size, difficulty and time along the top
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    // see-through gray, so the themed text reads on either theme
+                    color: Color::srgba(0.5, 0.5, 0.5, 0.15),
+                    custom_size: Some(HUD_SIZE),
+                    ..default()
+                },
+                ..default()
+            },
+            HudStrip,
+            ScreenAnchor::new(Vec2::Y, Vec2::new(0.0, -HUD_SIZE.y / 2.0)),
+            UI_LAYER,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font_size: HUD_FONT_SIZE,
+                            ..text_style.clone()
+                        },
+                    ),
+                    transform: Transform::from_translation(Vec3::Z),
+                    ..default()
+                },
+                HudText,
+                ThemedText,
+                ScaledText::new(HUD_FONT_SIZE),
+                UI_LAYER,
+            ));
+        });
+
+    // size of the island under the cursor
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section("", text_style.clone()),
+            ..default()
+        },
+        IslandText,
+        ScreenAnchor::new(Vec2::X, Vec2::new(-140.0, -50.0)),
+        ThemedText,
+        ScaledText::new(text_style.font_size),
+        UI_LAYER,
+    ));
+
+    // mistakes in assist mode
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section("", text_style.clone()),
+            text_anchor: Anchor::TopRight,
+            ..default()
+        },
+        MistakeText,
+        ScreenAnchor::new(Vec2::ONE, Vec2::new(-20.0, -60.0)),
+        ThemedText,
+        ScaledText::new(text_style.font_size),
+        UI_LAYER,
+    ));
+
+    // main menu
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgba(0.0, 0.0, 0.0, 0.85),
+                    custom_size: Some(MENU_SIZE),
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::new(0.0, 0.0, 4.0)),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            MenuPanel,
+            UI_LAYER,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                Text2dBundle {
+                    text: Text::from_section("", text_style.clone())
+                        .with_justify(JustifyText::Left),
+                    text_anchor: Anchor::CenterLeft,
+                    transform: Transform::from_translation(Vec3::new(
+                        -MENU_SIZE.x / 2.0 + 40.0,
+                        0.0,
+                        1.0,
+                    )),
+                    ..default()
+                },
+                MenuText,
+                UI_LAYER,
+            ));
+            builder.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 20.0,
+                            ..text_style.clone()
+                        },
+                    ),
+                    text_anchor: Anchor::TopCenter,
+                    transform: Transform::from_translation(Vec3::new(
+                        MENU_SIZE.x / 4.0 + 20.0,
+                        -MENU_PREVIEW_SIZE / 2.0 - 30.0,
+                        1.0,
+                    )),
+                    ..default()
+                },
+                MenuLeaderboard,
+                UI_LAYER,
+            ));
+        });
+
+    // settings page
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgba(0.0, 0.0, 0.0, 0.85),
+                    custom_size: Some(Vec2::new(600.0, MENU_SIZE.y)),
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::new(0.0, 0.0, 4.0)),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            SettingsPanel,
+            UI_LAYER,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                Text2dBundle {
+                    // small enough for every setting and key binding to fit
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 14.0,
+                            ..text_style.clone()
+                        },
+                    ),
+                    transform: Transform::from_translation(Vec3::Z),
+                    ..default()
+                },
+                SettingsText,
+                UI_LAYER,
+            ));
+        });
+
+    // "enter code" dialog
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgba(0.0, 0.0, 0.0, 0.85),
+                    custom_size: Some(Vec2::new(MENU_SIZE.x, 400.0)),
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::new(0.0, 0.0, 4.0)),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            CodePanel,
+            UI_LAYER,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 24.0,
+                            ..text_style.clone()
+                        },
+                    ),
+                    transform: Transform::from_translation(Vec3::Z),
+                    ..default()
+                },
+                CodeText,
+                UI_LAYER,
+            ));
+        });
+
+    // stats page
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgba(0.0, 0.0, 0.0, 0.85),
+                    custom_size: Some(MENU_SIZE),
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::new(0.0, 0.0, 4.0)),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            StatsPanel,
+            UI_LAYER,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 24.0,
+                            ..text_style.clone()
+                        },
+                    )
+                    .with_justify(JustifyText::Left),
+                    transform: Transform::from_translation(Vec3::Z),
+                    ..default()
+                },
+                StatsText,
+                UI_LAYER,
+            ));
+        });
+
+    // hint explanations
+    let hint_box_size = Vec2::new(240.0, 400.0);
+    commands.spawn((
+        Text2dBundle {
+            text: Text {
+                sections: vec![TextSection::new(
+                    "",
+                    TextStyle {
+                        font_size: 24.0,
+                        ..text_style.clone()
+                    },
+                )],
+                justify: JustifyText::Left,
+                linebreak_behavior: BreakLineOn::WordBoundary,
+            },
+            text_2d_bounds: Text2dBounds {
+                size: hint_box_size,
+            },
+            ..default()
+        },
+        HintText,
+        ScreenAnchor::new(Vec2::X, Vec2::new(-140.0, -240.0)),
+        ThemedText,
+        ScaledText::wrapped(24.0, hint_box_size),
+        UI_LAYER,
+    ));
+
+    // tutorial
+    let tutorial_text_size = TUTORIAL_PANEL_SIZE - Vec2::splat(20.0);
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgba(0.0, 0.0, 0.0, 0.85),
+                    custom_size: Some(TUTORIAL_PANEL_SIZE),
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::new(0.0, 0.0, 3.0)),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            TutorialPanel,
+            // under the strip along the top
+            ScreenAnchor::new(
+                Vec2::Y,
+                Vec2::new(0.0, -HUD_SIZE.y - TUTORIAL_PANEL_SIZE.y / 2.0 - 10.0),
+            ),
+            UI_LAYER,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                Text2dBundle {
+                    text: Text {
+                        sections: vec![TextSection::new(
+                            "",
+                            TextStyle {
+                                font_size: 24.0,
+                                ..text_style.clone()
+                            },
+                        )],
+                        justify: JustifyText::Center,
+                        linebreak_behavior: BreakLineOn::WordBoundary,
+                    },
+                    text_2d_bounds: Text2dBounds {
+                        size: tutorial_text_size,
+                    },
+                    transform: Transform::from_translation(Vec3::Z),
+                    ..default()
+                },
+                TutorialText,
+                ScaledText::wrapped(24.0, tutorial_text_size),
+                UI_LAYER,
+            ));
+        });
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::srgba(1.0, 0.85, 0.2, 0.0),
+                ..default()
+            },
+            // behind the cells, so only the edge of the glow shows
+            transform: Transform::from_translation(Vec3::new(0.0, 0.0, -0.5)),
+            ..default()
+        },
+        TutorialHighlight,
+    ));
+
+    // cursor
+    commands.spawn((
+        SpriteBundle {
+            texture: asset_server
+                .load_with_settings("cursor.png", |settings: &mut ImageLoaderSettings| {
+                    settings.sampler = ImageSampler::nearest()
+                }),
+            sprite: Sprite {
+                color: theme.cursor,
+                ..default()
+            },
+            transform: Transform {
+                scale: cursor_scale(&settings, &theme),
+                ..default()
+            },
+            ..default()
+        },
+        Cursor,
+        GridComponent::splat(0),
+    ));
+
+    let load_nearest = |path: &'static str| {
+        asset_server.load_with_settings(path, |settings: &mut ImageLoaderSettings| {
+            settings.sampler = ImageSampler::nearest()
+        })
+    };
+    let texture = load_nearest("tile_sheet.png");
+    let patterned = load_nearest("tile_sheet_patterned.png");
+    let layout = TextureAtlasLayout::from_grid(
+        UVec2::splat(16),
+        3,
+        5,
+        Some(UVec2::splat(2)),
+        Some(UVec2::splat(1)),
+    );
+    let tile_sheet = TileSheet {
+        texture,
+        patterned,
+        // loaded by `load_skin` along with the settings
+        skin: None,
+        layout: texture_atlas_layouts.add(layout),
+    };
+
+    spawn_grid(&mut commands, grid, &tile_sheet, &theme, settings.cell_size);
+    commands.insert_resource(tile_sheet);
+    next_state.set(GameState::Menu);
+}
+
+/// Spawns a cell entity for every tile of `grid`. The cells of a large board
+/// get no sprite, since the board is drawn as one mesh instead.
+fn spawn_grid(
+    commands: &mut Commands,
+    grid: &Grid,
+    tile_sheet: &TileSheet,
+    theme: &Theme,
+    cell_size: f32,
+) {
+    let grid_size = grid.grid_size;
+    let batched = board_mesh::is_batched(&grid_size);
+    // nothing is drawn for an empty overlay or outline
+    let visible = |color: Color| {
+        if color == Color::NONE {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        }
+    };
+
+    for row in 0..grid_size.rows {
+        for column in 0..grid_size.cols {
+            let brick_position = cell_position(&grid_size, cell_size, row, column);
+            let transform = Transform {
+                translation: brick_position.extend(0.0),
+                scale: Vec3::new(cell_size / 16.0, cell_size / 16.0, 1.0),
+                ..default()
+            };
+
+            // cell
+            let mut cell =
+                commands.spawn((Cell(grid.get(row, column)), GridComponent::new(row, column)));
+            if batched {
+                cell.insert(SpatialBundle::from_transform(transform));
+            } else {
+                cell.insert((
+                    SpriteBundle {
+                        transform,
+                        sprite: Sprite {
+                            color: theme.tile,
+                            ..default()
+                        },
+                        texture: tile_sheet.texture_for(theme).clone(),
+                        ..default()
+                    },
+                    TextureAtlas {
+                        layout: tile_sheet.layout.clone(),
+                        ..default()
+                    },
+                ));
+            }
+            cell.with_children(|builder| {
+                builder.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: Color::NONE,
+                            custom_size: Some(Vec2::splat(16.0)),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(Vec3::Z * 0.1),
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    },
+                    AnnotationOverlay,
+                ));
+                // behind the cell, sticking out past its edges
+                builder.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: theme.outline,
+                            custom_size: Some(Vec2::splat(16.0 + 2.0 * CELL_OUTLINE_WIDTH)),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(Vec3::Z * -0.1),
+                        visibility: visible(theme.outline),
+                        ..default()
+                    },
+                    CellOutline,
+                ));
+            });
+        }
+    }
+
+    commands.insert_resource(BoardSize(grid_size));
+}
+
+/// A puzzle being generated on another thread, since a big one can take
+/// seconds. The board stays playable until it is ready.
+#[derive(Resource, Default)]
+pub struct PuzzleGeneration(Option<Job<Option<Puzzle>>>);
+
+impl PuzzleGeneration {
+    /// Starts generating a puzzle the size of `grid_size`, with at least two
+    /// rows and columns, since a single one can't hold a clue, and no more
+    /// than the generator takes on. Any puzzle already being generated is
+    /// forgotten.
+    fn start(&mut self, grid_size: GridSize, difficulty_cache: &DifficultyCache) {
+        let grid_size = GridSize {
+            rows: grid_size.rows.clamp(2, generator::MAX_GENERATED_SIZE),
+            cols: grid_size.cols.clamp(2, generator::MAX_GENERATED_SIZE),
+        };
+        self.spawn(PuzzleSource::Generated, difficulty_cache, move || {
+            generator::generate(grid_size, &mut rand::thread_rng())
+        });
+    }
+
+    /// Starts generating the daily puzzle for `date`, which can be played
+    /// again on any later day.
+    fn start_daily(&mut self, date: Date, difficulty_cache: &DifficultyCache) {
+        self.spawn(PuzzleSource::Daily(date), difficulty_cache, move || {
+            daily::daily_puzzle(date)
+        });
+    }
+
+    fn spawn(
+        &mut self,
+        source: PuzzleSource,
+        difficulty_cache: &DifficultyCache,
+        generate: impl FnOnce() -> Option<Grid> + Send + 'static,
+    ) {
+        let difficulty_cache = difficulty_cache.clone();
+        self.0 = Some(Job::spawn(AsyncComputeTaskPool::get(), move || {
+            let game_grid = generate()?;
+            let difficulty = difficulty_cache.rate(&game_grid);
+            Some(Puzzle::new(game_grid, source, difficulty))
+        }));
+    }
+
+    fn is_running(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+/// Starts generating a puzzle the same size as the current one.
+fn generate_puzzle(
+    puzzle: Res<Puzzle>,
+    difficulty_cache: Res<DifficultyCache>,
+    mut generation: ResMut<PuzzleGeneration>,
+) {
+    generation.start(puzzle.game_grid.grid_size, &difficulty_cache);
+}
+
+/// Starts the generated puzzle once it is ready.
+fn finish_generating(
+    mut generation: ResMut<PuzzleGeneration>,
+    mut start_puzzle_ev: EventWriter<StartPuzzle>,
+) {
+    let Some(generated) = generation.0.as_mut().and_then(Job::poll) else {
+        return;
+    };
+    generation.0 = None;
+    match generated {
+        Some(puzzle) => {
+            start_puzzle_ev.send(StartPuzzle(puzzle));
+        }
+        None => warn!("couldn't generate a puzzle, try again"),
+    }
+}
+
+/// A puzzle read from elsewhere being checked for a solution and rated on
+/// another thread, since the solver can take a while on a big or hard one.
+#[derive(Resource, Default)]
+pub struct PuzzleImport(Option<Job<Result<Puzzle, String>>>);
+
+impl PuzzleImport {
+    /// Starts checking `game_grid`, to play it as a puzzle from `source` if
+    /// it has a solution, or else to warn that `unsolvable`. Any puzzle
+    /// already being checked is forgotten.
+    fn start(
+        &mut self,
+        game_grid: Grid,
+        source: PuzzleSource,
+        unsolvable: &str,
+        difficulty_cache: &DifficultyCache,
+    ) {
+        let unsolvable = unsolvable.to_string();
+        let difficulty_cache = difficulty_cache.clone();
+        self.0 = Some(Job::spawn(AsyncComputeTaskPool::get(), move || {
+            // a puzzle without a solution has no rating either
+            let difficulty = difficulty_cache.rate(&game_grid).ok_or(unsolvable)?;
+            Ok(Puzzle::new(game_grid, source, Some(difficulty)))
+        }));
+    }
+
+    fn is_running(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+/// Starts the checked puzzle once it turns out to have a solution. If it has
+/// none, the "enter code" dialog says so while it is open.
+fn finish_import(
+    game_state: Res<State<GameState>>,
+    mut import: ResMut<PuzzleImport>,
+    mut code_entry: ResMut<CodeEntry>,
+    mut start_puzzle_ev: EventWriter<StartPuzzle>,
+) {
+    let Some(imported) = import.0.as_mut().and_then(Job::poll) else {
+        return;
+    };
+    import.0 = None;
+    match imported {
+        Ok(puzzle) => {
+            start_puzzle_ev.send(StartPuzzle(puzzle));
+        }
+        Err(err) if *game_state == GameState::EnterCode => code_entry.error = Some(err),
+        Err(err) => warn!("{}", err),
+    }
+}
+
+/// Starts the puzzle file after the current one, wrapping around to the first
+/// file and skipping any without a solution. A puzzle in a pack is followed
+/// by the next one in the pack.
+fn play_next_puzzle(
+    puzzle: Res<Puzzle>,
+    puzzle_paths: Res<PuzzlePaths>,
+    packs: Res<Packs>,
+    difficulty_cache: Res<DifficultyCache>,
+    mut start_puzzle_ev: EventWriter<StartPuzzle>,
+) {
+    let in_pack = match &puzzle.source {
+        PuzzleSource::File(path) => packs.find(path),
+        _ => None,
+    };
+    let paths = match in_pack {
+        Some((pack, _)) => &packs.0[pack].puzzles,
+        None => &puzzle_paths.0,
+    };
+    let current = match &puzzle.source {
+        PuzzleSource::File(path) => paths.iter().position(|p| p == path),
+        PuzzleSource::Url(_)
+        | PuzzleSource::Code(_)
+        | PuzzleSource::Clipboard
+        | PuzzleSource::Generated
+        | PuzzleSource::Daily(_)
+        | PuzzleSource::Tutorial
+        | PuzzleSource::Network => None,
+    };
+    // start just after the current file, or at the first one
+    let first = current.map_or(0, |i| i + 1);
+    for i in 0..paths.len() {
+        let path = &paths[(first + i) % paths.len()];
+        if let Some(next) = read_puzzle(path, &difficulty_cache) {
+            start_puzzle_ev.send(StartPuzzle(next));
+            return;
+        }
+    }
+}
+
+/// Starts generating today's daily puzzle.
+fn play_daily_puzzle(
+    difficulty_cache: Res<DifficultyCache>,
+    mut generation: ResMut<PuzzleGeneration>,
+) {
+    generation.start_daily(Date::today(), &difficulty_cache);
+}
+
+/// Reloads the current puzzle file when it changes on disk. The player's
+/// marks carry over if the setting for it is on and the size didn't change.
+fn reload_changed_puzzle(
+    time: Res<Time>,
+    puzzle: Res<Puzzle>,
+    settings: Res<Settings>,
+    mut puzzle_watch: ResMut<PuzzleWatch>,
+    difficulty_cache: Res<DifficultyCache>,
+    mut start_puzzle_ev: EventWriter<StartPuzzle>,
+) {
+    if !puzzle_watch.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let PuzzleSource::File(path) = &puzzle.source else {
+        puzzle_watch.modified = None;
+        return;
+    };
+    let Ok(modified) = fs::metadata(path).and_then(|metadata| metadata.modified()) else {
+        return;
+    };
+    let changed = puzzle_watch
+        .modified
+        .as_ref()
+        .is_some_and(|(watched, last)| watched == path && *last != modified);
+    puzzle_watch.modified = Some((path.clone(), modified));
+    if !changed {
+        return;
+    }
+    let Some(mut reloaded) = read_puzzle(path, &difficulty_cache) else {
+        return;
+    };
+    let grid_size = puzzle.game_grid.grid_size;
+    if settings.keep_marks_on_reload && reloaded.game_grid.grid_size == grid_size {
+        for (row, col, mark) in puzzle.game_grid.iter() {
+            if matches!(
+                mark,
+                CellState::Island | CellState::River | CellState::Maybe
+            ) && !matches!(reloaded.game_grid.get(row, col), CellState::Value(_))
+            {
+                reloaded.set(row, col, mark);
+                let locked = puzzle.game_grid.is_locked(row, col);
+                reloaded.game_grid.set_locked(row, col, locked);
+            }
+        }
+    }
+    info!("{:?} changed on disk, reloading it", path);
+    start_puzzle_ev.send(StartPuzzle(reloaded));
+}
+
+/// Copies the current puzzle to the clipboard as a puzz.link URL, so it can be
+/// shared or played in other solvers.
+fn copy_puzzle_url(puzzle: Res<Puzzle>) {
+    let url = puzzle.game_grid.to_puzz_link();
+    match clipboard::copy(&url) {
+        Ok(()) => info!("Copied {}", url),
+        Err(err) => warn!("couldn't copy {} to the clipboard: {}", url, err),
+    }
+}
+
+/// Copies the board, marks included, to the clipboard as text, for pasting
+/// into forums or bug reports.
+fn copy_board_text(puzzle: Res<Puzzle>) {
+    let text = puzzle.game_grid.to_string();
+    match clipboard::copy(&text) {
+        Ok(()) => info!("Copied the board:\n{}", text),
+        Err(err) => warn!("couldn't copy the board to the clipboard: {}", err),
+    }
+}
+
+/// Plays the puzzle on the clipboard. The text can be a puzz.link URL, a
+/// share code, an ASCII grid, JSON or a puzzle file. A shared replay opens
+/// in the replay viewer instead, and the address of a puzzle file or pack
+/// manifest is downloaded into the puzzle folder.
+fn paste_puzzle(
+    mut shared_replay: ResMut<SharedReplay>,
+    mut download: ResMut<PuzzleDownload>,
+    mut import: ResMut<PuzzleImport>,
+    difficulty_cache: Res<DifficultyCache>,
+    mut start_puzzle_ev: EventWriter<StartPuzzle>,
+) {
+    let text = match clipboard::paste() {
+        Ok(text) => text,
+        Err(err) => {
+            warn!("couldn't read the clipboard: {}", err);
+            return;
+        }
+    };
+    if let Ok(replay) = replay::decode(&text) {
+        shared_replay.watch(replay, &difficulty_cache, &mut start_puzzle_ev);
+        return;
+    }
+    let game_grid = match format::parse_any(&text) {
+        Ok(game_grid) => game_grid,
+        Err(_) if download::is_url(&text) => {
+            download.start(text.trim());
+            return;
+        }
+        Err(err) => {
+            warn!("the clipboard doesn't hold a puzzle: {}", err);
+            return;
+        }
+    };
+    import.start(
+        game_grid,
+        PuzzleSource::Clipboard,
+        "the puzzle on the clipboard has no solution",
+        &difficulty_cache,
+    );
+}
+
+/// A puzzle file or pack being downloaded on another thread, since the
+/// download program can take up to its timeout for every file.
+#[derive(Resource, Default)]
+pub struct PuzzleDownload(Option<(String, Job<io::Result<Downloaded>>)>);
+
+impl PuzzleDownload {
+    /// Starts downloading `url`, unless something is being downloaded
+    /// already. That one is left to finish, since it saves its files either
+    /// way.
+    fn start(&mut self, url: &str) {
+        if let Some((current, _)) = &self.0 {
+            warn!("still downloading {}, try again once it is done", current);
+            return;
+        }
+        let job = Job::spawn(IoTaskPool::get(), {
+            let url = url.to_string();
+            move || download::download(&url)
+        });
+        self.0 = Some((url.to_string(), job));
+    }
+
+    fn is_running(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+/// Plays a downloaded puzzle once it is saved, or opens the pack list on a
+/// downloaded pack.
+fn finish_download(
+    mut download: ResMut<PuzzleDownload>,
+    mut puzzle_paths: ResMut<PuzzlePaths>,
+    mut packs: ResMut<Packs>,
+    difficulty_cache: Res<DifficultyCache>,
+    mut start_puzzle_ev: EventWriter<StartPuzzle>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(downloaded) = download.0.as_mut().and_then(|(_, job)| job.poll()) else {
+        return;
+    };
+    let Some((url, _)) = download.0.take() else {
+        return;
+    };
+    match downloaded {
+        Ok(Downloaded::Puzzle(path)) => {
+            info!("Downloaded the puzzle to {:?}", path);
+            if let Some(puzzle) = read_puzzle(&path, &difficulty_cache) {
+                if !puzzle_paths.0.contains(&path) {
+                    puzzle_paths.0.push(path);
+                    puzzle_paths.0.sort();
+                }
+                start_puzzle_ev.send(StartPuzzle(puzzle));
+            }
+        }
+        Ok(Downloaded::Pack(dir)) => {
+            info!("Downloaded the pack to {:?}", dir);
+            *packs = Packs::load();
+            next_state.set(GameState::Packs);
+        }
+        Err(err) => warn!("couldn't download {}: {}", url, err),
+    }
+}
+
+/// Plays a puzzle file dropped onto the window, and adds it to the puzzle
+/// list until the game is closed. A replay file opens in the replay viewer.
+fn drop_puzzle_file(
+    mut file_drag_and_drop_ev: EventReader<FileDragAndDrop>,
+    mut puzzle_paths: ResMut<PuzzlePaths>,
+    difficulty_cache: Res<DifficultyCache>,
+    mut shared_replay: ResMut<SharedReplay>,
+    mut start_puzzle_ev: EventWriter<StartPuzzle>,
+) {
+    for ev in file_drag_and_drop_ev.read() {
+        let FileDragAndDrop::DroppedFile { path_buf, .. } = ev else {
+            continue;
+        };
+        if path_buf
+            .extension()
+            .is_some_and(|extension| extension == REPLAY_EXTENSION)
+        {
+            let replay = fs::read_to_string(path_buf)
+                .map_err(|err| err.to_string())
+                .and_then(|text| replay::decode(&text).map_err(|err| err.to_string()));
+            match replay {
+                Ok(replay) => shared_replay.watch(replay, &difficulty_cache, &mut start_puzzle_ev),
+                Err(err) => warn!("couldn't read the replay {:?}: {}", path_buf, err),
+            }
+            continue;
+        }
+        let Some(puzzle) = read_puzzle(path_buf, &difficulty_cache) else {
+            continue;
+        };
+        if !puzzle_paths.0.contains(path_buf) {
+            puzzle_paths.0.push(path_buf.clone());
+        }
+        start_puzzle_ev.send(StartPuzzle(puzzle));
+    }
+}
+
+/// Saves a picture of the board, marks included, as a PNG file in the exports
+/// folder.
+fn export_png(puzzle: Res<Puzzle>) {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let path = Path::new(EXPORT_DIR).join(format!("board-{}.png", seconds));
+    let image = render::render(&puzzle.game_grid, EXPORT_CELL_SIZE);
+    let result = fs::create_dir_all(EXPORT_DIR)
+        .and_then(|()| fs::File::create(&path))
+        .map_err(|err| err.to_string())
+        .and_then(|file| image.write_png(file).map_err(|err| err.to_string()));
+    match result {
+        Ok(()) => info!("Saved the board to {:?}", path),
+        Err(err) => warn!("couldn't save the board to {:?}: {}", path, err),
+    }
+}
+
+/// Remembers the marks on the current board, so they come back the next
+/// time the puzzle is opened.
+fn save_board(puzzle: Option<Res<Puzzle>>, mut saved_games: ResMut<SavedGames>) {
+    if let Some(puzzle) = puzzle {
+        saved_games.store(&puzzle.game_grid);
+    }
+}
+
+/// Saves the board once it has gone `AUTOSAVE_DELAY_SECONDS` without a
+/// change.
+fn autosave_board(
+    time: Res<Time>,
+    puzzle: Option<Res<Puzzle>>,
+    mut autosave: ResMut<Autosave>,
+    mut saved_games: ResMut<SavedGames>,
+) {
+    let Some(puzzle) = puzzle else {
+        return;
+    };
+    if puzzle.is_changed() {
+        autosave.pending = true;
+        autosave.timer.reset();
+        return;
+    }
+    if autosave.pending && autosave.timer.tick(time.delta()).finished() {
+        autosave.pending = false;
+        saved_games.store(&puzzle.game_grid);
+    }
+}
+
+/// Fills in the sea that follows from the clues alone as a single undoable
+/// move, on a board the player hasn't marked yet.
+fn prefill_sea(puzzle: &mut Puzzle) {
+    if puzzle.game_grid.has_marks() {
+        return;
+    }
+    let Ok(cells) = deduction::forced_sea(&puzzle.game_grid) else {
+        return;
+    };
+    if cells.is_empty() {
+        return;
+    }
+    let mut moves = Vec::new();
+    for (row, col) in cells {
+        puzzle.set(row, col, CellState::River);
+        moves.push(Move {
+            row,
+            col,
+            from: CellState::Blank,
+            to: CellState::River,
+        });
+    }
+    puzzle.history.record(moves);
+}
+
+/// Swaps the board over to the puzzle from the latest `StartPuzzle` event,
+/// with any marks saved from the last time it was played.
+fn start_puzzle(
+    mut commands: Commands,
+    cells: Query<Entity, With<Cell>>,
+    mut cursor: Query<&mut GridComponent, With<Cursor>>,
+    board: (Res<TileSheet>, Res<Theme>, Res<Settings>, Res<SavedGames>),
+    mut start_puzzle_ev: EventReader<StartPuzzle>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let (tile_sheet, theme, settings, saved_games) = board;
+    let Some(StartPuzzle(puzzle)) = start_puzzle_ev.read().last() else {
+        return;
+    };
+    let mut puzzle = puzzle.clone();
+    // the tutorial always starts from a clean board, so its steps line up,
+    // and a shared puzzle comes with everyone's marks already on it
+    if !matches!(
+        puzzle.source,
+        PuzzleSource::Tutorial | PuzzleSource::Network
+    ) {
+        puzzle.restore(&saved_games);
+        if settings.prefill_sea {
+            prefill_sea(&mut puzzle);
+        }
+    }
+    for entity in &cells {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_grid(
+        &mut commands,
+        &puzzle.game_grid,
+        &tile_sheet,
+        &theme,
+        settings.cell_size,
+    );
+    commands.insert_resource(puzzle);
+    *cursor.single_mut() = GridComponent::splat(0);
+    next_state.set(GameState::Playing);
+}
+
+/// Keeps the strip along the top up to date. The time is left off if the
+/// player would rather not see it.
+fn update_hud_text(
+    puzzle: Res<Puzzle>,
+    generation: Res<PuzzleGeneration>,
+    download: Res<PuzzleDownload>,
+    import: Res<PuzzleImport>,
+    settings: Res<Settings>,
+    solve_timer: Res<SolveTimer>,
+    mut hud_text: Query<&mut Text, With<HudText>>,
+) {
+    let GridSize { rows, cols } = puzzle.game_grid.grid_size;
+    let difficulty = match puzzle.difficulty {
+        Some(difficulty) => difficulty.to_string(),
+        None => "?".to_string(),
+    };
+    let mut hud = format!(
+        "{}    {}x{}    Difficulty: {}",
+        puzzle.source.name(),
+        cols,
+        rows,
+        difficulty
+    );
+    if settings.show_timer {
+        hud.push_str("    ");
+        hud.push_str(&progress::format_time(solve_timer.0.elapsed_secs()));
+    }
+    if generation.is_running() {
+        hud.push_str("    Generating a new puzzle...");
+    }
+    if download.is_running() {
+        hud.push_str("    Downloading...");
+    }
+    if import.is_running() {
+        hud.push_str("    Checking the puzzle...");
+    }
+    let mut text = hud_text.single_mut();
+    // only touch the text when something changes, like the seconds ticking
+    // over, so it isn't laid out again every frame
+    if text.sections[0].value != hud {
+        text.sections[0].value = hud;
+    }
+}
+
+/// Plays the sound for marking sea or island whenever the player changes a
+/// cell. Clearing a cell is quiet.
+fn play_mark_sounds(
+    mut cell_marked_ev: EventReader<CellMarked>,
+    mut sound_ev: EventWriter<PlaySound>,
+) {
+    for CellMarked { state, .. } in cell_marked_ev.read() {
+        match state {
+            CellState::River => {
+                sound_ev.send(PlaySound(Sound::MarkSea));
+            }
+            CellState::Island => {
+                sound_ev.send(PlaySound(Sound::MarkIsland));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn play_win_sound(mut sound_ev: EventWriter<PlaySound>) {
+    sound_ev.send(PlaySound(Sound::Win));
+}
+
+/// Remembers the day once its daily puzzle is solved, and the best time on
+/// each puzzle file. A solve the solver helped with doesn't count towards
+/// the best time.
+fn record_win(
+    puzzle: Res<Puzzle>,
+    solve_timer: Res<SolveTimer>,
+    mut replays: ResMut<Replays>,
+    mut daily_progress: ResMut<DailyProgress>,
+    mut puzzle_progress: ResMut<PuzzleProgress>,
+    mut stats: ResMut<Stats>,
+    mut last_solve: ResMut<LastSolve>,
+) {
+    let seconds = solve_timer.0.elapsed_secs();
+    replays.store(&puzzle.replay);
+    *last_solve = LastSolve {
+        seconds,
+        moves: puzzle.moves,
+        new_record: false,
+        place: None,
+        solver_helped: puzzle.solver_helped,
+    };
+    stats.record_solve(&puzzle.game_grid, seconds);
+    last_solve.new_record = match &puzzle.source {
+        PuzzleSource::Daily(date) => {
+            daily_progress.complete(*date);
+            false
+        }
+        // a solve the solver helped with still counts as one, but not
+        // towards the best time
+        PuzzleSource::File(_) if puzzle.solver_helped => false,
+        PuzzleSource::File(path) => {
+            // the first solve sets the record rather than beating one
+            let solved_before = puzzle_progress.record(path).is_some();
+            puzzle_progress.complete(path, seconds, puzzle.moves) && solved_before
+        }
+        PuzzleSource::Url(_)
+        | PuzzleSource::Code(_)
+        | PuzzleSource::Clipboard
+        | PuzzleSource::Generated
+        | PuzzleSource::Tutorial
+        | PuzzleSource::Network => false,
+    };
+}
+
+/// Puts the solve on the puzzle's leaderboard if it was fast enough.
+fn record_leaderboard_time(
+    puzzle: Res<Puzzle>,
+    mut leaderboards: ResMut<Leaderboards>,
+    mut last_solve: ResMut<LastSolve>,
+) {
+    if last_solve.solver_helped {
+        return;
+    }
+    last_solve.place = leaderboards.record(
+        &puzzle.game_grid,
+        leaderboards::Entry {
+            seconds: last_solve.seconds,
+            moves: last_solve.moves,
+            date: Date::today(),
+        },
+    );
+}
+
+/// Adds this frame's moves to the replay of the solve. Runs after `Update`,
+/// so the winning move is in the replay before the win is recorded.
+/// The board doesn't change, so this leaves the puzzle unmarked to keep the
+/// systems watching it from running a second time for the same move.
+fn stamp_replay_moves(solve_timer: Res<SolveTimer>, puzzle: Option<ResMut<Puzzle>>) {
+    if let Some(mut puzzle) = puzzle {
+        if !puzzle.unstamped.is_empty() {
+            puzzle
+                .bypass_change_detection()
+                .stamp_moves(solve_timer.0.elapsed_secs());
+        }
+    }
+}
+
+/// Adds the time spent on the current puzzle to the stats, when leaving it.
+fn record_play_time(
+    puzzle: Option<Res<Puzzle>>,
+    solve_timer: Res<SolveTimer>,
+    mut stats: ResMut<Stats>,
+) {
+    if let Some(puzzle) = puzzle {
+        stats.record_play_time(&puzzle.game_grid, solve_timer.0.elapsed_secs());
+    }
+}
+
+/// Counts up while the puzzle is being played, and starts over with each new
+/// puzzle.
+fn tick_solve_timer(
+    time: Res<Time>,
+    game_state: Res<State<GameState>>,
+    mut solve_timer: ResMut<SolveTimer>,
+    mut start_puzzle_ev: EventReader<StartPuzzle>,
+) {
+    if start_puzzle_ev.read().count() > 0 {
+        solve_timer.0.reset();
+    }
+    if *game_state == GameState::Playing {
+        solve_timer.0.tick(time.delta());
+    }
+}
+
+fn update_cursor_location(
+    mut cursor: Query<(&mut Transform, &GridComponent), With<Cursor>>,
+    grid_size: Res<BoardSize>,
+    settings: Res<Settings>,
+) {
+    let (mut transform, location) = cursor.single_mut();
+    transform.translation =
+        cell_position(&grid_size, settings.cell_size, location.row, location.col).extend(1.0);
+}
+
+/// Moves and resizes the cells and the cursor when the cell size setting
+/// changes.
+fn apply_cell_size(
+    settings: Res<Settings>,
+    theme: Res<Theme>,
+    grid_size: Res<BoardSize>,
+    mut cells: Query<(&mut Transform, &GridComponent), With<Cell>>,
+    mut cursor: Query<&mut Transform, (With<Cursor>, Without<Cell>)>,
+) {
+    let scale = Vec3::new(settings.cell_size / 16.0, settings.cell_size / 16.0, 1.0);
+    for (mut transform, location) in &mut cells {
+        transform.translation =
+            cell_position(&grid_size, settings.cell_size, location.row, location.col).extend(0.0);
+        transform.scale = scale;
+    }
+    cursor.single_mut().scale = cursor_scale(&settings, &theme);
+}
+
+/// The scale of the cursor sprite, which is drawn the size of a cell times
+/// the theme's cursor scale.
+fn cursor_scale(settings: &Settings, theme: &Theme) -> Vec3 {
+    let scale = settings.cell_size / 16.0 * theme.cursor_scale;
+    Vec3::new(scale, scale, 1.0)
+}
+
+/// Resizes the text around the board for the UI scale. Text that wraps gets
+/// a box that much taller, so it keeps its width next to the board.
+fn apply_ui_scale(
+    settings: Res<Settings>,
+    mut texts: Query<(&mut Text, Option<&mut Text2dBounds>, &ScaledText)>,
+) {
+    for (mut text, bounds, scaled) in &mut texts {
+        for section in &mut text.sections {
+            section.style.font_size = scaled.font_size * settings.ui_scale;
+        }
+        if let (Some(mut bounds), Some(size)) = (bounds, scaled.bounds) {
+            bounds.size = Vec2::new(size.x, size.y * settings.ui_scale);
+        }
+    }
+}
+
+/// Starts a pop on every cell that changed this frame, if animations are on.
+/// Cells that were just spawned for a new puzzle don't pop.
+fn start_cell_pops(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    cells: Query<(Entity, Ref<Cell>)>,
+) {
+    if !settings.animations {
+        return;
+    }
+    for (entity, cell) in &cells {
+        if cell.is_changed() && !cell.is_added() {
+            // the cell may be despawned for a new puzzle before this is applied
+            commands
+                .entity(entity)
+                .try_insert(CellPop(Timer::from_seconds(
+                    CELL_POP_SECONDS,
+                    TimerMode::Once,
+                )));
+        }
+    }
+}
+
+/// Grows each popping cell and shrinks it back to its usual size.
+fn animate_cell_pops(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut cells: Query<(Entity, &mut Transform, &mut CellPop)>,
+) {
+    let size = settings.cell_size / 16.0;
+    for (entity, mut transform, mut pop) in &mut cells {
+        pop.0.tick(time.delta());
+        let growth = if pop.0.finished() || !settings.animations {
+            commands.entity(entity).remove::<CellPop>();
+            0.0
+        } else {
+            (pop.0.fraction() * std::f32::consts::PI).sin() * CELL_POP_GROWTH
+        };
+        let scale = size * (1.0 + growth);
+        transform.scale = Vec3::new(scale, scale, 1.0);
+    }
+}
+
+/// Shows or hides the cell outlines for the theme.
+fn apply_outlines(
+    theme: Res<Theme>,
+    mut outlines: Query<(&mut Sprite, &mut Visibility), With<CellOutline>>,
+) {
+    for (mut sprite, mut visibility) in &mut outlines {
+        sprite.color = theme.outline;
+        *visibility = if theme.outline == Color::NONE {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+    }
+}
+
+/// Switches to the colors of the theme picked in the settings.
+fn update_theme(settings: Res<Settings>, mut theme: ResMut<Theme>) {
+    let picked = Theme::new(&settings);
+    if *theme != picked {
+        *theme = picked;
+    }
+}
+
+/// Colors the background, the board, the cursor and the text around the
+/// board for the theme.
+fn apply_theme(
+    theme: Res<Theme>,
+    mut clear_color: ResMut<ClearColor>,
+    mut cells: Query<&mut Sprite, With<Cell>>,
+    mut cursor: Query<&mut Sprite, (With<Cursor>, Without<Cell>)>,
+    mut texts: Query<&mut Text, With<ThemedText>>,
+) {
+    clear_color.0 = theme.background;
+    for mut sprite in &mut cells {
+        sprite.color = theme.tile;
+    }
+    cursor.single_mut().color = theme.cursor;
+    for mut text in &mut texts {
+        for section in &mut text.sections {
+            section.style.color = theme.text;
+        }
+    }
+}
+
+fn save_settings(settings: Res<Settings>) {
+    settings.save();
+}
+
+/// Clears every cell but the clues and the cells the player locked.
+fn reset_puzzle(
+    actions: Res<Actions>,
+    mut tile_query: Query<(&mut Cell, &GridComponent)>,
+    mut puzzle: ResMut<Puzzle>,
+    mut stats: ResMut<Stats>,
+) {
+    if actions.triggered(Action::Reset) {
+        stats.record_reset(&puzzle.game_grid);
+        let grid = &puzzle.game_grid;
+        let moves: Vec<Move> = grid
+            .iter()
+            .filter(|&(row, col, _)| !grid.is_locked(row, col))
+            .filter_map(|(row, col, tile)| {
+                let reset = match tile {
+                    CellState::Blank | CellState::Island | CellState::River | CellState::Maybe => {
+                        CellState::Blank
+                    }
+                    CellState::Value(_) => tile,
+                };
+                (reset != tile).then_some(Move {
+                    row,
+                    col,
+                    from: tile,
+                    to: reset,
+                })
+            })
+            .collect();
+        for m in &moves {
+            puzzle.set(m.row, m.col, m.to);
+        }
+        // the moves are in row-major order, like the grid they came from
+        for (mut tile, location) in &mut tile_query {
+            if let Ok(index) =
+                moves.binary_search_by_key(&(location.row, location.col), |m| (m.row, m.col))
+            {
+                tile.0 = moves[index].to;
+            }
+        }
+        puzzle.history.record(moves);
+    }
+}
+
+/// Paints the cell under the cursor with one of the annotation colors with the
+/// number keys 1 to 4, or wipes the color off by pressing its key again. The
+/// number keys count moves for the vim keys, so they don't annotate then.
+fn annotate_cell(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    cursor_query: Query<&GridComponent, With<Cursor>>,
+    mut puzzle: ResMut<Puzzle>,
+) {
+    if settings.vim_keys {
+        return;
+    }
+    let Some(color) = ANNOTATION_KEYS
+        .iter()
+        .position(|&key| keyboard_input.just_pressed(key))
+    else {
+        return;
+    };
+    let cursor = cursor_query.single();
+    let annotation = &mut puzzle.annotations[cursor.row][cursor.col];
+    *annotation = if *annotation == Some(color) {
+        None
+    } else {
+        Some(color)
+    };
+}
+
+/// Undoes the last action with Z (or Ctrl+Z), and redoes it with Y (or
+/// Ctrl+Shift+Z). Locked cells are left alone.
+fn undo_redo(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut tile_query: Query<(&mut Cell, &GridComponent)>,
+    mut puzzle: ResMut<Puzzle>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let moves = if keyboard_input.just_pressed(KeyCode::KeyY)
+        || (shift && keyboard_input.just_pressed(KeyCode::KeyZ))
+    {
+        puzzle.history.redo()
+    } else if keyboard_input.just_pressed(KeyCode::KeyZ) {
+        puzzle.history.undo()
+    } else {
+        return;
+    };
+    let Some(moves) = moves else {
+        return;
+    };
+    for m in &moves {
+        let location = GridComponent::new(m.row, m.col);
+        // like a click, neither can change a locked cell
+        if puzzle.game_grid.is_locked(location.row, location.col) {
+            continue;
+        }
+        puzzle.set(location.row, location.col, m.to);
+        if let Some((mut cell, _)) = tile_query.iter_mut().find(|(_, loc)| **loc == location) {
+            cell.0 = m.to;
+        }
+    }
+    if puzzle.is_solved() {
+        next_state.set(GameState::Won);
+    }
+}
+
+/// Whether a cell in `state` can be locked. Only cells the player has made up
+/// their mind about can be, so the solver and hints never touch a locked cell.
+fn can_lock(state: CellState) -> bool {
+    matches!(state, CellState::Island | CellState::River)
+}
+
+/// Changes a cell like `apply_cell`, recording the change in the history.
+fn set_cell(
+    cell: &mut Cell,
+    location: &GridComponent,
+    state: CellState,
+    puzzle: &mut Puzzle,
+    next_state: &mut NextState<GameState>,
+) {
+    puzzle.history.record(vec![Move {
+        row: location.row,
+        col: location.col,
+        from: cell.0,
+        to: state,
+    }]);
+    apply_cell(cell, location, state, puzzle, next_state);
+}
+
+/// Changes a cell on both the board and the puzzle grid, and checks whether
+/// that solved the puzzle.
+fn apply_cell(
+    cell: &mut Cell,
+    location: &GridComponent,
+    state: CellState,
+    puzzle: &mut Puzzle,
+    next_state: &mut NextState<GameState>,
+) {
+    cell.0 = state;
+    puzzle.set(location.row, location.col, state);
+    if !can_lock(state) {
+        puzzle
+            .game_grid
+            .set_locked(location.row, location.col, false);
+    }
+    // check puzzle solved
+    if puzzle.is_solved() {
+        next_state.set(GameState::Won);
+    }
+}
+
+fn update_hovered_cell(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<BoardCamera>>,
+    grid_size: Res<BoardSize>,
+    settings: Res<Settings>,
+    mut hovered_cell: ResMut<HoveredCell>,
+) {
+    let hovered = pointer_position(&windows, &camera)
+        .and_then(|position| cell_at(&grid_size, settings.cell_size, position));
+    if hovered_cell.0 != hovered {
+        hovered_cell.0 = hovered;
+    }
+}
+
+/// Where the mouse pointer is in the world as `camera` sees it, if it is over
+/// the window.
+fn pointer_position<F: QueryFilter>(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    camera: &Query<(&Camera, &GlobalTransform), F>,
+) -> Option<Vec2> {
+    let (camera, camera_transform) = camera.single();
+    windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+        .and_then(|position| camera.viewport_to_world_2d(camera_transform, position))
+}
+
+/// Paints cells while a mouse button is held. With the left button the first
+/// cell clicked cycles like pressing space, the right button marks island and
+/// the middle button clears. Every cell the pointer passes over takes the same
+/// state as the first, and the keyboard cursor follows the pointer.
+fn paint_cells(
+    pointer: (Res<ButtonInput<MouseButton>>, Res<HoveredCell>),
+    mut cursor: Query<&mut GridComponent, With<Cursor>>,
+    mut tile_query: Query<(&mut Cell, &GridComponent), Without<Cursor>>,
+    mut puzzle: ResMut<Puzzle>,
+    mut stroke: ResMut<PaintStroke>,
+    mut cell_marked_ev: EventWriter<CellMarked>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let (mouse_input, hovered_cell) = pointer;
+    let Some(button) = stroke.button.or_else(|| {
+        PAINT_BUTTONS
+            .into_iter()
+            .find(|&button| mouse_input.just_pressed(button))
+    }) else {
+        return;
+    };
+    if !mouse_input.pressed(button) {
+        return;
+    }
+    stroke.button = Some(button);
+    let Some(location) = hovered_cell.0 else {
+        return;
+    };
+    if stroke.visited.contains(&location) {
+        return;
+    }
+    stroke.visited.push(location);
+    *cursor.single_mut() = location;
+    let Some((mut cell, _)) = tile_query.iter_mut().find(|(_, loc)| **loc == location) else {
+        return;
+    };
+    if matches!(cell.0, CellState::Value(_))
+        || puzzle.game_grid.is_locked(location.row, location.col)
+    {
+        return;
+    }
+    let state = *stroke.state.get_or_insert(match button {
+        MouseButton::Right => CellState::Island,
+        MouseButton::Middle => CellState::Blank,
+        _ => cell.0.next(),
+    });
+    if cell.0 == state {
+        return;
+    }
+    stroke.moves.push(Move {
+        row: location.row,
+        col: location.col,
+        from: cell.0,
+        to: state,
+    });
+    puzzle.moves += 1;
+    apply_cell(&mut cell, &location, state, &mut puzzle, &mut next_state);
+    cell_marked_ev.send(CellMarked { location, state });
+}
+
+/// Turns the blank cells around an island to river once a mark brings the
+/// island up to the size of its clue, if the setting for it is on. The river
+/// is undone along with the paint stroke that finished the island, or on
+/// its own after a key press.
+fn surround_complete_islands(
+    settings: Res<Settings>,
+    mut tile_query: Query<(&mut Cell, &GridComponent)>,
+    mut puzzle: ResMut<Puzzle>,
+    mut stroke: ResMut<PaintStroke>,
+    mut cell_marked_ev: EventReader<CellMarked>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !settings.auto_surround {
+        cell_marked_ev.clear();
+        return;
+    }
+    for CellMarked { location, .. } in cell_marked_ev.read() {
+        let Some(island) = puzzle.game_grid.island_at(location.row, location.col) else {
+            continue;
+        };
+        if island.clues != [island.cells.len()] {
+            continue;
+        }
+        let mut moves = Vec::new();
+        for (mut cell, location) in &mut tile_query {
+            let touches_island = puzzle
+                .game_grid
+                .neighbors(location.row, location.col)
+                .iter()
+                .any(|neighbor| island.cells.contains(neighbor));
+            if !cell.0.is_blank() || !touches_island {
+                continue;
+            }
+            moves.push(Move {
+                row: location.row,
+                col: location.col,
+                from: cell.0,
+                to: CellState::River,
+            });
+            apply_cell(
+                &mut cell,
+                location,
+                CellState::River,
+                &mut puzzle,
+                &mut next_state,
+            );
+        }
+        if stroke.button.is_some() {
+            stroke.moves.extend(moves);
+        } else if !moves.is_empty() {
+            puzzle.history.record(moves);
+        }
+    }
+}
+
+/// Records a finished paint stroke as a single undoable action.
+fn finish_paint_stroke(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut puzzle: ResMut<Puzzle>,
+    mut stroke: ResMut<PaintStroke>,
+) {
+    let Some(button) = stroke.button else {
+        return;
+    };
+    if mouse_input.pressed(button) {
+        return;
+    }
+    let stroke = std::mem::take(&mut *stroke);
+    puzzle.history.record(stroke.moves);
+}
+
+/// Cycles the cell under the cursor with space (or A on a gamepad), sets it
+/// straight to island with I (X), and clears it with backspace or delete (Y).
+/// With control held, space puts a maybe mark on the cell or takes it off, and
+/// with shift held it locks a marked cell or unlocks it. Locked cells can't be
+/// changed until they are unlocked.
+fn toggle_cell(
+    input: (Res<Actions>, Res<ButtonInput<KeyCode>>),
+    cursor_query: Query<&GridComponent, With<Cursor>>,
+    mut tile_query: Query<(&mut Cell, &GridComponent)>,
+    mut puzzle: ResMut<Puzzle>,
+    mut cell_marked_ev: EventWriter<CellMarked>,
+    mut sound_ev: EventWriter<PlaySound>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let (actions, keyboard_input) = input;
+    let cycle = actions.triggered(Action::Toggle);
+    let mark = if actions.triggered(Action::MarkIsland) {
+        Some(CellState::Island)
+    } else if actions.triggered(Action::Clear) {
+        Some(CellState::Blank)
+    } else {
+        None
+    };
+    if !cycle && mark.is_none() {
+        return;
+    }
+    let maybe = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let lock = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let cursor_loc = cursor_query.single();
+    for (mut cell, tile_loc) in &mut tile_query {
+        if cursor_loc != tile_loc {
+            continue;
+        }
+        let locked = puzzle.game_grid.is_locked(tile_loc.row, tile_loc.col);
+        if cycle && lock {
+            if locked || can_lock(cell.0) {
+                puzzle
+                    .game_grid
+                    .set_locked(tile_loc.row, tile_loc.col, !locked);
+            }
+            break;
+        }
+        if locked || matches!(cell.0, CellState::Value(_)) {
+            sound_ev.send(PlaySound(Sound::Invalid));
+            break;
+        }
+        let new_cell_state = match (cell.0, mark) {
+            (_, Some(mark)) => mark,
+            (state, None) if maybe => state.next_maybe(),
+            (state, None) => state.next(),
+        };
+        if new_cell_state != cell.0 {
+            puzzle.moves += 1;
+            set_cell(
+                &mut cell,
+                tile_loc,
+                new_cell_state,
+                &mut puzzle,
+                &mut next_state,
+            );
+            cell_marked_ev.send(CellMarked {
+                location: *tile_loc,
+                state: new_cell_state,
+            });
+        }
+        break;
+    }
+}
+
+/// Asks the solver for a cell the player can fill in next. With shift held,
+/// the cell is filled in as well.
+fn show_hint(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut tile_query: Query<(&mut Cell, &GridComponent)>,
+    mut puzzle: ResMut<Puzzle>,
+    mut active_hint: ResMut<ActiveHint>,
+    mut stats: ResMut<Stats>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let hint = match solver::hint(&puzzle.game_grid) {
+        Ok(Some(hint)) => hint,
+        Ok(None) => return,
+        Err(_) => {
+            *active_hint = ActiveHint::Mistake;
+            return;
+        }
+    };
+    *active_hint = ActiveHint::Hint(hint);
+    stats.record_hint(&puzzle.game_grid);
+    if !keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        return;
+    }
+    let location = GridComponent::new(hint.row, hint.col);
+    if let Some((mut cell, _)) = tile_query.iter_mut().find(|(_, loc)| **loc == location) {
+        puzzle.solver_helped = true;
+        set_cell(
+            &mut cell,
+            &location,
+            hint.state,
+            &mut puzzle,
+            &mut next_state,
+        );
+    }
+}
+
+/// Highlights the cell picked by the latest hint.
+fn highlight_hint(
+    mut commands: Commands,
+    active_hint: Res<ActiveHint>,
+    tile_query: Query<(Entity, &GridComponent), With<Cell>>,
+    highlights: Query<Entity, With<HintHighlight>>,
+) {
+    for entity in &highlights {
+        commands.entity(entity).despawn_recursive();
+    }
+    let ActiveHint::Hint(hint) = *active_hint else {
+        return;
+    };
+    let location = GridComponent::new(hint.row, hint.col);
+    let Some((entity, _)) = tile_query.iter().find(|(_, loc)| **loc == location) else {
+        return;
+    };
+    commands.entity(entity).with_children(|builder| {
+        builder.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgba(1.0, 0.85, 0.0, 0.5),
+                    custom_size: Some(Vec2::splat(16.0)),
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::Z * 0.5),
+                ..default()
+            },
+            HintHighlight(Timer::from_seconds(HINT_HIGHLIGHT_SECONDS, TimerMode::Once)),
+        ));
+    });
+}
+
+/// Explains the latest hint in the hint panel.
+fn update_hint_text(active_hint: Res<ActiveHint>, mut hint_text: Query<&mut Text, With<HintText>>) {
+    let mut text = hint_text.single_mut();
+    text.sections[0].value = match *active_hint {
+        ActiveHint::None => String::new(),
+        ActiveHint::Hint(hint) => hint.explanation(),
+        ActiveHint::Mistake => {
+            "Something on the board is wrong: at least one of your marks can't be part of the solution.".to_string()
+        }
+    };
+}
+
+/// Forgets the hint when a different puzzle starts.
+fn clear_hint(mut active_hint: ResMut<ActiveHint>, mut start_puzzle_ev: EventReader<StartPuzzle>) {
+    if start_puzzle_ev.read().count() > 0 {
+        *active_hint = ActiveHint::None;
+    }
+}
+
+/// Runs the tutorial when its puzzle starts, and stops it when any other
+/// puzzle does.
+fn track_tutorial(mut tutorial: ResMut<Tutorial>, mut start_puzzle_ev: EventReader<StartPuzzle>) {
+    let Some(StartPuzzle(puzzle)) = start_puzzle_ev.read().last() else {
+        return;
+    };
+    if puzzle.source == PuzzleSource::Tutorial {
+        tutorial.start();
+    } else {
+        tutorial.stop();
+    }
+}
+
+/// Moves the tutorial on to its next step once the player has done what the
+/// current one asks.
+fn advance_tutorial(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    cursor: Query<&GridComponent, With<Cursor>>,
+    puzzle: Res<Puzzle>,
+    game_state: Res<State<GameState>>,
+    mut tutorial: ResMut<Tutorial>,
+) {
+    let Some(step) = tutorial.step() else {
+        return;
+    };
+    let done = match step.goal {
+        Goal::Continue => keyboard_input.just_pressed(KeyCode::Enter),
+        Goal::MoveTo { row, col } => *cursor.single() == GridComponent::new(row, col),
+        Goal::Mark { row, col, state } => puzzle.game_grid.get(row, col) == state,
+        Goal::Solve => *game_state.get() == GameState::Won,
+        Goal::Done => false,
+    };
+    if done {
+        tutorial.advance();
+    }
+}
+
+/// Keeps the cursor on the cell the tutorial wants marked, so the keyboard
+/// can only change that cell.
+fn pin_tutorial_cursor(
+    tutorial: Res<Tutorial>,
+    mut cursor: Query<&mut GridComponent, With<Cursor>>,
+) {
+    let Some(Goal::Mark { row, col, .. }) = tutorial.step().map(|step| step.goal) else {
+        return;
+    };
+    cursor.single_mut().set_if_neq(GridComponent::new(row, col));
+}
+
+fn tutorial_allows_cursor_cell(
+    tutorial: Res<Tutorial>,
+    cursor: Query<&GridComponent, With<Cursor>>,
+) -> bool {
+    let location = cursor.single();
+    tutorial.allows(location.row, location.col)
+}
+
+fn tutorial_allows_hovered_cell(tutorial: Res<Tutorial>, hovered_cell: Res<HoveredCell>) -> bool {
+    hovered_cell
+        .0
+        .is_none_or(|location| tutorial.allows(location.row, location.col))
+}
+
+fn tutorial_allows_free_play(tutorial: Res<Tutorial>) -> bool {
+    tutorial.allows_free_play()
+}
+
+fn update_tutorial_text(
+    tutorial: Res<Tutorial>,
+    mut panel: Query<&mut Visibility, With<TutorialPanel>>,
+    mut tutorial_text: Query<&mut Text, With<TutorialText>>,
+) {
+    let step = tutorial.step();
+    *panel.single_mut() = if step.is_some() {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    tutorial_text.single_mut().sections[0].value =
+        step.map_or(String::new(), |step| step.text.to_string());
+}
+
+/// Puts a pulsing glow around the cell the tutorial is explaining.
+fn highlight_tutorial_cell(
+    time: Res<Time>,
+    tutorial: Res<Tutorial>,
+    grid_size: Res<BoardSize>,
+    settings: Res<Settings>,
+    mut highlight: Query<(&mut Transform, &mut Sprite), With<TutorialHighlight>>,
+) {
+    let (mut transform, mut sprite) = highlight.single_mut();
+    let Some((row, col)) = tutorial.target() else {
+        sprite.color.set_alpha(0.0);
+        return;
+    };
+    transform.translation = cell_position(&grid_size, settings.cell_size, row, col).extend(-0.5);
+    sprite.custom_size = Some(Vec2::splat(
+        settings.cell_size + 2.0 * TUTORIAL_HIGHLIGHT_MARGIN,
+    ));
+    let pulse = (time.elapsed_seconds() * TUTORIAL_HIGHLIGHT_PULSE * std::f32::consts::TAU).sin();
+    sprite.color.set_alpha(0.6 + 0.3 * pulse);
+}
+
+fn toggle_auto_solve(mut auto_solve: ResMut<AutoSolve>) {
+    auto_solve.active = !auto_solve.active;
+    auto_solve.timer.reset();
+}
+
+/// Halves or doubles the delay between auto-solve moves with `]` and `[`.
+fn adjust_auto_solve_delay(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut auto_solve: ResMut<AutoSolve>,
+) {
+    let delay = auto_solve.timer.duration().as_secs_f32();
+    let delay = if keyboard_input.just_pressed(KeyCode::BracketRight) {
+        delay / 2.0
+    } else if keyboard_input.just_pressed(KeyCode::BracketLeft) {
+        delay * 2.0
+    } else {
+        return;
+    };
+    let delay = delay.clamp(AUTO_SOLVE_MIN_DELAY_SECONDS, AUTO_SOLVE_MAX_DELAY_SECONDS);
+    auto_solve
+        .timer
+        .set_duration(std::time::Duration::from_secs_f32(delay));
+}
+
+/// Fills in one cell from the solver each time the auto-solve timer fires.
+fn auto_solve_step(
+    time: Res<Time>,
+    mut auto_solve: ResMut<AutoSolve>,
+    mut tile_query: Query<(&mut Cell, &GridComponent)>,
+    mut puzzle: ResMut<Puzzle>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !auto_solve.active {
+        return;
+    }
+    if !auto_solve.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Ok(Some(hint)) = solver::hint(&puzzle.game_grid) else {
+        auto_solve.active = false;
+        return;
+    };
+    let location = GridComponent::new(hint.row, hint.col);
+    if let Some((mut cell, _)) = tile_query.iter_mut().find(|(_, loc)| **loc == location) {
+        puzzle.solver_helped = true;
+        set_cell(
+            &mut cell,
+            &location,
+            hint.state,
+            &mut puzzle,
+            &mut next_state,
+        );
+    }
+}
+
+/// Counts and flashes the player's marks that don't match the solution,
+/// while assist mode is on. Clearing a cell is never a mistake.
+fn check_marks(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    puzzle: Res<Puzzle>,
+    mut assist: ResMut<Assist>,
+    tile_query: Query<(Entity, &GridComponent), With<Cell>>,
+    mut cell_marked_ev: EventReader<CellMarked>,
+) {
+    if !settings.assist_mode {
+        cell_marked_ev.clear();
+        return;
+    }
+    for CellMarked { location, state } in cell_marked_ev.read() {
+        if state.is_blank() {
+            continue;
+        }
+        let code = puzzle.to_code();
+        if assist.code != code {
+            // solved once per puzzle, the first time it's needed
+            assist.solution = solver::solve(&puzzle.game_grid.clues_only());
+            assist.code = code;
+        }
+        let Some(solution) = &assist.solution else {
+            continue;
+        };
+        if state.is_same(solution.get(location.row, location.col)) {
+            continue;
+        }
+        assist.mistakes += 1;
+        let Some((entity, _)) = tile_query.iter().find(|(_, loc)| *loc == location) else {
+            continue;
+        };
+        commands.entity(entity).with_children(|builder| {
+            builder.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::srgba(0.9, 0.1, 0.1, 0.5),
+                        custom_size: Some(Vec2::splat(16.0)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(Vec3::Z * 0.5),
+                    ..default()
+                },
+                MistakeFlash(Timer::from_seconds(MISTAKE_FLASH_SECONDS, TimerMode::Once)),
+            ));
+        });
+    }
+}
+
+/// Tints the cells that already break the rules whenever the board changes:
+/// 2x2 pools of river in red, islands that are too big, have two clues or
+/// are sealed off without a clue in orange, and river cut off from the rest
+/// of the sea in purple.
+fn highlight_broken_rules(
+    mut commands: Commands,
+    puzzle: Res<Puzzle>,
+    tile_query: Query<(Entity, &GridComponent), With<Cell>>,
+    highlights: Query<Entity, With<RuleHighlight>>,
+) {
+    for entity in &highlights {
+        commands.entity(entity).despawn_recursive();
+    }
+    let pools = puzzle.game_grid.pools();
+    let bad_islands = puzzle.game_grid.bad_islands();
+    let cut_off_sea = puzzle.game_grid.cut_off_sea();
+    for (entity, location) in &tile_query {
+        let cell = (location.row, location.col);
+        let color = if pools.binary_search(&cell).is_ok() {
+            Color::srgba(0.9, 0.1, 0.1, 0.4)
+        } else if bad_islands.binary_search(&cell).is_ok() {
+            Color::srgba(1.0, 0.5, 0.0, 0.4)
+        } else if cut_off_sea.binary_search(&cell).is_ok() {
+            Color::srgba(0.6, 0.2, 0.9, 0.4)
+        } else {
+            continue;
+        };
+        commands.entity(entity).with_children(|builder| {
+            builder.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(Vec2::splat(16.0)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(Vec3::Z * 0.25),
+                    ..default()
+                },
+                RuleHighlight,
+            ));
+        });
+    }
+}
+
+/// Highlights the island under the cursor and shows its size next to its
+/// clue, whenever the cursor moves or the board changes.
+fn highlight_cursor_island(
+    mut commands: Commands,
+    puzzle: Res<Puzzle>,
+    cursor: Query<Ref<GridComponent>, With<Cursor>>,
+    tile_query: Query<(Entity, &GridComponent), With<Cell>>,
+    highlights: Query<Entity, With<CursorIslandHighlight>>,
+    mut island_text: Query<&mut Text, With<IslandText>>,
+) {
+    let cursor = cursor.single();
+    if !cursor.is_changed() && !puzzle.is_changed() {
+        return;
+    }
+    for entity in &highlights {
+        commands.entity(entity).despawn_recursive();
+    }
+    let island = puzzle.game_grid.island_at(cursor.row, cursor.col);
+    island_text.single_mut().sections[0].value = match &island {
+        None => String::new(),
+        Some(island) => match island.clues[..] {
+            [] => format!("Island: {}", island.cells.len()),
+            [clue] => format!("Island: {} of {}", island.cells.len(), clue),
+            _ => format!(
+                "Island: {}, {} clues",
+                island.cells.len(),
+                island.clues.len()
+            ),
+        },
+    };
+    let Some(mut island) = island else {
+        return;
+    };
+    island.cells.sort_unstable();
+    for (entity, location) in &tile_query {
+        if island
+            .cells
+            .binary_search(&(location.row, location.col))
+            .is_err()
+        {
+            continue;
+        }
+        commands.entity(entity).with_children(|builder| {
+            builder.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::srgba(1.0, 1.0, 1.0, 0.2),
+                        custom_size: Some(Vec2::splat(16.0)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(Vec3::Z * 0.2),
+                    ..default()
+                },
+                CursorIslandHighlight,
+            ));
+        });
+    }
+}
+
+/// Writes how many more cells each clue's island needs in the corner of the
+/// clue, or how many too many it has. Islands with more than one clue get
+/// no count, since they are already highlighted as broken.
+fn update_remaining_counts(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    puzzle: Res<Puzzle>,
+    settings: Res<Settings>,
+    tile_query: Query<(Entity, &GridComponent), With<Cell>>,
+    counts: Query<Entity, With<RemainingText>>,
+) {
+    for entity in &counts {
+        commands.entity(entity).despawn_recursive();
+    }
+    let grid = &puzzle.game_grid;
+    // each island is walked once, and its count put by its only clue
+    let mut remaining: Vec<((usize, usize), i64)> = grid
+        .islands()
+        .into_iter()
+        .filter(|island| island.clues.len() == 1)
+        .filter_map(|island| {
+            island
+                .cells
+                .iter()
+                .find_map(|&(row, col)| match grid.get(row, col) {
+                    CellState::Value(clue) => {
+                        Some(((row, col), clue as i64 - island.cells.len() as i64))
+                    }
+                    _ => None,
+                })
+        })
+        .collect();
+    remaining.sort_unstable();
+    let font = asset_server.load("FiraSans-Regular.ttf");
+    // the text is a child of the cell, so undo the cell's scale to keep it
+    // sharp
+    let scale = 16.0 / settings.cell_size;
+    for (entity, location) in &tile_query {
+        let Ok(index) =
+            remaining.binary_search_by_key(&(location.row, location.col), |&(cell, _)| cell)
+        else {
+            continue;
+        };
+        let remaining = remaining[index].1;
+        commands.entity(entity).with_children(|builder| {
+            builder.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        remaining.to_string(),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: settings.cell_size * 0.3 * settings.ui_scale,
+                            color: Color::srgb(0.2, 0.4, 0.9),
+                        },
+                    ),
+                    text_anchor: Anchor::BottomRight,
+                    transform: Transform {
+                        translation: Vec3::new(7.5, -7.5, 0.75),
+                        scale: Vec3::new(scale, scale, 1.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                RemainingText,
+            ));
+        });
+    }
+}
+
+/// Writes the number of every clue onto its cell, whenever a new board is
+/// spawned or the cell size changes.
+fn update_clue_texts(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    tile_query: Query<(Entity, &Cell)>,
+    clue_texts: Query<Entity, With<ClueText>>,
+) {
+    for entity in &clue_texts {
+        commands.entity(entity).despawn_recursive();
+    }
+    let font = asset_server.load("FiraSans-Regular.ttf");
+    let scale = 16.0 / settings.cell_size;
+    for (entity, cell) in &tile_query {
+        let CellState::Value(clue) = cell.0 else {
+            continue;
+        };
+        commands.entity(entity).with_children(|builder| {
+            builder.spawn((
+                clue_text(clue, font.clone(), settings.cell_size, scale),
+                ClueText,
+            ));
+        });
+    }
+}
+
+/// The number of a clue, drawn at `font_size` and scaled by `scale` to undo
+/// the scale of the cell it sits on.
+fn clue_text(clue: i8, font: Handle<Font>, font_size: f32, scale: f32) -> Text2dBundle {
+    let number = clue.to_string();
+    // longer numbers shrink to stay inside the cell
+    let digits = number.len() as f32;
+    Text2dBundle {
+        text: Text::from_section(
+            number,
+            TextStyle {
+                font,
+                font_size: font_size * 0.6 / digits.sqrt(),
+                color: Color::BLACK,
+            },
+        ),
+        transform: Transform {
+            translation: Vec3::Z * 0.5,
+            scale: Vec3::new(scale, scale, 1.0),
+            ..default()
+        },
+        ..default()
+    }
+}
+
+/// Puts a lock indicator on every locked cell whenever the board changes.
+fn show_locks(
+    mut commands: Commands,
+    puzzle: Res<Puzzle>,
+    tile_query: Query<(Entity, &GridComponent), With<Cell>>,
+    indicators: Query<Entity, With<LockIndicator>>,
+) {
+    for entity in &indicators {
+        commands.entity(entity).despawn_recursive();
+    }
+    for (entity, location) in &tile_query {
+        if !puzzle.game_grid.is_locked(location.row, location.col) {
+            continue;
+        }
+        commands.entity(entity).with_children(|builder| {
+            builder.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::srgb(0.95, 0.75, 0.2),
+                        custom_size: Some(Vec2::splat(4.0)),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(-5.0, 5.0, 0.3),
+                    ..default()
+                },
+                LockIndicator,
+            ));
+        });
+    }
+}
+
+/// Colors each cell's overlay with its annotation.
+fn update_annotations(
+    puzzle: Res<Puzzle>,
+    tile_query: Query<&GridComponent, With<Cell>>,
+    mut overlays: Query<(&Parent, &mut Sprite, &mut Visibility), With<AnnotationOverlay>>,
+) {
+    for (parent, mut sprite, mut visibility) in &mut overlays {
+        let Ok(location) = tile_query.get(parent.get()) else {
+            continue;
+        };
+        let color = puzzle.annotations[location.row][location.col]
+            .map_or(Color::NONE, |color| ANNOTATION_COLORS[color]);
+        if sprite.color != color {
+            sprite.color = color;
+            // nothing is drawn for a cell without an annotation
+            *visibility = if color == Color::NONE {
+                Visibility::Hidden
+            } else {
+                Visibility::Inherited
+            };
+        }
+    }
+}
+
+/// Dims the islands that are finished whenever the board changes, so the
+/// player can tell at a glance which ones are done.
+fn shade_complete_islands(
+    mut commands: Commands,
+    puzzle: Res<Puzzle>,
+    tile_query: Query<(Entity, &GridComponent), With<Cell>>,
+    shades: Query<Entity, With<CompleteIslandShade>>,
+) {
+    for entity in &shades {
+        commands.entity(entity).despawn_recursive();
+    }
+    let mut complete: Vec<(usize, usize)> = puzzle
+        .game_grid
+        .islands()
+        .into_iter()
+        .filter(Island::is_complete)
+        .flat_map(|island| island.cells)
+        .collect();
+    complete.sort_unstable();
+    for (entity, location) in &tile_query {
+        if complete
+            .binary_search(&(location.row, location.col))
+            .is_err()
+        {
+            continue;
+        }
+        commands.entity(entity).with_children(|builder| {
+            builder.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::srgba(0.0, 0.0, 0.0, 0.35),
+                        custom_size: Some(Vec2::splat(16.0)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(Vec3::Z * 0.25),
+                    ..default()
+                },
+                CompleteIslandShade,
+            ));
+        });
+    }
+}
+
+/// Starts the mistake count over with each new puzzle.
+fn reset_mistakes(mut assist: ResMut<Assist>) {
+    assist.mistakes = 0;
+}
+
+fn update_mistake_text(
+    settings: Res<Settings>,
+    assist: Res<Assist>,
+    mut mistake_text: Query<&mut Text, With<MistakeText>>,
+) {
+    mistake_text.single_mut().sections[0].value = if settings.assist_mode {
+        format!("Mistakes: {}", assist.mistakes)
+    } else {
+        String::new()
+    };
+}
+
+fn fade_mistake_flashes(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut flashes: Query<(Entity, &mut MistakeFlash, &mut Sprite)>,
+) {
+    for (entity, mut flash, mut sprite) in &mut flashes {
+        flash.0.tick(time.delta());
+        if flash.0.finished() {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            sprite.color.set_alpha(0.5 * flash.0.fraction_remaining());
+        }
+    }
+}
+
+fn fade_hint_highlights(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut highlights: Query<(Entity, &mut HintHighlight, &mut Sprite)>,
+) {
+    for (entity, mut highlight, mut sprite) in &mut highlights {
+        highlight.0.tick(time.delta());
+        if highlight.0.finished() {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            sprite
+                .color
+                .set_alpha(0.5 * highlight.0.fraction_remaining());
+        }
+    }
+}
+
+/// Opens the main menu, or closes it again and carries on where the player
+/// left off.
+fn toggle_menu(
+    actions: Res<Actions>,
+    game_state: Res<State<GameState>>,
+    mut main_menu: ResMut<MainMenu>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !actions.triggered(Action::Menu) {
+        return;
+    }
+    match game_state.get() {
+        GameState::Playing | GameState::Won | GameState::Spectating => {
+            *main_menu = MainMenu {
+                selected: 0,
+                previous: Some(game_state.get().clone()),
+            };
+            next_state.set(GameState::Menu);
+        }
+        GameState::Menu => {
+            let previous = main_menu.previous.take().unwrap_or(GameState::Playing);
+            next_state.set(previous);
+        }
+        // these pages are opened from the menu, so they go back there
+        GameState::Stats | GameState::Packs | GameState::Calendar => {
+            next_state.set(GameState::Menu)
+        }
+        GameState::Loading
+        | GameState::Settings
+        | GameState::EnterCode
+        | GameState::Replay
+        | GameState::Editor
+        | GameState::Lobby => {}
+    }
+}
+
+/// The entries of the main menu: carrying on, every puzzle file outside a
+/// pack, the packs, the daily and randomly generated puzzles, and the player
+/// profiles.
+fn menu_items(puzzle_paths: &PuzzlePaths) -> Vec<MenuItem> {
+    let mut items = vec![MenuItem::Resume];
+    items.extend(puzzle_paths.0.iter().cloned().map(MenuItem::File));
+    items.push(MenuItem::Packs);
+    items.push(MenuItem::Daily);
+    items.push(MenuItem::Calendar);
+    items.push(MenuItem::Generate);
+    items.push(MenuItem::Multiplayer);
+    items.push(MenuItem::Tutorial);
+    items.push(MenuItem::Stats);
+    items.push(MenuItem::Editor);
+    items.push(MenuItem::Profile);
+    items.push(MenuItem::NewProfile);
+    items
+}
+
+/// Moves through the main menu and picks the selected entry with enter.
+fn menu_input(
+    actions: Res<Actions>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    puzzle_paths: Res<PuzzlePaths>,
+    mut main_menu: ResMut<MainMenu>,
+    mut choose_menu_item_ev: EventWriter<ChooseMenuItem>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let items = menu_items(&puzzle_paths);
+    if actions.triggered(Action::MoveUp) {
+        main_menu.selected = (main_menu.selected + items.len() - 1) % items.len();
+    } else if actions.triggered(Action::MoveDown) {
+        main_menu.selected = (main_menu.selected + 1) % items.len();
+    } else if keyboard_input.just_pressed(KeyCode::Enter) || actions.triggered(Action::Toggle) {
+        match &items[main_menu.selected] {
+            MenuItem::Resume => {
+                let previous = main_menu.previous.take().unwrap_or(GameState::Playing);
+                next_state.set(previous);
+            }
+            MenuItem::Stats => next_state.set(GameState::Stats),
+            MenuItem::Editor => next_state.set(GameState::Editor),
+            MenuItem::Packs => next_state.set(GameState::Packs),
+            MenuItem::Calendar => next_state.set(GameState::Calendar),
+            MenuItem::Multiplayer => next_state.set(GameState::Lobby),
+            item => {
+                choose_menu_item_ev.send(ChooseMenuItem(item.clone()));
+            }
+        }
+    }
+}
+
+/// Loads the puzzle picked in the main menu and starts it.
+fn load_menu_item(
+    puzzle: Res<Puzzle>,
+    mut generation: ResMut<PuzzleGeneration>,
+    difficulty_cache: Res<DifficultyCache>,
+    mut choose_menu_item_ev: EventReader<ChooseMenuItem>,
+    mut start_puzzle_ev: EventWriter<StartPuzzle>,
+) {
+    for ChooseMenuItem(item) in choose_menu_item_ev.read() {
+        let chosen = match item {
+            MenuItem::Resume
+            | MenuItem::Packs
+            | MenuItem::Calendar
+            | MenuItem::Multiplayer
+            | MenuItem::Stats
+            | MenuItem::Editor
+            | MenuItem::Profile
+            | MenuItem::NewProfile => None,
+            MenuItem::File(path) => read_puzzle(path, &difficulty_cache),
+            MenuItem::Daily => {
+                generation.start_daily(Date::today(), &difficulty_cache);
+                None
+            }
+            MenuItem::Generate => {
+                generation.start(puzzle.game_grid.grid_size, &difficulty_cache);
+                None
+            }
+            MenuItem::Tutorial => {
+                let game_grid = tutorial::tutorial_puzzle();
+                let difficulty = difficulty_cache.rate(&game_grid);
+                Some(Puzzle::new(game_grid, PuzzleSource::Tutorial, difficulty))
+            }
+        };
+        if let Some(chosen) = chosen {
+            start_puzzle_ev.send(StartPuzzle(chosen));
+        }
+    }
+}
+
+/// Switches player profiles from the main menu. Everything the old profile
+/// saved is swapped for the new profile's, including its marks on the
+/// current puzzle.
+fn switch_profile(
+    mut commands: Commands,
+    mut profiles: ResMut<Profiles>,
+    mut puzzle: ResMut<Puzzle>,
+    mut saved_games: ResMut<SavedGames>,
+    mut tile_query: Query<(&mut Cell, &GridComponent)>,
+    mut choose_menu_item_ev: EventReader<ChooseMenuItem>,
+) {
+    for ChooseMenuItem(item) in choose_menu_item_ev.read() {
+        // the board has to be saved before the save paths change
+        saved_games.store(&puzzle.game_grid);
+        match item {
+            MenuItem::Profile => profiles.select_next(),
+            MenuItem::NewProfile => profiles.add(),
+            _ => continue,
+        }
+        commands.insert_resource(Settings::load());
+        commands.insert_resource(KeyBindings::load());
+        commands.insert_resource(DailyProgress::load());
+        commands.insert_resource(PuzzleProgress::load());
+        commands.insert_resource(Replays::load());
+        commands.insert_resource(Leaderboards::load());
+        commands.insert_resource(Stats::load());
+        *saved_games = SavedGames::load();
+
+        let mut game_grid = puzzle.game_grid.clues_only();
+        saved_games.restore(&mut game_grid);
+        *puzzle = Puzzle::new(game_grid, puzzle.source.clone(), puzzle.difficulty);
+        for (mut cell, location) in &mut tile_query {
+            cell.0 = puzzle.game_grid.get(location.row, location.col);
+        }
+    }
+}
+
+/// Shows the panel behind a menu or page while its state is active.
+fn show_panel<T: Component>(mut panel: Query<&mut Visibility, With<T>>) {
+    *panel.single_mut() = Visibility::Inherited;
+}
+
+fn hide_panel<T: Component>(mut panel: Query<&mut Visibility, With<T>>) {
+    *panel.single_mut() = Visibility::Hidden;
+}
+
+fn update_menu(
+    main_menu: Res<MainMenu>,
+    profiles: Res<Profiles>,
+    puzzle_paths: Res<PuzzlePaths>,
+    packs: Res<Packs>,
+    daily_progress: Res<DailyProgress>,
+    puzzle_progress: Res<PuzzleProgress>,
+    mut menu_text: Query<&mut Text, With<MenuText>>,
+) {
+    let mut text = String::from("Nurikabe\n\n");
+    for (i, item) in menu_items(&puzzle_paths).iter().enumerate() {
+        let label = match item {
+            MenuItem::Resume => "Resume".to_string(),
+            MenuItem::File(path) => {
+                let name = file_name(path);
+                // the menu font has no check mark glyph
+                match puzzle_progress.record(path) {
+                    Some(Record {
+                        seconds,
+                        moves: Some(moves),
+                    }) => format!(
+                        "{} (solved, best {} in {} moves)",
+                        name,
+                        progress::format_time(seconds),
+                        moves
+                    ),
+                    Some(Record { seconds, .. }) => {
+                        format!("{} (solved, best {})", name, progress::format_time(seconds))
+                    }
+                    None => name,
+                }
+            }
+            MenuItem::Daily => {
+                let date = Date::today();
+                if daily_progress.is_completed(date) {
+                    format!("Daily puzzle {} (solved)", date)
+                } else {
+                    format!("Daily puzzle {}", date)
+                }
+            }
+            MenuItem::Packs => {
+                let solved: usize = packs
+                    .0
+                    .iter()
+                    .map(|pack| pack.solved(&puzzle_progress))
+                    .sum();
+                let total: usize = packs.0.iter().map(|pack| pack.puzzles.len()).sum();
+                format!("Puzzle packs ({}/{} solved)", solved, total)
+            }
+            MenuItem::Calendar => "Daily puzzle calendar".to_string(),
+            MenuItem::Generate => "New random puzzle".to_string(),
+            MenuItem::Multiplayer => "Play together over the network".to_string(),
+            MenuItem::Tutorial => "Tutorial".to_string(),
+            MenuItem::Stats => "Statistics".to_string(),
+            MenuItem::Editor => "Puzzle editor".to_string(),
+            MenuItem::Profile => format!("Profile: {}", profiles.current()),
+            MenuItem::NewProfile => "New profile".to_string(),
+        };
+        let marker = if i == main_menu.selected { "> " } else { "  " };
+        text.push_str(&format!("{}{}\n", marker, label));
+    }
+    text.push_str("\nUp/Down to choose, Enter to play, M to close");
+    menu_text.single_mut().sections[0].value = text;
+}
+
+/// Draws the clues of the selected puzzle file next to the menu, so puzzles
+/// can be told apart by their size and shape.
+fn update_menu_preview(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    main_menu: Res<MainMenu>,
+    puzzle_paths: Res<PuzzlePaths>,
+    tile_sheet: Res<TileSheet>,
+    panel: Query<Entity, With<MenuPanel>>,
+    previews: Query<Entity, With<MenuPreview>>,
+) {
+    for entity in &previews {
+        commands.entity(entity).despawn_recursive();
+    }
+    let items = menu_items(&puzzle_paths);
+    let Some(MenuItem::File(path)) = items.get(main_menu.selected) else {
+        return;
+    };
+    let Some(grid) = read_grid(path) else {
+        return;
+    };
+    let GridSize { rows, cols } = grid.grid_size;
+    let step = MENU_PREVIEW_SIZE / rows.max(cols) as f32;
+    let center = Vec2::new(MENU_SIZE.x / 4.0 + 20.0, 0.0);
+    let offset = center - Vec2::new(cols as f32 - 1.0, rows as f32 - 1.0) * step / 2.0;
+    let font = asset_server.load("FiraSans-Regular.ttf");
+    commands.entity(panel.single()).with_children(|builder| {
+        for row in 0..rows {
+            for col in 0..cols {
+                let position = offset + Vec2::new(col as f32, row as f32) * step;
+                let mut cell = builder.spawn((
+                    SpriteBundle {
+                        transform: Transform {
+                            translation: position.extend(1.0),
+                            // leave a small gap between cells
+                            scale: Vec3::splat(step * 0.9 / 16.0),
+                            ..default()
+                        },
+                        texture: tile_sheet.texture.clone(),
+                        ..default()
+                    },
+                    TextureAtlas {
+                        layout: tile_sheet.layout.clone(),
+                        index: tile_index(grid.get(row, col)),
+                    },
+                    MenuPreview,
+                    UI_LAYER,
+                ));
+                if let CellState::Value(clue) = grid.get(row, col) {
+                    let size = step * 0.9;
+                    cell.with_children(|builder| {
+                        builder.spawn((clue_text(clue, font.clone(), size, 16.0 / size), UI_LAYER));
+                    });
+                }
+            }
+        }
+    });
+}
+
+/// Lists the best solves of the selected puzzle file under its preview.
+fn update_menu_leaderboard(
+    main_menu: Res<MainMenu>,
+    puzzle_paths: Res<PuzzlePaths>,
+    leaderboards: Res<Leaderboards>,
+    mut leaderboard_text: Query<&mut Text, With<MenuLeaderboard>>,
+) {
+    let items = menu_items(&puzzle_paths);
+    let top = match items.get(main_menu.selected) {
+        Some(MenuItem::File(path)) => read_grid(path)
+            .map(|grid| leaderboards::lines(leaderboards.top(&grid), 5))
+            .filter(|lines| !lines.is_empty())
+            .map(|lines| format!("Best solves\n{}", lines)),
+        _ => None,
+    };
+    leaderboard_text.single_mut().sections[0].value = top.unwrap_or_default();
+}
+
+fn open_code_entry(
+    game_state: Res<State<GameState>>,
+    mut code_entry: ResMut<CodeEntry>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    *code_entry = CodeEntry {
+        previous: Some(game_state.get().clone()),
+        ..default()
+    };
+    next_state.set(GameState::EnterCode);
+}
+
+/// Types into the "enter code" dialog. Enter plays the code, Tab copies the
+/// current puzzle's code and Escape closes the dialog.
+fn code_entry_input(
+    game_state: Res<State<GameState>>,
+    puzzle: Res<Puzzle>,
+    mut keyboard_input_ev: EventReader<KeyboardInput>,
+    mut code_entry: ResMut<CodeEntry>,
+    mut import: ResMut<PuzzleImport>,
+    difficulty_cache: Res<DifficultyCache>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    // read the events every frame, so the key that opened the dialog isn't
+    // typed into it
+    let events: Vec<&KeyboardInput> = keyboard_input_ev
+        .read()
+        .filter(|ev| ev.state == ButtonState::Pressed)
+        .collect();
+    if *game_state != GameState::EnterCode {
+        return;
+    }
+    for ev in events {
+        match &ev.logical_key {
+            Key::Character(chars) => {
+                for c in chars.chars() {
+                    if (c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                        && code_entry.text.len() < MAX_CODE_LENGTH
+                    {
+                        code_entry.text.push(c);
+                    }
+                }
+            }
+            Key::Backspace => {
+                code_entry.text.pop();
+            }
+            Key::Tab => {
+                let code = puzzle.to_code();
+                if let Err(err) = clipboard::copy(&code) {
+                    warn!("couldn't copy {} to the clipboard: {}", code, err);
+                }
+            }
+            // the puzzle is checked for a solution in the background, and
+            // the dialog stays open until it turns out to have one
+            Key::Enter => match code::decode(&code_entry.text) {
+                Ok(game_grid) => {
+                    let source = PuzzleSource::Code(code_entry.text.trim().to_string());
+                    code_entry.error = None;
+                    import.start(
+                        game_grid,
+                        source,
+                        "that puzzle has no solution",
+                        &difficulty_cache,
+                    );
+                    return;
+                }
+                Err(err) => code_entry.error = Some(err.to_string()),
+            },
+            Key::Escape => {
+                let previous = code_entry.previous.take().unwrap_or(GameState::Playing);
+                next_state.set(previous);
+                return;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn update_code_panel(
+    puzzle: Res<Puzzle>,
+    code_entry: Res<CodeEntry>,
+    import: Res<PuzzleImport>,
+    mut code_text: Query<&mut Text, With<CodeText>>,
+) {
+    let error = if import.is_running() {
+        "Checking the puzzle..."
+    } else {
+        code_entry.error.as_deref().unwrap_or("")
+    };
+    code_text.single_mut().sections[0].value = format!(
+        "Enter a puzzle code\n\n{}_\n{}\n\nThis puzzle: {}\n\nEnter to play, Tab to copy this puzzle's code, Esc to cancel",
+        code_entry.text,
+        error,
+        puzzle.to_code()
+    );
+}
+
+/// Writes out the stats on every puzzle and on the current one.
+fn update_stats_page(
+    puzzle: Res<Puzzle>,
+    solve_timer: Res<SolveTimer>,
+    stats: Res<Stats>,
+    mut stats_text: Query<&mut Text, With<StatsText>>,
+) {
+    fn lines(stats: &PuzzleStats) -> String {
+        let average = stats
+            .average_solve_time()
+            .map_or_else(|| "-".to_string(), progress::format_time);
+        format!(
+            "  Solves: {}\n  Play time: {}\n  Average solve time: {}\n  Hints used: {}\n  Resets: {}\n",
+            stats.solves,
+            progress::format_time(stats.play_time),
+            average,
+            stats.hints,
+            stats.resets
+        )
+    }
+    // the time on the current puzzle is only recorded when leaving it
+    let (mut total, mut current) = (stats.total(), stats.puzzle(&puzzle.game_grid));
+    total.play_time += solve_timer.0.elapsed_secs();
+    current.play_time += solve_timer.0.elapsed_secs();
+    stats_text.single_mut().sections[0].value = format!(
+        "Statistics\n\nAll puzzles ({} solved)\n{}\nThis puzzle\n{}\nM to go back",
+        stats.puzzles_solved(),
+        lines(&total),
+        lines(&current),
+    );
+}
+
+fn open_settings_page(
+    game_state: Res<State<GameState>>,
+    mut settings_page: ResMut<SettingsPage>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    *settings_page = SettingsPage {
+        previous: Some(game_state.get().clone()),
+        ..default()
+    };
+    next_state.set(GameState::Settings);
+}
+
+/// Chooses a setting or an action with the up and down arrows. Left and
+/// right change the selected setting, and enter flips it or rebinds the
+/// selected action to the next key pressed. F1 closes the page again.
+fn settings_page_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings_page: ResMut<SettingsPage>,
+    mut settings: ResMut<Settings>,
+    mut key_bindings: ResMut<KeyBindings>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    // the settings come first on the page, then the key bindings
+    let selected = settings_page.selected;
+    let action = selected
+        .checked_sub(Setting::ALL.len())
+        .map(|i| Action::ALL[i]);
+    if settings_page.rebinding {
+        if let (Some(&key), Some(action)) = (
+            keyboard_input
+                .get_just_pressed()
+                .find(|key| input::BINDABLE_KEYS.contains(key)),
+            action,
+        ) {
+            key_bindings.rebind(action, key);
+            settings_page.rebinding = false;
+        }
+        return;
+    }
+    let rows = Setting::ALL.len() + Action::ALL.len();
+    let steps = if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+        1
+    } else if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+        -1
+    } else {
+        0
+    };
+    if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        settings_page.selected = (selected + rows - 1) % rows;
+    } else if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        settings_page.selected = (selected + 1) % rows;
+    } else if let (Some(setting), true) = (Setting::ALL.get(selected), steps != 0) {
+        setting.adjust(&mut settings, steps);
+    } else if keyboard_input.just_pressed(KeyCode::Enter) {
+        match Setting::ALL.get(selected) {
+            Some(setting) => setting.adjust(&mut settings, 1),
+            None => settings_page.rebinding = true,
+        }
+    } else if keyboard_input.just_pressed(KeyCode::F1) {
+        let previous = settings_page.previous.take().unwrap_or(GameState::Playing);
+        next_state.set(previous);
+    }
+}
+
+fn update_settings_page(
+    settings_page: Res<SettingsPage>,
+    settings: Res<Settings>,
+    key_bindings: Res<KeyBindings>,
+    mut settings_text: Query<&mut Text, With<SettingsText>>,
+) {
+    let marker = |i| {
+        if i == settings_page.selected {
+            "> "
+        } else {
+            "  "
+        }
+    };
+    let mut text = String::from("Settings\n\n");
+    for (i, setting) in Setting::ALL.iter().enumerate() {
+        text.push_str(&format!(
+            "{}{}: {}\n",
+            marker(i),
+            setting.name(),
+            setting.value(&settings)
+        ));
+    }
+    text.push_str("\nKey bindings\n\n");
+    for (i, action) in Action::ALL.iter().enumerate() {
+        let i = i + Setting::ALL.len();
+        let keys: Vec<String> = key_bindings
+            .keys(*action)
+            .into_iter()
+            .map(input::key_name)
+            .collect();
+        let keys = if i == settings_page.selected && settings_page.rebinding {
+            "press a key...".to_string()
+        } else {
+            keys.join(", ")
+        };
+        text.push_str(&format!("{}{}: {}\n", marker(i), action.name(), keys));
+    }
+    text.push_str("\nUp/Down to choose, Left/Right to change,\nEnter to rebind, F1 to close");
+    settings_text.single_mut().sections[0].value = text;
+}
+
+/// Shows the win sprite and the time taken for as long as the puzzle stays
+/// won.
+fn game_win(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    puzzle: Res<Puzzle>,
+    leaderboards: Res<Leaderboards>,
+    last_solve: Res<LastSolve>,
+) {
+    let mut text = format!(
+        "Solved in {}\nwith {} moves",
+        progress::format_time(last_solve.seconds),
+        last_solve.moves
+    );
+    if last_solve.solver_helped {
+        text.push_str("\nwith help from the solver");
+    } else if last_solve.new_record {
+        text.push_str("\nNew record!");
+    } else if let Some(place) = last_solve.place.filter(|&place| place > 0) {
+        text.push_str(&format!("\nNumber {} on the leaderboard", place + 1));
+    }
+    let leaderboard = leaderboards::lines(leaderboards.top(&puzzle.game_grid), 5);
+    let font = asset_server.load("FiraSans-Regular.ttf");
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgba(0.0, 0.0, 0.0, 0.85),
+                    custom_size: Some(WIN_PANEL_SIZE),
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::new(0.0, -170.0, 3.0)),
+                ..default()
+            },
+            StateScoped(GameState::Won),
+            UI_LAYER,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        text,
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 36.0,
+                            ..default()
+                        },
+                    ),
+                    transform: Transform::from_xyz(0.0, 95.0, 1.0),
+                    ..default()
+                },
+                UI_LAYER,
+            ));
+            builder.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        leaderboard,
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 20.0,
+                            ..default()
+                        },
+                    ),
+                    transform: Transform::from_xyz(0.0, -40.0, 1.0),
+                    ..default()
+                },
+                UI_LAYER,
+            ));
+            for (button, x) in [(WinButton::NextPuzzle, -85.0), (WinButton::Menu, 85.0)] {
+                builder
+                    .spawn((
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color: Color::srgb(0.3, 0.3, 0.3),
+                                custom_size: Some(WIN_BUTTON_SIZE),
+                                ..default()
+                            },
+                            transform: Transform::from_xyz(x, -135.0, 1.0),
+                            ..default()
+                        },
+                        button,
+                        UI_LAYER,
+                    ))
+                    .with_children(|builder| {
+                        builder.spawn((
+                            Text2dBundle {
+                                text: Text::from_section(
+                                    button.label(),
+                                    TextStyle {
+                                        font: font.clone(),
+                                        font_size: 24.0,
+                                        ..default()
+                                    },
+                                ),
+                                transform: Transform::from_translation(Vec3::Z),
+                                ..default()
+                            },
+                            UI_LAYER,
+                        ));
+                    });
+            }
+        });
+    if !settings.animations {
+        return;
+    }
+    let mut rng = rand::thread_rng();
+    for _ in 0..CONFETTI_PIECES {
+        let angle = rng.gen_range(0.2..std::f32::consts::PI - 0.2);
+        let speed = rng.gen_range(300.0..700.0);
+        let color = ANNOTATION_COLORS[rng.gen_range(0..ANNOTATION_COLORS.len())].with_alpha(1.0);
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::new(8.0, 12.0)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(0.0, -100.0, 2.5),
+                ..default()
+            },
+            Confetti {
+                velocity: Vec2::from_angle(angle) * speed,
+                spin: rng.gen_range(-10.0..10.0),
+                timer: Timer::from_seconds(CONFETTI_SECONDS, TimerMode::Once),
+            },
+            StateScoped(GameState::Won),
+            UI_LAYER,
+        ));
+    }
+}
+
+/// Moves the confetti under gravity, fading each piece out over its lifetime.
+fn animate_confetti(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut confetti: Query<(Entity, &mut Transform, &mut Sprite, &mut Confetti)>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut transform, mut sprite, mut piece) in &mut confetti {
+        if piece.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        piece.velocity.y -= CONFETTI_GRAVITY * delta;
+        transform.translation += (piece.velocity * delta).extend(0.0);
+        transform.rotate_z(piece.spin * delta);
+        sprite.color.set_alpha(1.0 - piece.timer.fraction());
+    }
+}
+
+/// Goes on to the next puzzle or opens the menu when one of the buttons on
+/// the win panel is clicked.
+fn click_win_buttons(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<UiCamera>>,
+    buttons: Query<(&WinButton, &GlobalTransform)>,
+    mut main_menu: ResMut<MainMenu>,
+    mut next_puzzle_ev: EventWriter<NextPuzzle>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let Some(position) = pointer_position(&windows, &camera) else {
+        return;
+    };
+    let clicked = buttons.iter().find(|(_, transform)| {
+        let offset = (position - transform.translation().truncate()).abs();
+        offset.cmple(WIN_BUTTON_SIZE / 2.0).all()
+    });
+    match clicked.map(|(button, _)| *button) {
+        Some(WinButton::NextPuzzle) => {
+            next_puzzle_ev.send(NextPuzzle);
+        }
+        Some(WinButton::Menu) => {
+            *main_menu = MainMenu {
+                selected: 0,
+                previous: Some(GameState::Won),
+            };
+            next_state.set(GameState::Menu);
+        }
+        None => {}
+    }
+}
+
+/// Loads the skin picked in the settings, or goes back to the built-in tiles
+/// if the skin's file has gone missing.
+fn load_skin(
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
+    mut tile_sheet: ResMut<TileSheet>,
+) {
+    let loaded = tile_sheet.skin.as_ref().map(|(name, _)| name);
+    if loaded == settings.skin.as_ref() {
+        return;
+    }
+    tile_sheet.skin = settings.skin.as_ref().and_then(|name| {
+        if !settings::available_skins().contains(name) {
+            warn!(
+                "couldn't find the skin {:?}, using the built-in tiles",
+                name
+            );
+            return None;
+        }
+        let texture = asset_server.load_with_settings(
+            format!("skins/{}", name),
+            |settings: &mut ImageLoaderSettings| settings.sampler = ImageSampler::nearest(),
+        );
+        Some((name.clone(), texture))
+    });
+}
+
+/// Draws each cell that changed with the tile for its new state, leaving the
+/// rest of the board's sprites alone.
+fn update_cell(mut tile_query: Query<(&mut TextureAtlas, &Cell), Changed<Cell>>) {
+    for (mut texture_atlas, cell) in &mut tile_query {
+        texture_atlas.index = tile_index(cell.0);
+    }
+}
+
+/// Draws every cell from the tile sheet the theme or skin asks for.
+fn apply_tile_sheet(
+    tile_sheet: Res<TileSheet>,
+    theme: Res<Theme>,
+    mut tile_query: Query<&mut Handle<Image>, With<Cell>>,
+) {
+    let texture = tile_sheet.texture_for(&theme);
+    for mut handle in &mut tile_query {
+        if *handle != *texture {
+            *handle = texture.clone();
+        }
+    }
+}
+
+fn move_cursor(
+    actions: Res<Actions>,
+    mut cursor: Query<&mut GridComponent, With<Cursor>>,
+    grid_size: Res<BoardSize>,
+    settings: Res<Settings>,
+    mut sound_ev: EventWriter<PlaySound>,
+) {
+    let mut location = cursor.single_mut();
+    let from = *location;
+    let mut temp = IVec2 {
+        x: location.row as i32,
+        y: location.col as i32,
+    };
+
+    let times = |action| actions.times(action) as i32;
+    temp.y += times(Action::MoveRight) - times(Action::MoveLeft);
+    temp.x += times(Action::MoveUp) - times(Action::MoveDown);
+    let (rows, cols) = (grid_size.rows as i32, grid_size.cols as i32);
+    *location = if settings.wrap_cursor {
+        GridComponent::new(
+            temp.x.rem_euclid(rows) as usize,
+            temp.y.rem_euclid(cols) as usize,
+        )
+    } else {
+        GridComponent::new(
+            temp.x.clamp(0, rows - 1) as usize,
+            temp.y.clamp(0, cols - 1) as usize,
+        )
+    };
+    if *location != from {
+        sound_ev.send(PlaySound(Sound::CursorMove));
+    }
+}
+
+/// Moves the cursor to the next clue in reading order with Tab, or the
+/// previous one with Shift+Tab. Reading order starts at the top row, which is
+/// the last row of the grid.
+fn jump_to_clue(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut cursor: Query<&mut GridComponent, With<Cursor>>,
+    puzzle: Res<Puzzle>,
+) {
+    let grid = &puzzle.game_grid;
+    let GridSize { rows, cols } = grid.grid_size;
+    let clues: Vec<GridComponent> = (0..rows)
+        .rev()
+        .flat_map(|row| (0..cols).map(move |col| GridComponent::new(row, col)))
+        .filter(|location| matches!(grid.get(location.row, location.col), CellState::Value(_)))
+        .collect();
+    if clues.is_empty() {
+        return;
+    }
+    let mut location = cursor.single_mut();
+    // the cursor's place in reading order, counting every cell
+    let order = |location: &GridComponent| (rows - 1 - location.row) * cols + location.col;
+    let current = order(&location);
+    let backwards = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let next = if backwards {
+        clues
+            .iter()
+            .rev()
+            .find(|clue| order(clue) < current)
+            .or(clues.last())
+    } else {
+        clues
+            .iter()
+            .find(|clue| order(clue) > current)
+            .or(clues.first())
+    };
+    if let Some(next) = next {
+        *location = *next;
+    }
+}
+
+fn toggle_cursor_wrap(mut settings: ResMut<Settings>) {
+    settings.wrap_cursor = !settings.wrap_cursor;
+}
+
+fn toggle_vim_keys(mut settings: ResMut<Settings>) {
+    settings.vim_keys = !settings.vim_keys;
+}
+
+/// Zooms the board camera out until the whole grid fits in the window next to
+/// the text around it, and centers it, whenever a puzzle is loaded or the
+/// window is resized. Grids that
+/// already fit are drawn at their usual size.
+fn fit_camera_to_grid(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    grid_size: Res<BoardSize>,
+    settings: Res<Settings>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<BoardCamera>>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let (mut transform, mut projection) = camera.single_mut();
+    let step = settings.cell_size + SPACE_BETWEEN_CELLS;
+    let board = Vec2::new(grid_size.cols as f32, grid_size.rows as f32) * step;
+    let room = (window.size() - FIT_MARGIN).max(Vec2::ONE);
+    let scale = (board / room).max_element();
+    projection.scale = scale.clamp(1.0, MAX_ZOOM_SCALE);
+    transform.translation = Vec3::new(0.0, 0.0, transform.translation.z);
+}
+
+/// Zooms the board in or out with the mouse wheel.
+fn zoom_camera(
+    mut wheel_ev: EventReader<MouseWheel>,
+    mut camera: Query<&mut OrthographicProjection, With<BoardCamera>>,
+) {
+    let mut projection = camera.single_mut();
+    for wheel in wheel_ev.read() {
+        let notches = match wheel.unit {
+            MouseScrollUnit::Line => wheel.y,
+            // touchpads scroll by pixels, about a hundred to a notch
+            MouseScrollUnit::Pixel => wheel.y / 100.0,
+        };
+        projection.scale =
+            (projection.scale * ZOOM_STEP.powf(-notches)).clamp(MIN_ZOOM_SCALE, MAX_ZOOM_SCALE);
+    }
+}
+
+/// Pans the board with Alt and the movement keys, or by dragging with the
+/// middle button from outside the grid, since a middle click on a cell clears
+/// it. The camera can't wander further than `PAN_MARGIN` past the grid.
+fn pan_camera(
+    time: Res<Time>,
+    input: (
+        Res<ButtonInput<KeyCode>>,
+        Res<ButtonInput<MouseButton>>,
+        Res<KeyBindings>,
+    ),
+    windows: Query<&Window, With<PrimaryWindow>>,
+    hovered_cell: Res<HoveredCell>,
+    board: (Res<BoardSize>, Res<Settings>),
+    mut drag: ResMut<CameraDrag>,
+    mut camera: Query<(&mut Transform, &OrthographicProjection), With<BoardCamera>>,
+) {
+    let (keyboard_input, mouse_input, key_bindings) = input;
+    let (grid_size, settings) = board;
+    let (mut transform, projection) = camera.single_mut();
+    if keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]) {
+        let held = |action| keyboard_input.any_pressed(key_bindings.keys(action)) as i32;
+        let direction = Vec2::new(
+            (held(Action::MoveRight) - held(Action::MoveLeft)) as f32,
+            (held(Action::MoveUp) - held(Action::MoveDown)) as f32,
+        );
+        let step = direction * PAN_SPEED * projection.scale * time.delta_seconds();
+        transform.translation += step.extend(0.0);
+    }
+    let pointer = windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position());
+    if mouse_input.just_pressed(MouseButton::Middle) && hovered_cell.0.is_none() {
+        drag.0 = pointer;
+    } else if !mouse_input.pressed(MouseButton::Middle) {
+        drag.0 = None;
+    }
+    if let (Some(last), Some(pointer)) = (drag.0, pointer) {
+        // window positions grow downwards, the world's grow upwards
+        let moved = (pointer - last) * projection.scale;
+        transform.translation += Vec3::new(-moved.x, moved.y, 0.0);
+        drag.0 = Some(pointer);
+    }
+    let step = settings.cell_size + SPACE_BETWEEN_CELLS;
+    let limit = Vec2::new(grid_size.cols as f32, grid_size.rows as f32) * step / 2.0 + PAN_MARGIN;
+    let clamped = transform.translation.truncate().clamp(-limit, limit);
+    transform.translation = clamped.extend(transform.translation.z);
+}
+
+fn camera_dragging(drag: Res<CameraDrag>) -> bool {
+    drag.0.is_some()
+}
+
+fn toggle_instructions(mut panel: Query<&mut InstructionsPanel>) {
+    let mut panel = panel.single_mut();
+    panel.shown = !panel.shown;
+}
+
+/// Slides the instructions in from the left edge of the window or back out
+/// of sight. The board stays in the middle of the window either way, so
+/// hiding them leaves room for wide grids.
+fn slide_instructions(time: Res<Time>, mut panel: Query<(&mut ScreenAnchor, &InstructionsPanel)>) {
+    for (mut anchor, panel) in &mut panel {
+        let target = if panel.shown {
+            INSTRUCTIONS_OFFSET
+        } else {
+            INSTRUCTIONS_HIDDEN_OFFSET
+        };
+        // left alone once it arrives, so the layout isn't redone every frame
+        if anchor.offset.x == target {
+            continue;
+        }
+        let step = (INSTRUCTIONS_OFFSET - INSTRUCTIONS_HIDDEN_OFFSET) / INSTRUCTIONS_SLIDE_SECONDS
+            * time.delta_seconds();
+        let x = anchor.offset.x;
+        anchor.offset.x = if x < target {
+            (x + step).min(target)
+        } else {
+            (x - step).max(target)
+        };
+    }
+}
+
+/// Moves the text and panels along with the edges of the window when it is
+/// resized, or when they are moved to a new spot, and stretches the strip
+/// along the top across the window.
+fn apply_screen_anchors(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut resized_ev: EventReader<WindowResized>,
+    mut anchored: Query<(Ref<ScreenAnchor>, &mut Transform)>,
+    mut hud_strip: Query<&mut Sprite, With<HudStrip>>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let resized = resized_ev.read().count() > 0;
+    let half = window.size() / 2.0;
+    for (anchor, mut transform) in &mut anchored {
+        if resized || anchor.is_changed() {
+            let position = anchor.edge * half + anchor.offset;
+            transform.translation = position.extend(transform.translation.z);
+        }
+    }
+    if resized {
+        for mut sprite in &mut hud_strip {
+            sprite.custom_size = Some(Vec2::new(window.width(), HUD_SIZE.y));
+        }
+    }
+}
+
+/// Whether the player asked to show or hide the instructions: F4, or `?` while
+/// it isn't taken by the vim keys' hint.
+fn instructions_toggle_requested(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+) -> bool {
+    let question_mark = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+        && keyboard_input.just_pressed(KeyCode::Slash);
+    keyboard_input.just_pressed(KeyCode::F4) || (!settings.vim_keys && question_mark)
+}
+
+fn ctrl_held(keyboard_input: Res<ButtonInput<KeyCode>>) -> bool {
+    keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+}
+
+/// Keeps the fixed shortcuts quiet while a page wants the keys for itself.
+fn shortcuts_enabled(game_state: Res<State<GameState>>) -> bool {
+    !matches!(
+        game_state.get(),
+        GameState::Settings
+            | GameState::EnterCode
+            | GameState::Replay
+            | GameState::Editor
+            | GameState::Packs
+            | GameState::Calendar
+            | GameState::Lobby
+            | GameState::Spectating
+    )
+}
+
+/// Whether the player asked for a hint: H normally, or `?` when H is taken by
+/// the vim keys.
+fn hint_requested(keyboard_input: Res<ButtonInput<KeyCode>>, settings: Res<Settings>) -> bool {
+    if settings.vim_keys {
+        keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+            && keyboard_input.just_pressed(KeyCode::Slash)
+    } else {
+        keyboard_input.just_pressed(KeyCode::KeyH)
+    }
+}
+
+/// The whole game: the board, the menus and the systems that drive them.
+/// Add it next to `DefaultPlugins` to embed the puzzle in another app.
+pub struct NurikabePlugin;
+
+impl Plugin for NurikabePlugin {
+    fn build(&self, app: &mut App) {
+        // loaded first, since it decides where everything else is loaded from
+        app.insert_resource(Profiles::load());
+        let settings = Settings::load();
+        app.insert_resource(Theme::new(&settings))
+            .insert_resource(settings)
+            .init_state::<GameState>()
+            .enable_state_scoped_entities::<GameState>()
+            .add_plugins((
+                ActionsPlugin,
+                SoundPlugin,
+                PuzzleLogicPlugin,
+                BoardRenderingPlugin,
+                BoardMeshPlugin,
+                MenuPlugin,
+                ReplayViewerPlugin,
+                EditorPlugin,
+                PacksPlugin,
+                CalendarPlugin,
+                MultiplayerPlugin,
+            ))
+            .add_systems(
+                Update,
+                // the settings page needs every key for rebinding, and the
+                // code dialog, the replay viewer, the packs page, the
+                // calendar and the lobby use Escape to close
+                close_on_esc.run_if(on_desktop.and_then(shortcuts_enabled)),
+            );
+    }
+}
+
+/// Loading and switching puzzles, and everything the player does to the board.
+pub struct PuzzleLogicPlugin;
+
+impl Plugin for PuzzleLogicPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DailyProgress::load())
+            .insert_resource(DifficultyCache::load())
+            .insert_resource(Leaderboards::load())
+            .insert_resource(PuzzleProgress::load())
+            .insert_resource(Replays::load())
+            .insert_resource(SavedGames::load())
+            .insert_resource(Stats::load())
+            .init_resource::<AutoSolve>()
+            .init_resource::<Autosave>()
+            .init_resource::<Assist>()
+            .init_resource::<ActiveHint>()
+            .init_resource::<HoveredCell>()
+            .init_resource::<PaintStroke>()
+            .init_resource::<SolveTimer>()
+            .init_resource::<LastSolve>()
+            .init_resource::<PuzzleWatch>()
+            .init_resource::<PuzzleGeneration>()
+            .init_resource::<PuzzleDownload>()
+            .init_resource::<PuzzleImport>()
+            .init_resource::<Tutorial>()
+            .init_resource::<CameraDrag>()
+            .add_event::<StartPuzzle>()
+            .add_event::<CellMarked>()
+            .add_event::<NextPuzzle>()
+            .add_systems(Startup, load_puzzle)
+            .add_systems(
+                Update,
+                (
+                    (
+                        move_cursor,
+                        toggle_cell.run_if(tutorial_allows_cursor_cell),
+                        (reset_puzzle, undo_redo).run_if(tutorial_allows_free_play),
+                    )
+                        .after(input::read_actions)
+                        .run_if(in_state(GameState::Playing)),
+                    (
+                        generate_puzzle
+                            .run_if(input_just_pressed(KeyCode::KeyN).and_then(shortcuts_enabled)),
+                        finish_generating.before(start_puzzle),
+                    ),
+                    play_next_puzzle.run_if(
+                        input_just_pressed(KeyCode::Enter)
+                            .and_then(in_state(GameState::Won))
+                            .or_else(on_event::<NextPuzzle>()),
+                    ),
+                    play_daily_puzzle
+                        .run_if(input_just_pressed(KeyCode::KeyT).and_then(shortcuts_enabled)),
+                    drop_puzzle_file.run_if(shortcuts_enabled),
+                    (
+                        (
+                            copy_board_text.run_if(input_just_pressed(KeyCode::KeyC)),
+                            paste_puzzle.run_if(input_just_pressed(KeyCode::KeyV)),
+                        )
+                            .run_if(ctrl_held.and_then(shortcuts_enabled)),
+                        finish_download.before(start_puzzle),
+                        finish_import.before(start_puzzle),
+                    ),
+                    copy_puzzle_url
+                        .run_if(input_just_pressed(KeyCode::KeyU).and_then(shortcuts_enabled)),
+                    start_puzzle,
+                    reload_changed_puzzle.run_if(
+                        on_desktop.and_then(
+                            in_state(GameState::Playing).or_else(in_state(GameState::Won)),
+                        ),
+                    ),
+                    tick_solve_timer,
+                    update_hovered_cell,
+                    paint_cells.after(update_hovered_cell).run_if(
+                        in_state(GameState::Playing)
+                            .and_then(tutorial_allows_hovered_cell)
+                            .and_then(not(camera_dragging)),
+                    ),
+                    finish_paint_stroke.after(paint_cells),
+                    show_hint.run_if(
+                        hint_requested
+                            .and_then(in_state(GameState::Playing))
+                            .and_then(tutorial_allows_free_play),
+                    ),
+                    clear_hint,
+                    toggle_auto_solve
+                        .run_if(input_just_pressed(KeyCode::F5).and_then(shortcuts_enabled)),
+                    adjust_auto_solve_delay,
+                    auto_solve_step
+                        .run_if(in_state(GameState::Playing).and_then(tutorial_allows_free_play)),
+                    jump_to_clue.run_if(
+                        input_just_pressed(KeyCode::Tab).and_then(in_state(GameState::Playing)),
+                    ),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    export_png.run_if(
+                        on_desktop
+                            .and_then(input_just_pressed(KeyCode::KeyP))
+                            .and_then(shortcuts_enabled),
+                    ),
+                    (save_board, record_play_time)
+                        .run_if(on_event::<StartPuzzle>())
+                        .before(start_puzzle)
+                        .before(tick_solve_timer),
+                    autosave_board,
+                    check_marks.after(start_puzzle),
+                    surround_complete_islands
+                        .after(toggle_cell)
+                        .after(paint_cells)
+                        .before(finish_paint_stroke)
+                        .run_if(in_state(GameState::Playing)),
+                    reset_mistakes.run_if(on_event::<StartPuzzle>()),
+                    annotate_cell.run_if(in_state(GameState::Playing)),
+                    play_mark_sounds,
+                    track_tutorial,
+                    advance_tutorial
+                        .run_if(in_state(GameState::Playing).or_else(in_state(GameState::Won))),
+                    // the tutorial takes over the cursor after everything
+                    // else that moves it
+                    pin_tutorial_cursor
+                        .after(move_cursor)
+                        .after(jump_to_clue)
+                        .after(paint_cells)
+                        .before(toggle_cell),
+                ),
+            )
+            // the window is gone by now, but the app only stops after this
+            .add_systems(PostUpdate, stamp_replay_moves)
+            .add_systems(
+                Last,
+                (save_board, record_play_time).run_if(on_event::<AppExit>()),
+            )
+            // only on a real win, not when coming back to the win panel from
+            // the menu or a replay
+            .add_systems(
+                OnTransition {
+                    exited: GameState::Playing,
+                    entered: GameState::Won,
+                },
+                (
+                    record_win,
+                    record_leaderboard_time.after(record_win),
+                    play_win_sound,
+                ),
+            );
+    }
+}
+
+/// Drawing the board, the cursor, the hint and the win sprite.
+pub struct BoardRenderingPlugin;
+
+impl Plugin for BoardRenderingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup.after(load_puzzle))
+            .add_systems(
+                Update,
+                (
+                    update_cursor_location,
+                    load_skin
+                        .run_if(resource_changed::<Settings>)
+                        .before(apply_tile_sheet),
+                    (
+                        update_cell,
+                        apply_tile_sheet.run_if(
+                            resource_changed::<TileSheet>.or_else(resource_changed::<Theme>),
+                        ),
+                    ),
+                    (start_cell_pops, animate_cell_pops).chain(),
+                    animate_confetti,
+                    click_win_buttons.run_if(in_state(GameState::Won)),
+                    update_hud_text,
+                    update_mistake_text
+                        .run_if(resource_changed::<Assist>.or_else(resource_changed::<Settings>)),
+                    fade_mistake_flashes,
+                    // after the board is rebuilt for a new puzzle, so the
+                    // highlights go on the new cells. Unlike the win check,
+                    // these overlays aren't kept up to date cell by cell: each
+                    // one still looks over the whole board, once, for every
+                    // change to it.
+                    highlight_broken_rules
+                        .after(start_puzzle)
+                        .run_if(resource_changed::<Puzzle>),
+                    // the editor's draft can be bigger than the puzzle
+                    highlight_cursor_island
+                        .after(start_puzzle)
+                        .run_if(not(in_state(GameState::Editor))),
+                    shade_complete_islands
+                        .after(start_puzzle)
+                        .run_if(resource_changed::<Puzzle>),
+                    update_clue_texts.after(start_puzzle).run_if(
+                        resource_changed::<BoardSize>
+                            .or_else(resource_changed::<Settings>)
+                            .or_else(resource_changed::<PuzzleEditor>),
+                    ),
+                    update_remaining_counts
+                        .after(start_puzzle)
+                        .run_if(resource_changed::<Puzzle>.or_else(resource_changed::<Settings>)),
+                    (update_annotations, show_locks)
+                        .after(start_puzzle)
+                        .run_if(resource_changed::<Puzzle>),
+                    fade_hint_highlights,
+                    (highlight_hint, update_hint_text).run_if(resource_changed::<ActiveHint>),
+                    apply_ui_scale.run_if(resource_changed::<Settings>),
+                    apply_cell_size
+                        .after(update_theme)
+                        .run_if(resource_changed::<Settings>.or_else(resource_changed::<Theme>)),
+                    (
+                        update_theme.run_if(resource_changed::<Settings>),
+                        (apply_theme, apply_outlines).run_if(resource_changed::<Theme>),
+                    )
+                        .chain(),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    update_tutorial_text.run_if(resource_changed::<Tutorial>),
+                    highlight_tutorial_cell,
+                    slide_instructions,
+                    fit_camera_to_grid
+                        .before(zoom_camera)
+                        .run_if(resource_changed::<BoardSize>.or_else(on_event::<WindowResized>())),
+                    apply_screen_anchors.after(slide_instructions),
+                    zoom_camera
+                        .run_if(in_state(GameState::Playing).or_else(in_state(GameState::Won))),
+                    pan_camera
+                        .after(update_hovered_cell)
+                        .before(paint_cells)
+                        .run_if(in_state(GameState::Playing).or_else(in_state(GameState::Won))),
+                ),
+            )
+            .add_systems(OnEnter(GameState::Won), game_win);
+    }
+}
+
+/// The main menu, the settings page and the shortcuts that change settings.
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MainMenu>()
+            .init_resource::<SettingsPage>()
+            .init_resource::<CodeEntry>()
+            .add_event::<ChooseMenuItem>()
+            .add_systems(
+                Update,
+                (
+                    toggle_menu.after(input::read_actions),
+                    menu_input
+                        .after(input::read_actions)
+                        .run_if(in_state(GameState::Menu)),
+                    load_menu_item,
+                    switch_profile,
+                    update_menu.run_if(
+                        resource_changed::<MainMenu>
+                            .or_else(resource_changed::<Profiles>)
+                            .or_else(resource_changed::<DailyProgress>)
+                            .or_else(resource_changed::<PuzzleProgress>),
+                    ),
+                    update_menu_preview.run_if(resource_changed::<MainMenu>),
+                    update_menu_leaderboard.run_if(
+                        resource_changed::<MainMenu>.or_else(resource_changed::<Leaderboards>),
+                    ),
+                    open_settings_page
+                        .run_if(input_just_pressed(KeyCode::F1).and_then(shortcuts_enabled)),
+                    settings_page_input.run_if(in_state(GameState::Settings)),
+                    update_settings_page.run_if(
+                        resource_changed::<SettingsPage>
+                            .or_else(resource_changed::<KeyBindings>)
+                            .or_else(resource_changed::<Settings>),
+                    ),
+                    save_settings.run_if(
+                        resource_changed::<Settings>.and_then(not(resource_added::<Settings>)),
+                    ),
+                    open_code_entry.run_if(
+                        input_just_pressed(KeyCode::KeyC)
+                            .and_then(not(ctrl_held))
+                            .and_then(shortcuts_enabled),
+                    ),
+                    code_entry_input,
+                    update_code_panel.run_if(
+                        resource_changed::<CodeEntry>
+                            .or_else(resource_changed::<PuzzleImport>)
+                            .or_else(resource_changed::<Puzzle>),
+                    ),
+                    toggle_cursor_wrap
+                        .run_if(input_just_pressed(KeyCode::F2).and_then(shortcuts_enabled)),
+                    toggle_vim_keys
+                        .run_if(input_just_pressed(KeyCode::F3).and_then(shortcuts_enabled)),
+                    toggle_instructions
+                        .run_if(instructions_toggle_requested.and_then(shortcuts_enabled)),
+                ),
+            )
+            .add_systems(OnEnter(GameState::Menu), show_panel::<MenuPanel>)
+            .add_systems(OnExit(GameState::Menu), hide_panel::<MenuPanel>)
+            .add_systems(OnEnter(GameState::Settings), show_panel::<SettingsPanel>)
+            .add_systems(OnExit(GameState::Settings), hide_panel::<SettingsPanel>)
+            .add_systems(OnEnter(GameState::EnterCode), show_panel::<CodePanel>)
+            .add_systems(OnExit(GameState::EnterCode), hide_panel::<CodePanel>)
+            .add_systems(
+                OnEnter(GameState::Stats),
+                (show_panel::<StatsPanel>, update_stats_page),
+            )
+            .add_systems(OnExit(GameState::Stats), hide_panel::<StatsPanel>);
+    }
+}
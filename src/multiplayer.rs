@@ -0,0 +1,1336 @@
+//! Playing over the network: the lobby page for hosting, joining or watching
+//! a game, keeping everyone's board and cursor in step when solving
+//! together, and the progress bar of a race.
+//!
+//! The host passes everything it hears on to every guest, the sender
+//! included, so everyone sees the changes in the order the host got them and
+//! the last change to a cell wins. Whoever starts a different puzzle brings
+//! everyone else along.
+//!
+//! Those watching can't mark anything. They are sent what each player has on
+//! the board instead, the host's replay included when it is watching one, and
+//! show one player's board at a time.
+
+use std::{collections::BTreeMap, io};
+
+use bevy::{
+    ecs::system::SystemParam,
+    input::{
+        keyboard::{Key, KeyboardInput},
+        ButtonState,
+    },
+    prelude::*,
+    sprite::Anchor,
+    tasks::{AsyncComputeTaskPool, IoTaskPool},
+};
+use nurikabe_core::{
+    code,
+    grid::{Grid, GridSize},
+    solver, CellState,
+};
+
+use crate::{
+    background::Job,
+    can_lock, cell_position, cursor_scale,
+    difficulty::{Difficulty, DifficultyCache},
+    hide_panel,
+    input::{self, Action, Actions},
+    network::{self, Connection, Listener, Message, Mode, DEFAULT_PORT},
+    replay_viewer::ReplayViewer,
+    settings::Settings,
+    show_panel, start_puzzle,
+    theme::Theme,
+    BoardSize, Cell, Cursor, GameState, GridComponent, Puzzle, PuzzleSource, ScreenAnchor,
+    StartPuzzle, MENU_SIZE, UI_LAYER,
+};
+
+/// How many players can join a hosted game.
+const MAX_GUESTS: usize = 1;
+/// How many more can watch it.
+const MAX_WATCHERS: usize = 8;
+const MAX_ADDRESS_LENGTH: usize = 100;
+/// The most rows or columns a puzzle sent by another player can have.
+const MAX_BOARD_SIZE: usize = 50;
+const PEER_CURSOR_COLOR: Color = Color::srgb(0.95, 0.55, 0.1);
+/// The choices on the lobby page before a game starts: solving together,
+/// racing, and joining or watching someone else's game.
+const LOBBY_CHOICES: usize = 4;
+const RACE_BAR_SIZE: Vec2 = Vec2::new(400.0, 36.0);
+
+enum Role {
+    Host {
+        listener: Listener,
+        /// Those who connected but haven't said yet whether they are there
+        /// to play or to watch.
+        pending: Vec<Connection>,
+        guests: Vec<Guest>,
+    },
+    Guest(Connection),
+}
+
+struct Guest {
+    player: usize,
+    connection: Connection,
+    watching: bool,
+}
+
+/// The network game being played, if there is one.
+#[derive(Resource, Default)]
+struct Session {
+    role: Option<Role>,
+    /// This player's number, 0 for the host.
+    player: usize,
+    mode: Mode,
+    /// The number the next guest to join gets.
+    next_player: usize,
+    /// The board as everyone last agreed on it. The puzzle is compared
+    /// against it to find the changes made here.
+    board: Option<Grid>,
+    /// Where this player's cursor is, for telling guests as they join.
+    cursor: (usize, usize),
+    /// Whether this is someone watching, who can't mark the board.
+    watching: bool,
+    /// What each player last had on the board, by player number. The host
+    /// keeps them for those who start watching later.
+    views: BTreeMap<usize, Grid>,
+    /// The player whose board someone watching shows.
+    following: usize,
+    /// What happened last, for the lobby page.
+    status: String,
+    /// The game being joined while the connection to it is made, which can
+    /// take a while: the address typed, whether it is to watch, and the
+    /// connection.
+    joining: Option<(String, bool, Job<io::Result<Connection>>)>,
+}
+
+impl Session {
+    /// Starts hosting a game of `mode` on the default port.
+    fn host(&mut self, mode: Mode) -> io::Result<()> {
+        let listener = Listener::bind(DEFAULT_PORT)?;
+        let address = network::local_address()
+            .map_or_else(|| "this computer".to_string(), |ip| ip.to_string());
+        *self = Session {
+            role: Some(Role::Host {
+                listener,
+                pending: Vec::new(),
+                guests: Vec::new(),
+            }),
+            next_player: 1,
+            mode,
+            status: format!(
+                "Hosting at {}:{}. Waiting for someone to join",
+                address, DEFAULT_PORT
+            ),
+            ..default()
+        };
+        Ok(())
+    }
+
+    /// Starts joining the game hosted at `address`, to play or only to
+    /// watch. The connection is made on another thread, and the game is
+    /// joined once `finish_joining` picks it up.
+    fn join(&mut self, address: &str, watching: bool) {
+        let connecting = address.to_string();
+        let job = Job::spawn(IoTaskPool::get(), move || Connection::connect(&connecting));
+        self.status = format!("Connecting to {}...", address);
+        self.joining = Some((address.to_string(), watching, job));
+    }
+
+    /// Joins the game once the connection to it is made. The board follows
+    /// the host's as soon as they say what they are playing.
+    fn joined(&mut self, mut connection: Connection, watching: bool) -> io::Result<()> {
+        let (hello, joined) = if watching {
+            (Message::Watch, "Watching")
+        } else {
+            (Message::Join, "Joined")
+        };
+        connection.send(&hello)?;
+        *self = Session {
+            status: format!("{} the game at {}", joined, connection.peer()),
+            role: Some(Role::Guest(connection)),
+            watching,
+            ..default()
+        };
+        Ok(())
+    }
+
+    /// Leaves the game, closing every connection.
+    fn end(&mut self, status: String) {
+        *self = Session {
+            status,
+            ..default()
+        };
+    }
+
+    fn is_active(&self) -> bool {
+        self.role.is_some()
+    }
+
+    /// Sends `message` to the host, or from the host to every guest. A
+    /// connection that fails here is dropped the next time it is read.
+    fn send(&mut self, message: &Message) {
+        let connections: Vec<&mut Connection> = match &mut self.role {
+            None => Vec::new(),
+            Some(Role::Guest(connection)) => vec![connection],
+            Some(Role::Host { guests, .. }) => guests
+                .iter_mut()
+                .map(|guest| &mut guest.connection)
+                .collect(),
+        };
+        for connection in connections {
+            if let Err(err) = connection.send(message) {
+                warn!("couldn't send to {}: {}", connection.peer(), err);
+            }
+        }
+    }
+
+    /// Sends `message` only to those watching, by way of the host from a
+    /// guest.
+    fn cast(&mut self, message: &Message) {
+        let connections: Vec<&mut Connection> = match &mut self.role {
+            None => Vec::new(),
+            Some(Role::Guest(connection)) => vec![connection],
+            Some(Role::Host { guests, .. }) => guests
+                .iter_mut()
+                .filter(|guest| guest.watching)
+                .map(|guest| &mut guest.connection)
+                .collect(),
+        };
+        for connection in connections {
+            if let Err(err) = connection.send(message) {
+                warn!("couldn't send to {}: {}", connection.peer(), err);
+            }
+        }
+    }
+}
+
+/// Where the other players' cursors are, by player number.
+#[derive(Resource, Default)]
+struct PeerCursors(BTreeMap<usize, (usize, usize)>);
+
+/// How everyone is getting on in a race.
+#[derive(Resource, Default)]
+struct Race {
+    /// The solution of the puzzle being raced on, which progress is measured
+    /// against.
+    solution: Option<Grid>,
+    /// The solution while it is being worked out on another thread.
+    solving: Option<Job<Option<Grid>>>,
+    /// How much each other player has right, by player number.
+    progress: BTreeMap<usize, u32>,
+    /// The progress this player last sent.
+    sent: Option<u32>,
+    winner: Option<usize>,
+}
+
+impl Race {
+    /// Starts over on the puzzle with the clues of `grid`.
+    fn start(&mut self, grid: &Grid) {
+        let clues = grid.clues_only();
+        *self = Race {
+            solving: Some(Job::spawn(AsyncComputeTaskPool::get(), move || {
+                solver::solutions(&clues, 1).pop()
+            })),
+            ..default()
+        };
+    }
+
+    /// Whether the solution has been worked out, picking it up if it just
+    /// was.
+    fn solved(&mut self) -> bool {
+        if let Some(solution) = self.solving.as_mut().and_then(Job::poll) {
+            self.solution = solution;
+            self.solving = None;
+        }
+        self.solving.is_none()
+    }
+}
+
+/// What is picked and typed on the lobby page.
+#[derive(Resource, Default)]
+struct Lobby {
+    /// The index of the picked choice, counting to `LOBBY_CHOICES`.
+    selected: usize,
+    address: String,
+}
+
+impl Lobby {
+    /// Whether the picked choice goes to someone else's game, to play or to
+    /// watch, so the address is being typed.
+    fn joining(&self) -> bool {
+        self.selected >= 2
+    }
+
+    fn watching(&self) -> bool {
+        self.selected == LOBBY_CHOICES - 1
+    }
+}
+
+/// The backdrop of the lobby page, shown while it is open.
+#[derive(Component)]
+struct LobbyPanel;
+
+#[derive(Component)]
+struct LobbyText;
+
+/// Another player's cursor on the board.
+#[derive(Component)]
+struct PeerCursor;
+
+/// The backdrop of the bar showing how far along the other player is in a
+/// race.
+#[derive(Component)]
+struct RaceBar;
+
+#[derive(Component)]
+struct RaceBarFill;
+
+#[derive(Component)]
+struct RaceText;
+
+/// Playing over the network, and the lobby page opened from the main menu.
+pub struct MultiplayerPlugin;
+
+impl Plugin for MultiplayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Session>()
+            .init_resource::<PeerCursors>()
+            .init_resource::<Race>()
+            .init_resource::<Lobby>()
+            .init_resource::<PendingRating>()
+            .add_systems(Startup, (spawn_lobby_panel, spawn_race_bar))
+            .add_systems(
+                Update,
+                (
+                    (
+                        share_changes
+                            .run_if(in_state(GameState::Playing).or_else(in_state(GameState::Won))),
+                        receive_messages,
+                    )
+                        .chain()
+                        .before(start_puzzle),
+                    finish_rating.after(start_puzzle),
+                    share_cursor,
+                    share_view,
+                    keep_watching
+                        .after(start_puzzle)
+                        .run_if(on_event::<StartPuzzle>()),
+                    watch_input
+                        .after(input::read_actions)
+                        .run_if(in_state(GameState::Spectating)),
+                    show_peer_cursors.run_if(
+                        resource_changed::<PeerCursors>
+                            .or_else(resource_changed::<BoardSize>)
+                            .or_else(resource_changed::<Settings>)
+                            .or_else(state_changed::<GameState>),
+                    ),
+                    lobby_input,
+                    finish_joining,
+                    update_lobby_page.run_if(in_state(GameState::Lobby)),
+                    update_race_bar,
+                ),
+            )
+            // only on solving it here, not when a partner's mark solves it
+            // or when coming back to the win panel
+            .add_systems(
+                OnTransition {
+                    exited: GameState::Playing,
+                    entered: GameState::Won,
+                },
+                claim_race_win,
+            )
+            .add_systems(OnEnter(GameState::Lobby), show_panel::<LobbyPanel>)
+            .add_systems(OnExit(GameState::Lobby), hide_panel::<LobbyPanel>)
+            // those watching have nothing to move a cursor of their own for
+            .add_systems(OnEnter(GameState::Spectating), hide_panel::<Cursor>)
+            .add_systems(OnExit(GameState::Spectating), show_panel::<Cursor>);
+    }
+}
+
+/// Whether two grids have the same clues in the same places, whatever their
+/// marks.
+fn same_clues(first: &Grid, second: &Grid) -> bool {
+    first.grid_size == second.grid_size
+        && first.cells().iter().zip(second.cells()).all(|(a, b)| {
+            a == b || !(matches!(a, CellState::Value(_)) || matches!(b, CellState::Value(_)))
+        })
+}
+
+/// How much of the puzzle `grid` has right, as a percentage: the cells that
+/// match `solution`, or every marked cell if the puzzle has no solution.
+fn progress(grid: &Grid, solution: Option<&Grid>) -> u32 {
+    let (mut cells, mut right) = (0, 0u32);
+    for (cell, &state) in grid.cells().iter().enumerate() {
+        if matches!(state, CellState::Value(_)) {
+            continue;
+        }
+        cells += 1;
+        let is_right = match solution {
+            Some(solution) => solution.cells()[cell] == state,
+            None => matches!(state, CellState::Island | CellState::River),
+        };
+        if is_right {
+            right += 1;
+        }
+    }
+    (right * 100).checked_div(cells).unwrap_or(100)
+}
+
+fn puzzle_message(grid: &Grid) -> Message {
+    Message::Puzzle {
+        code: code::encode(grid),
+        marks: grid.marks_string(),
+    }
+}
+
+fn view_message(player: usize, view: &Grid) -> Message {
+    Message::View {
+        player,
+        code: code::encode(view),
+        marks: view.marks_string(),
+    }
+}
+
+/// The grid sent in a `Puzzle` or `View` message, if it can be read.
+fn read_board(code: &str, marks: &str) -> Option<Grid> {
+    let grid = code::decode(code)
+        .ok()
+        .and_then(|mut grid| grid.apply_marks(marks).then_some(grid));
+    let Some(grid) = grid else {
+        warn!("ignoring a puzzle that can't be read: {} {}", code, marks);
+        return None;
+    };
+    let GridSize { rows, cols } = grid.grid_size;
+    if rows.max(cols) > MAX_BOARD_SIZE {
+        warn!(
+            "ignoring a {}x{} puzzle, which is too big to play",
+            cols, rows
+        );
+        return None;
+    }
+    Some(grid)
+}
+
+/// Starts the puzzles other players send. One that hasn't been rated before
+/// is rated on another thread, since the solver can take a long while on a
+/// hard one, and its difficulty shows once that is done.
+#[derive(SystemParam)]
+struct NetworkPuzzles<'w> {
+    difficulty_cache: Res<'w, DifficultyCache>,
+    rating: ResMut<'w, PendingRating>,
+    start_puzzle_ev: EventWriter<'w, StartPuzzle>,
+}
+
+impl NetworkPuzzles<'_> {
+    fn start(&mut self, grid: &Grid) {
+        let clues = grid.clues_only();
+        let difficulty = self.difficulty_cache.get(&clues);
+        if difficulty.is_none() {
+            let difficulty_cache = self.difficulty_cache.clone();
+            let rated = clues.clone();
+            let job = Job::spawn(AsyncComputeTaskPool::get(), move || {
+                difficulty_cache.rate(&rated)
+            });
+            self.rating.0 = Some((clues, job));
+        }
+        self.start_puzzle_ev.send(StartPuzzle(Puzzle::new(
+            grid.clone(),
+            PuzzleSource::Network,
+            difficulty,
+        )));
+    }
+}
+
+/// The rating of the last puzzle another player sent, with its clues, while
+/// it is being worked out.
+#[derive(Resource, Default)]
+struct PendingRating(Option<(Grid, Job<Option<Difficulty>>)>);
+
+fn finish_rating(mut rating: ResMut<PendingRating>, mut puzzle: ResMut<Puzzle>) {
+    let Some((clues, job)) = &mut rating.0 else {
+        return;
+    };
+    let Some(difficulty) = job.poll() else {
+        return;
+    };
+    // the board may have moved on to another puzzle by now
+    if same_clues(&puzzle.game_grid, clues) {
+        puzzle.difficulty = difficulty;
+    }
+    rating.0 = None;
+}
+
+/// Sends the cells changed here since the last frame, or in a race how much
+/// of the puzzle is right, or the whole puzzle if a different one was
+/// started.
+fn share_changes(puzzle: Res<Puzzle>, mut session: ResMut<Session>, mut race: ResMut<Race>) {
+    if !session.is_active() || session.watching {
+        return;
+    }
+    let (is_guest, mode) = (matches!(session.role, Some(Role::Guest(_))), session.mode);
+    let board = match &mut session.board {
+        Some(board) if same_clues(board, &puzzle.game_grid) => board,
+        // a guest waits to hear what the host is playing first
+        None if is_guest => return,
+        _ => {
+            session.board = Some(puzzle.game_grid.clone());
+            session.send(&puzzle_message(&puzzle.game_grid));
+            if mode == Mode::Race {
+                race.start(&puzzle.game_grid);
+            }
+            return;
+        }
+    };
+    if mode == Mode::Race {
+        // there is nothing to measure progress against until then
+        if !race.solved() {
+            return;
+        }
+        let percent = progress(&puzzle.game_grid, race.solution.as_ref());
+        if race.sent != Some(percent) {
+            race.sent = Some(percent);
+            let player = session.player;
+            session.send(&Message::Progress { player, percent });
+        }
+        return;
+    }
+    let cols = board.grid_size.cols;
+    let mut changes = Vec::new();
+    for (cell, &state) in puzzle.game_grid.cells().iter().enumerate() {
+        let (row, col) = (cell / cols, cell % cols);
+        if board.get(row, col) != state {
+            board.set(row, col, state);
+            changes.push(Message::Cell { row, col, state });
+        }
+    }
+    for change in &changes {
+        session.send(change);
+    }
+}
+
+/// Tells everyone else where the cursor went.
+fn share_cursor(
+    cursor: Query<&GridComponent, (With<Cursor>, Changed<GridComponent>)>,
+    mut session: ResMut<Session>,
+) {
+    let Ok(cursor) = cursor.get_single() else {
+        return;
+    };
+    // racers don't get to see where the others are looking
+    if !session.is_active() || session.watching || session.mode == Mode::Race {
+        return;
+    }
+    session.cursor = (cursor.row, cursor.col);
+    let player = session.player;
+    session.send(&Message::Cursor {
+        player,
+        row: cursor.row,
+        col: cursor.col,
+    });
+}
+
+/// Tells those watching what is on the board here whenever it changes: the
+/// puzzle, or the replay being watched. When solving together only the
+/// host's board is sent, since everyone has the same one.
+fn share_view(
+    game_state: Res<State<GameState>>,
+    puzzle: Res<Puzzle>,
+    viewer: Option<Res<ReplayViewer>>,
+    mut session: ResMut<Session>,
+) {
+    let is_host = matches!(session.role, Some(Role::Host { .. }));
+    let racing = session.board.is_some() && session.mode == Mode::Race;
+    if session.watching || !(is_host || racing) {
+        return;
+    }
+    let view = match (game_state.get(), &viewer) {
+        (GameState::Replay, Some(viewer)) => viewer.board(),
+        // a draft in the editor isn't anything to watch
+        (GameState::Loading | GameState::Editor, _) => return,
+        _ => &puzzle.game_grid,
+    };
+    let player = session.player;
+    let unchanged = session
+        .views
+        .get(&player)
+        .is_some_and(|sent| sent.grid_size == view.grid_size && sent.cells() == view.cells());
+    if unchanged {
+        return;
+    }
+    session.views.insert(player, view.clone());
+    session.cast(&view_message(player, view));
+}
+
+/// Lets in whoever is waiting to join a hosted game, and reads what has
+/// arrived from the other players. The host passes every change on.
+fn receive_messages(
+    game_state: Res<State<GameState>>,
+    mut session: ResMut<Session>,
+    mut puzzle: ResMut<Puzzle>,
+    mut tile_query: Query<(&mut Cell, &GridComponent)>,
+    players: (ResMut<PeerCursors>, ResMut<Race>),
+    mut network_puzzles: NetworkPuzzles,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let (mut peer_cursors, mut race) = players;
+    let session = session.as_mut();
+    let mut status = None;
+    // each message, with the player it came from if this is the host
+    let mut messages = Vec::new();
+    // those who just said whether they are there to play or to watch
+    let mut arrived = Vec::new();
+    match &mut session.role {
+        None => return,
+        Some(Role::Guest(connection)) => match connection.receive() {
+            Ok(received) => messages.extend(received.into_iter().map(|message| (None, message))),
+            Err(err) => {
+                session.end(format!("Lost the connection to the host: {}", err));
+                peer_cursors.0.clear();
+                return;
+            }
+        },
+        Some(Role::Host {
+            listener,
+            pending,
+            guests,
+        }) => {
+            match listener.accept() {
+                Ok(Some(connection)) => pending.push(connection),
+                Ok(None) => {}
+                Err(err) => warn!("couldn't let someone join: {}", err),
+            }
+            // whoever connects says first whether they are there to play or
+            // to watch
+            for mut connection in std::mem::take(pending) {
+                match connection
+                    .receive()
+                    .map(|received| received.into_iter().next())
+                {
+                    Ok(Some(Message::Join)) => arrived.push((connection, false)),
+                    Ok(Some(Message::Watch)) => arrived.push((connection, true)),
+                    Ok(None) => pending.push(connection),
+                    Ok(Some(message)) => {
+                        warn!("{} didn't join first: {:?}", connection.peer(), message);
+                    }
+                    Err(_) => {}
+                }
+            }
+            guests.retain_mut(|guest| match guest.connection.receive() {
+                // those watching can't change anything
+                Ok(_) if guest.watching => true,
+                Ok(received) => {
+                    messages.extend(
+                        received
+                            .into_iter()
+                            .map(|message| (Some(guest.player), message)),
+                    );
+                    true
+                }
+                Err(err) if guest.watching => {
+                    status = Some(format!("Someone stopped watching: {}", err));
+                    false
+                }
+                Err(err) => {
+                    status = Some(format!("Player {} left: {}", guest.player, err));
+                    peer_cursors.0.remove(&guest.player);
+                    session.views.remove(&guest.player);
+                    false
+                }
+            });
+        }
+    }
+    for (connection, watching) in arrived {
+        if let Some(admitted) = admit(session, connection, watching, &puzzle, &peer_cursors, &race)
+        {
+            status = Some(admitted);
+        }
+    }
+    if let Some(status) = status {
+        session.status = status;
+    }
+    // the cells on the board belong to another grid while these are open
+    let shown = !matches!(game_state.get(), GameState::Editor | GameState::Replay);
+    let playing = *game_state.get() == GameState::Playing;
+    let mut changed = false;
+    for (from, message) in messages {
+        let message = match (from, message) {
+            // guests can only speak for themselves
+            (Some(player), Message::Cursor { row, col, .. }) => {
+                Message::Cursor { player, row, col }
+            }
+            (Some(player), Message::Progress { percent, .. }) => {
+                Message::Progress { player, percent }
+            }
+            (Some(player), Message::Finished(_)) => Message::Finished(player),
+            (Some(player), Message::View { code, marks, .. }) => Message::View {
+                player,
+                code,
+                marks,
+            },
+            (_, message) => message,
+        };
+        match &message {
+            &Message::Welcome { player, mode } if from.is_none() => {
+                session.player = player;
+                session.mode = mode;
+            }
+            Message::Full if from.is_none() => {
+                session.end("That game is full".to_string());
+                return;
+            }
+            &Message::Winner(player) if from.is_none() => race.winner = Some(player),
+            &Message::Finished(player) if from.is_some() => {
+                if race.winner.is_none() {
+                    race.winner = Some(player);
+                    session.send(&Message::Winner(player));
+                }
+                continue;
+            }
+            Message::Join
+            | Message::Watch
+            | Message::Welcome { .. }
+            | Message::Full
+            | Message::Winner(_)
+            | Message::Finished(_) => continue,
+            Message::View {
+                player,
+                code,
+                marks,
+            } => {
+                let Some(view) = read_board(code, marks) else {
+                    continue;
+                };
+                if session.watching && *player == session.following {
+                    show_view(
+                        &view,
+                        &mut puzzle,
+                        &mut tile_query,
+                        &mut network_puzzles,
+                        shown,
+                    );
+                }
+                session.views.insert(*player, view);
+                // a racer's board is kept from the other racers
+                if from.is_some() {
+                    session.cast(&message);
+                }
+                continue;
+            }
+            Message::Puzzle { code, marks } => {
+                let Some(grid) = read_board(code, marks) else {
+                    continue;
+                };
+                let new_puzzle = session
+                    .board
+                    .as_ref()
+                    .is_none_or(|board| !same_clues(board, &grid));
+                if session.mode == Mode::Race && new_puzzle {
+                    race.start(&grid);
+                }
+                // those watching show the views of the board they are sent
+                // instead
+                if session.watching {
+                    session.board = Some(grid);
+                    continue;
+                }
+                // everyone races on a board of their own, so only a new
+                // puzzle changes the board then
+                if same_clues(&puzzle.game_grid, &grid) {
+                    if session.mode == Mode::Coop {
+                        changed |= apply_remote_marks(&mut puzzle, &mut tile_query, &grid, shown);
+                    }
+                } else {
+                    network_puzzles.start(&grid);
+                }
+                session.board = Some(grid);
+            }
+            &Message::Cell { row, col, state } => {
+                let Some(board) = session
+                    .board
+                    .as_mut()
+                    .filter(|_| session.mode == Mode::Coop && !session.watching)
+                else {
+                    continue;
+                };
+                let in_bounds = row < board.grid_size.rows && col < board.grid_size.cols;
+                if !in_bounds || matches!(board.get(row, col), CellState::Value(_)) {
+                    continue;
+                }
+                board.set(row, col, state);
+                if same_clues(&puzzle.game_grid, board) && puzzle.game_grid.get(row, col) != state {
+                    apply_remote_cell(&mut puzzle, &mut tile_query, (row, col), state, shown);
+                    changed = true;
+                }
+            }
+            &Message::Cursor { player, row, col } => {
+                if player != session.player {
+                    peer_cursors.0.insert(player, (row, col));
+                }
+            }
+            &Message::Progress { player, percent } => {
+                if player != session.player {
+                    race.progress.insert(player, percent);
+                }
+            }
+        }
+        if from.is_some() {
+            session.send(&message);
+        }
+    }
+    if playing && changed && puzzle.is_solved() {
+        next_state.set(GameState::Won);
+    }
+}
+
+/// Lets in someone who asked to play or to watch and brings them up to date
+/// on the game, or turns them away if there's no room. Returns what happened
+/// for the lobby page.
+fn admit(
+    session: &mut Session,
+    mut connection: Connection,
+    watching: bool,
+    puzzle: &Puzzle,
+    peer_cursors: &PeerCursors,
+    race: &Race,
+) -> Option<String> {
+    let Some(Role::Host { guests, .. }) = &session.role else {
+        return None;
+    };
+    let watchers = guests.iter().filter(|guest| guest.watching).count();
+    let full = if watching {
+        watchers >= MAX_WATCHERS
+    } else {
+        guests.len() - watchers >= MAX_GUESTS
+    };
+    if full {
+        let _ = connection.send(&Message::Full);
+        return None;
+    }
+    let player = session.next_player;
+    session.next_player += 1;
+    let board = session
+        .board
+        .get_or_insert_with(|| puzzle.game_grid.clone());
+    let mut welcome = vec![
+        Message::Welcome {
+            player,
+            mode: session.mode,
+        },
+        puzzle_message(board),
+    ];
+    if session.mode == Mode::Coop {
+        welcome.push(Message::Cursor {
+            player: 0,
+            row: session.cursor.0,
+            col: session.cursor.1,
+        });
+        welcome.extend(
+            peer_cursors
+                .0
+                .iter()
+                .map(|(&player, &(row, col))| Message::Cursor { player, row, col }),
+        );
+    }
+    if watching {
+        welcome.extend(
+            session
+                .views
+                .iter()
+                .map(|(&player, view)| view_message(player, view)),
+        );
+        let own = race.sent.map(|percent| (session.player, percent));
+        welcome.extend(
+            own.into_iter()
+                .chain(
+                    race.progress
+                        .iter()
+                        .map(|(&player, &percent)| (player, percent)),
+                )
+                .map(|(player, percent)| Message::Progress { player, percent }),
+        );
+    }
+    if let Some(winner) = race.winner {
+        welcome.push(Message::Winner(winner));
+    }
+    for message in &welcome {
+        let _ = connection.send(message);
+    }
+    let status = if watching {
+        format!("Someone is watching from {}", connection.peer())
+    } else {
+        format!("Player {} joined from {}", player, connection.peer())
+    };
+    if let Some(Role::Host { guests, .. }) = &mut session.role {
+        guests.push(Guest {
+            player,
+            connection,
+            watching,
+        });
+    }
+    Some(status)
+}
+
+/// Makes the marks of `grid`, a board with the same clues as the puzzle, on
+/// the puzzle and on the board if it is `shown`. Returns whether anything
+/// changed.
+fn apply_remote_marks(
+    puzzle: &mut Puzzle,
+    tile_query: &mut Query<(&mut Cell, &GridComponent)>,
+    grid: &Grid,
+    shown: bool,
+) -> bool {
+    let cols = grid.grid_size.cols;
+    let mut changed = false;
+    for (cell, &state) in grid.cells().iter().enumerate() {
+        let (row, col) = (cell / cols, cell % cols);
+        if puzzle.game_grid.get(row, col) != state {
+            apply_remote_cell(puzzle, tile_query, (row, col), state, shown);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Puts a player's `view` of the board on the board of someone watching:
+/// its marks if it is the puzzle already there, or else its puzzle afresh.
+fn show_view(
+    view: &Grid,
+    puzzle: &mut Puzzle,
+    tile_query: &mut Query<(&mut Cell, &GridComponent)>,
+    network_puzzles: &mut NetworkPuzzles,
+    shown: bool,
+) {
+    if same_clues(&puzzle.game_grid, view) {
+        apply_remote_marks(puzzle, tile_query, view, shown);
+    } else {
+        network_puzzles.start(view);
+    }
+}
+
+/// Makes a change another player made to a cell, on the puzzle and on the
+/// board if it is `shown`. It isn't added to the undo history, which only
+/// holds this player's own moves.
+fn apply_remote_cell(
+    puzzle: &mut Puzzle,
+    tile_query: &mut Query<(&mut Cell, &GridComponent)>,
+    (row, col): (usize, usize),
+    state: CellState,
+    shown: bool,
+) {
+    puzzle.set(row, col, state);
+    if !can_lock(state) {
+        puzzle.game_grid.set_locked(row, col, false);
+    }
+    if !shown {
+        return;
+    }
+    let location = GridComponent::new(row, col);
+    if let Some((mut cell, _)) = tile_query.iter_mut().find(|(_, loc)| **loc == location) {
+        cell.0 = state;
+    }
+}
+
+/// Draws the other players' cursors over the board while it is being
+/// played.
+fn show_peer_cursors(
+    mut commands: Commands,
+    game_state: Res<State<GameState>>,
+    peer_cursors: Res<PeerCursors>,
+    board: (Res<BoardSize>, Res<Settings>, Res<Theme>),
+    cursor: Query<&Handle<Image>, With<Cursor>>,
+    shown: Query<Entity, With<PeerCursor>>,
+) {
+    let (grid_size, settings, theme) = board;
+    for entity in &shown {
+        commands.entity(entity).despawn();
+    }
+    if !matches!(
+        game_state.get(),
+        GameState::Playing | GameState::Won | GameState::Spectating
+    ) {
+        return;
+    }
+    let (texture, scale) = (cursor.single(), cursor_scale(&settings, &theme));
+    for &(row, col) in peer_cursors.0.values() {
+        if row >= grid_size.rows || col >= grid_size.cols {
+            continue;
+        }
+        let position = cell_position(&grid_size, settings.cell_size, row, col);
+        commands.spawn((
+            SpriteBundle {
+                texture: texture.clone(),
+                sprite: Sprite {
+                    color: PEER_CURSOR_COLOR,
+                    ..default()
+                },
+                // just under this player's own cursor
+                transform: Transform {
+                    translation: position.extend(0.9),
+                    scale,
+                    ..default()
+                },
+                ..default()
+            },
+            PeerCursor,
+        ));
+    }
+}
+
+/// Tells the host the puzzle of a race was solved here, or announces it if
+/// this is the host and no one beat them to it.
+fn claim_race_win(mut session: ResMut<Session>, mut race: ResMut<Race>) {
+    if !session.is_active() || session.mode != Mode::Race || race.winner.is_some() {
+        return;
+    }
+    let player = session.player;
+    if matches!(session.role, Some(Role::Host { .. })) {
+        race.winner = Some(player);
+        session.send(&Message::Winner(player));
+    } else {
+        session.send(&Message::Finished(player));
+    }
+}
+
+/// Keeps someone watching on the board when a new puzzle starts, rather
+/// than letting them play it.
+fn keep_watching(session: Res<Session>, mut next_state: ResMut<NextState<GameState>>) {
+    if session.watching {
+        next_state.set(GameState::Spectating);
+    }
+}
+
+/// Shows the board of the next player along with Tab, and goes back to the
+/// lobby page with Escape.
+fn watch_input(
+    actions: Res<Actions>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut session: ResMut<Session>,
+    mut puzzle: ResMut<Puzzle>,
+    mut tile_query: Query<(&mut Cell, &GridComponent)>,
+    mut network_puzzles: NetworkPuzzles,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if actions.triggered(Action::Quit) {
+        next_state.set(GameState::Lobby);
+        return;
+    }
+    if !keyboard_input.just_pressed(KeyCode::Tab) {
+        return;
+    }
+    let following = session.following;
+    let next = session
+        .views
+        .range(following + 1..)
+        .chain(session.views.range(..=following))
+        .next();
+    if let Some((&player, view)) = next {
+        show_view(
+            view,
+            &mut puzzle,
+            &mut tile_query,
+            &mut network_puzzles,
+            true,
+        );
+        session.following = player;
+    }
+}
+
+fn spawn_race_bar(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgba(0.0, 0.0, 0.0, 0.6),
+                    custom_size: Some(RACE_BAR_SIZE),
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::new(0.0, 0.0, 2.0)),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            RaceBar,
+            ScreenAnchor::new(Vec2::NEG_Y, Vec2::new(0.0, RACE_BAR_SIZE.y)),
+            UI_LAYER,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::srgb(0.9, 0.45, 0.1),
+                        custom_size: Some(Vec2::new(0.0, RACE_BAR_SIZE.y)),
+                        anchor: Anchor::CenterLeft,
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(-RACE_BAR_SIZE.x / 2.0, 0.0, 0.5),
+                    ..default()
+                },
+                RaceBarFill,
+                UI_LAYER,
+            ));
+            builder.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load("FiraSans-Regular.ttf"),
+                            font_size: 24.0,
+                            ..default()
+                        },
+                    ),
+                    transform: Transform::from_translation(Vec3::Z),
+                    ..default()
+                },
+                RaceText,
+                UI_LAYER,
+            ));
+        });
+}
+
+/// Fills the race bar up to the share of the puzzle the furthest along of
+/// the other players has right, without showing which cells those are, and
+/// says who won once someone has. Those watching see the share of the player
+/// they follow, and how far along everyone is.
+fn update_race_bar(
+    game_state: Res<State<GameState>>,
+    session: Res<Session>,
+    race: Res<Race>,
+    mut bar: Query<&mut Visibility, With<RaceBar>>,
+    mut fill: Query<&mut Sprite, With<RaceBarFill>>,
+    mut race_text: Query<&mut Text, With<RaceText>>,
+) {
+    let racing = session.is_active()
+        && session.mode == Mode::Race
+        && matches!(
+            game_state.get(),
+            GameState::Playing | GameState::Won | GameState::Spectating
+        );
+    let visibility = if racing {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    bar.single_mut().set_if_neq(visibility);
+    if !racing {
+        return;
+    }
+    let leader = if session.watching {
+        let following = session.following;
+        race.progress
+            .get(&following)
+            .map(|&percent| (following, percent))
+    } else {
+        race.progress
+            .iter()
+            .max_by_key(|(_, &percent)| percent)
+            .map(|(&player, &percent)| (player, percent))
+    };
+    let text = match (race.winner, leader) {
+        (Some(winner), _) if winner == session.player => "You won the race!".to_string(),
+        (Some(winner), _) => format!("Player {} won the race", winner),
+        (None, Some(_)) if session.watching => race
+            .progress
+            .iter()
+            .map(|(player, percent)| format!("Player {}: {}%", player, percent))
+            .collect::<Vec<_>>()
+            .join("   "),
+        (None, Some((player, percent))) => format!("Player {}: {}%", player, percent),
+        (None, None) => "Waiting for the others to start".to_string(),
+    };
+    let percent = leader.map_or(0, |(_, percent)| percent);
+    let width = RACE_BAR_SIZE.x * percent as f32 / 100.0;
+    let size = Some(Vec2::new(width, RACE_BAR_SIZE.y));
+    let mut fill = fill.single_mut();
+    if fill.custom_size != size {
+        fill.custom_size = size;
+    }
+    let mut race_text = race_text.single_mut();
+    if race_text.sections[0].value != text {
+        race_text.sections[0].value = text;
+    }
+}
+
+fn spawn_lobby_panel(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgba(0.0, 0.0, 0.0, 0.85),
+                    custom_size: Some(MENU_SIZE),
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::new(0.0, 0.0, 4.0)),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            LobbyPanel,
+            UI_LAYER,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load("FiraSans-Regular.ttf"),
+                            font_size: 24.0,
+                            ..default()
+                        },
+                    )
+                    .with_justify(JustifyText::Left),
+                    transform: Transform::from_translation(Vec3::Z),
+                    ..default()
+                },
+                LobbyText,
+                UI_LAYER,
+            ));
+        });
+}
+
+/// Picks between hosting a game to solve together, hosting a race, joining
+/// and watching with Up and Down, types the address to join or watch, and
+/// starts the picked one with Enter. Once in a game, Enter goes to the
+/// board and D leaves the game. Escape goes back to the menu.
+fn lobby_input(
+    game_state: Res<State<GameState>>,
+    mut keyboard_input_ev: EventReader<KeyboardInput>,
+    mut lobby: ResMut<Lobby>,
+    mut session: ResMut<Session>,
+    mut peer_cursors: ResMut<PeerCursors>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    // read the events every frame, so the key that opened the page isn't
+    // typed into it
+    let events: Vec<&KeyboardInput> = keyboard_input_ev
+        .read()
+        .filter(|ev| ev.state == ButtonState::Pressed)
+        .collect();
+    if *game_state != GameState::Lobby {
+        return;
+    }
+    for ev in events {
+        match &ev.logical_key {
+            Key::Escape => {
+                next_state.set(GameState::Menu);
+                return;
+            }
+            Key::Enter if session.is_active() => {
+                next_state.set(if session.watching {
+                    GameState::Spectating
+                } else {
+                    GameState::Playing
+                });
+                return;
+            }
+            Key::Character(chars) if session.is_active() && chars.eq_ignore_ascii_case("d") => {
+                session.end("Left the game".to_string());
+                peer_cursors.0.clear();
+            }
+            _ if session.is_active() => {}
+            Key::ArrowUp => lobby.selected = (lobby.selected + LOBBY_CHOICES - 1) % LOBBY_CHOICES,
+            Key::ArrowDown => lobby.selected = (lobby.selected + 1) % LOBBY_CHOICES,
+            Key::Character(chars) if lobby.joining() => {
+                for c in chars.chars() {
+                    let allowed = c.is_ascii_alphanumeric() || ".:-[]".contains(c);
+                    if allowed && lobby.address.len() < MAX_ADDRESS_LENGTH {
+                        lobby.address.push(c);
+                    }
+                }
+            }
+            Key::Backspace if lobby.joining() => {
+                lobby.address.pop();
+            }
+            Key::Enter if lobby.joining() => {
+                session.join(&lobby.address, lobby.watching());
+            }
+            Key::Enter => {
+                let mode = if lobby.selected == 0 {
+                    Mode::Coop
+                } else {
+                    Mode::Race
+                };
+                if let Err(err) = session.host(mode) {
+                    session.status = format!("Couldn't host a game: {}", err);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn finish_joining(mut session: ResMut<Session>) {
+    let Some((_, _, job)) = &mut session.joining else {
+        return;
+    };
+    let Some(connection) = job.poll() else {
+        return;
+    };
+    let (address, watching, _) = session.joining.take().unwrap();
+    if let Err(err) = connection.and_then(|connection| session.joined(connection, watching)) {
+        session.status = format!("Couldn't join {}: {}", address, err);
+    }
+}
+
+fn update_lobby_page(
+    lobby: Res<Lobby>,
+    session: Res<Session>,
+    mut lobby_text: Query<&mut Text, With<LobbyText>>,
+) {
+    let mut text = String::from("Play together over the network\n\n");
+    match &session.role {
+        None => {
+            let marker = |selected: bool| if selected { "> " } else { "  " };
+            text.push_str(&format!(
+                "{}Host a game to solve together\n{}Host a race\n{}Join a game\n{}Watch a game\n\nAddress: {}{}\n\n",
+                marker(lobby.selected == 0),
+                marker(lobby.selected == 1),
+                marker(lobby.joining() && !lobby.watching()),
+                marker(lobby.watching()),
+                lobby.address,
+                if lobby.joining() { "_" } else { "" }
+            ));
+            text.push_str(&session.status);
+            text.push_str(
+                "\n\nUp/Down to choose, type the address to join or watch (host:port),\nEnter to start, Esc to go back",
+            );
+        }
+        Some(_) if session.watching => {
+            let follow = match session.mode {
+                Mode::Coop => "The board shows the host's, and the cursors of everyone playing.",
+                Mode::Race => "The board shows one racer's, and Tab on it switches to the next\nracer's. The bar at the bottom shows how far along everyone is.",
+            };
+            text.push_str(&format!(
+                "{}\n\nYou are watching, so nothing can be marked on the board.\n{}\n\nEnter to go to the board, D to stop watching, Esc to go back",
+                session.status, follow
+            ));
+        }
+        Some(role) => {
+            let (players, watchers) = match role {
+                Role::Host { guests, .. } => {
+                    let watchers = guests.iter().filter(|guest| guest.watching).count();
+                    (guests.len() - watchers + 1, watchers)
+                }
+                Role::Guest(_) => (2, 0),
+            };
+            let rules = match session.mode {
+                Mode::Coop => "Every mark anyone makes shows up for everyone, and the last one to\nreach a cell wins.",
+                Mode::Race => "Everyone solves the same puzzle on a board of their own, and the first\nto solve it wins. The bar at the bottom shows how far along the others are.",
+            };
+            let watching = match watchers {
+                0 => String::new(),
+                _ => format!(", with {} more watching", watchers),
+            };
+            text.push_str(&format!(
+                "{}\n\nYou are player {} of the {} in the game{}.\n{} Whoever starts a new\npuzzle starts it for everyone.\n\nEnter to go to the board, D to leave the game, Esc to go back",
+                session.status,
+                session.player,
+                players,
+                watching,
+                rules
+            ));
+        }
+    }
+    let mut lobby_text = lobby_text.single_mut();
+    if lobby_text.sections[0].value != text {
+        lobby_text.sections[0].value = text;
+    }
+}
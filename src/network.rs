@@ -0,0 +1,314 @@
+//! Talking to other copies of the game over the network. Every message is a
+//! line of text sent over TCP, and nothing blocks: sockets are polled once a
+//! frame for whatever has arrived. The one exception is
+//! `Connection::connect`, which waits on the other side and so is run on
+//! another thread.
+
+use std::{
+    io::{self, Read, Write},
+    net::{IpAddr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+    time::Duration,
+};
+
+use bevy::log::warn;
+use nurikabe_core::CellState;
+
+/// The port games are hosted on, and joined on when the address leaves it
+/// out.
+pub const DEFAULT_PORT: u16 = 7878;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// The longest line accepted from the other side, so a broken peer can't
+/// fill up memory. A share code of the largest board fits easily.
+const MAX_LINE_LENGTH: usize = 1 << 16;
+
+/// What a network game is played for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Mode {
+    /// Everyone marks the same board.
+    #[default]
+    Coop,
+    /// Everyone solves the same puzzle on a board of their own, and the first
+    /// to solve it wins.
+    Race,
+}
+
+impl Mode {
+    fn word(self) -> &'static str {
+        match self {
+            Mode::Coop => "coop",
+            Mode::Race => "race",
+        }
+    }
+
+    fn parse(word: &str) -> Option<Mode> {
+        [Mode::Coop, Mode::Race]
+            .into_iter()
+            .find(|mode| mode.word() == word)
+    }
+}
+
+/// Something one copy of the game tells another. The host is player 0 and
+/// numbers the others as they join.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Message {
+    /// Sent to the host right after connecting, to play.
+    Join,
+    /// Sent to the host right after connecting, to watch without playing.
+    Watch,
+    /// Sent by the host to whoever just joined, with their player number.
+    Welcome { player: usize, mode: Mode },
+    /// Sent by the host instead of `Welcome` when no one else can join.
+    Full,
+    /// The puzzle being played, as a share code, and the marks on it as
+    /// written by `Grid::marks_string`.
+    Puzzle { code: String, marks: String },
+    Cell {
+        row: usize,
+        col: usize,
+        state: CellState,
+    },
+    Cursor {
+        player: usize,
+        row: usize,
+        col: usize,
+    },
+    /// How much of the puzzle a player has right in a race, as a percentage.
+    Progress { player: usize, percent: u32 },
+    /// Sent to the host by a player who solved the puzzle of a race.
+    Finished(usize),
+    /// Sent by the host to announce who solved the puzzle of a race first.
+    Winner(usize),
+    /// What a player has on the board, for those watching: the puzzle and
+    /// its marks like `Puzzle`.
+    View {
+        player: usize,
+        code: String,
+        marks: String,
+    },
+}
+
+impl Message {
+    /// The message as a line, without the newline: a word naming it, then
+    /// its fields separated by spaces.
+    pub fn to_line(&self) -> String {
+        match self {
+            Message::Join => "join".to_string(),
+            Message::Watch => "watch".to_string(),
+            Message::Welcome { player, mode } => format!("welcome {} {}", player, mode.word()),
+            Message::Full => "full".to_string(),
+            Message::Puzzle { code, marks } => format!("puzzle {} {}", code, marks),
+            Message::Cell { row, col, state } => {
+                format!("cell {} {} {}", row, col, mark_char(*state))
+            }
+            Message::Cursor { player, row, col } => format!("cursor {} {} {}", player, row, col),
+            Message::Progress { player, percent } => format!("progress {} {}", player, percent),
+            Message::Finished(player) => format!("finished {}", player),
+            Message::Winner(player) => format!("winner {}", player),
+            Message::View {
+                player,
+                code,
+                marks,
+            } => format!("view {} {} {}", player, code, marks),
+        }
+    }
+
+    /// Reads a line written by `to_line`.
+    pub fn parse(line: &str) -> Option<Message> {
+        let mut words = line.split_whitespace();
+        let message = match words.next()? {
+            "join" => Message::Join,
+            "watch" => Message::Watch,
+            "welcome" => Message::Welcome {
+                player: words.next()?.parse().ok()?,
+                mode: Mode::parse(words.next()?)?,
+            },
+            "full" => Message::Full,
+            "puzzle" => Message::Puzzle {
+                code: words.next()?.to_string(),
+                marks: words.next()?.to_string(),
+            },
+            "cell" => Message::Cell {
+                row: words.next()?.parse().ok()?,
+                col: words.next()?.parse().ok()?,
+                state: parse_mark(words.next()?)?,
+            },
+            "cursor" => Message::Cursor {
+                player: words.next()?.parse().ok()?,
+                row: words.next()?.parse().ok()?,
+                col: words.next()?.parse().ok()?,
+            },
+            "progress" => Message::Progress {
+                player: words.next()?.parse().ok()?,
+                percent: words.next()?.parse().ok()?,
+            },
+            "finished" => Message::Finished(words.next()?.parse().ok()?),
+            "winner" => Message::Winner(words.next()?.parse().ok()?),
+            "view" => Message::View {
+                player: words.next()?.parse().ok()?,
+                code: words.next()?.to_string(),
+                marks: words.next()?.to_string(),
+            },
+            _ => return None,
+        };
+        Some(message)
+    }
+}
+
+/// A cell's state as it is written in `Grid::marks_string`.
+fn mark_char(state: CellState) -> char {
+    match state {
+        CellState::Island => '.',
+        CellState::River => 'X',
+        CellState::Maybe => '?',
+        CellState::Blank | CellState::Value(_) => '-',
+    }
+}
+
+fn parse_mark(mark: &str) -> Option<CellState> {
+    match mark {
+        "." => Some(CellState::Island),
+        "X" => Some(CellState::River),
+        "?" => Some(CellState::Maybe),
+        "-" => Some(CellState::Blank),
+        _ => None,
+    }
+}
+
+/// A connection to another copy of the game.
+pub struct Connection {
+    stream: TcpStream,
+    /// What has been read that doesn't make up a whole line yet.
+    incoming: Vec<u8>,
+    /// What couldn't be written yet without waiting.
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Connection {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+        })
+    }
+
+    /// Connects to a game hosted at `address`, a host name or IP address
+    /// with an optional `:port` after it.
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let address = address.trim();
+        let has_port = address
+            .rsplit_once(':')
+            .is_some_and(|(_, port)| port.parse::<u16>().is_ok());
+        let addresses: Vec<SocketAddr> = if has_port {
+            address.to_socket_addrs()?.collect()
+        } else {
+            (address, DEFAULT_PORT).to_socket_addrs()?.collect()
+        };
+        let mut last_err = io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{:?} doesn't name a computer", address),
+        );
+        for address in addresses {
+            match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+                Ok(stream) => return Connection::new(stream),
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err)
+    }
+
+    /// Sends `message`, keeping whatever the socket won't take right away
+    /// for the next call.
+    pub fn send(&mut self, message: &Message) -> io::Result<()> {
+        self.outgoing.extend(message.to_line().bytes());
+        self.outgoing.push(b'\n');
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Every message that has arrived since the last call. Lines that can't
+    /// be read are skipped, in case the other side runs a newer version.
+    /// Fails once the other side has hung up.
+    pub fn receive(&mut self) -> io::Result<Vec<Message>> {
+        let mut buffer = [0; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "the connection was closed",
+                    ))
+                }
+                Ok(read) => self.incoming.extend_from_slice(&buffer[..read]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        let mut messages = Vec::new();
+        while let Some(end) = self.incoming.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.incoming.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            match Message::parse(&line) {
+                Some(message) => messages.push(message),
+                None => warn!("ignoring {:?} from the network", line.trim()),
+            }
+        }
+        if self.incoming.len() > MAX_LINE_LENGTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the other side sent a line that is far too long",
+            ));
+        }
+        Ok(messages)
+    }
+
+    /// The address of the other side, for showing who is connected.
+    pub fn peer(&self) -> String {
+        self.stream
+            .peer_addr()
+            .map_or_else(|_| "someone".to_string(), |address| address.to_string())
+    }
+}
+
+/// Waits for others to join a hosted game.
+pub struct Listener(TcpListener);
+
+impl Listener {
+    /// Starts listening on `port` on every network the computer is on.
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Listener(listener))
+    }
+
+    /// The next person to join, if anyone is waiting.
+    pub fn accept(&self) -> io::Result<Option<Connection>> {
+        match self.0.accept() {
+            Ok((stream, _)) => Connection::new(stream).map(Some),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// The address others on the same network can reach this computer at. The
+/// socket is never sent anything; connecting it only asks the system which
+/// network it would use.
+pub fn local_address() -> Option<IpAddr> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0)).ok()?;
+    socket.connect(("192.0.2.1", DEFAULT_PORT)).ok()?;
+    socket.local_addr().ok().map(|address| address.ip())
+}
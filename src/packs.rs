@@ -0,0 +1,329 @@
+//! Puzzle packs: folders of `assets/puzzles` with a manifest naming the pack
+//! and the order its puzzles are played in, and the page for picking them.
+
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+
+use crate::{
+    file_name, hide_panel,
+    input::{self, Action, Actions},
+    progress::{self, PuzzleProgress, Record},
+    puzzle_files, show_panel, ChooseMenuItem, GameState, MenuItem, Puzzle, PuzzleSource, MENU_SIZE,
+    UI_LAYER,
+};
+
+/// A named set of puzzle files, played in order.
+pub struct Pack {
+    pub name: String,
+    pub puzzles: Vec<PathBuf>,
+    /// Whether each puzzle stays locked until the one before it is solved.
+    pub in_order: bool,
+}
+
+impl Pack {
+    /// Reads the manifest of the pack in `dir`: a `name = ...` line and a
+    /// `puzzle = <file>` line for each puzzle in the folder, in the order
+    /// they are played, with `in_order = true` to unlock them one at a time.
+    /// Lines starting with `#` are comments. A pack without a name is named
+    /// after its folder.
+    pub fn parse(dir: &Path, manifest: &str) -> Self {
+        let mut pack = Pack {
+            name: dir
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().to_string()),
+            puzzles: Vec::new(),
+            in_order: false,
+        };
+        for line in manifest.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                warn!("ignoring {:?} in the manifest of {:?}", line, dir);
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "name" => pack.name = value.to_string(),
+                "puzzle" => pack.puzzles.push(dir.join(value)),
+                "in_order" => match value.parse() {
+                    Ok(in_order) => pack.in_order = in_order,
+                    Err(_) => warn!("ignoring {:?} in the manifest of {:?}", line, dir),
+                },
+                _ => warn!("ignoring {:?} in the manifest of {:?}", line, dir),
+            }
+        }
+        pack
+    }
+
+    /// How many of the pack's puzzles have been solved.
+    pub fn solved(&self, puzzle_progress: &PuzzleProgress) -> usize {
+        self.puzzles
+            .iter()
+            .filter(|path| puzzle_progress.record(path).is_some())
+            .count()
+    }
+
+    /// Whether the puzzle at `index` can be played yet: always, unless the
+    /// pack is played in order and the puzzle before it hasn't been solved.
+    pub fn is_unlocked(&self, index: usize, puzzle_progress: &PuzzleProgress) -> bool {
+        !self.in_order
+            || index == 0
+            || self
+                .puzzles
+                .get(index - 1)
+                .is_some_and(|path| puzzle_progress.record(path).is_some())
+    }
+}
+
+/// Every pack found in `assets/puzzles`, sorted by folder name.
+#[derive(Resource, Default)]
+pub struct Packs(pub Vec<Pack>);
+
+impl Packs {
+    pub fn load() -> Self {
+        let packs = match puzzle_files::list_packs() {
+            Ok(packs) => packs
+                .iter()
+                .map(|(dir, manifest)| Pack::parse(dir, manifest))
+                .collect(),
+            Err(err) => {
+                warn!("couldn't look for puzzle packs: {}", err);
+                Vec::new()
+            }
+        };
+        Packs(packs)
+    }
+
+    /// The index of the pack holding the puzzle file at `path`, and where in
+    /// the pack it is.
+    pub fn find(&self, path: &Path) -> Option<(usize, usize)> {
+        self.0.iter().enumerate().find_map(|(i, pack)| {
+            let place = pack.puzzles.iter().position(|puzzle| puzzle == path)?;
+            Some((i, place))
+        })
+    }
+}
+
+/// Where the player is on the packs page: choosing a pack, or a puzzle in
+/// the pack they opened.
+#[derive(Resource, Default)]
+struct PackMenu {
+    pack: usize,
+    /// The puzzle selected in the open pack, or `None` while choosing a pack.
+    puzzle: Option<usize>,
+}
+
+/// The backdrop of the packs page, shown while it is open.
+#[derive(Component)]
+struct PacksPanel;
+
+#[derive(Component)]
+struct PacksText;
+
+/// The packs page, opened from the main menu.
+pub struct PacksPlugin;
+
+impl Plugin for PacksPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Packs::load())
+            .init_resource::<PackMenu>()
+            .add_systems(Startup, spawn_packs_panel)
+            .add_systems(
+                Update,
+                (
+                    pack_menu_input
+                        .after(input::read_actions)
+                        .run_if(in_state(GameState::Packs)),
+                    update_packs_page.run_if(
+                        resource_changed::<PackMenu>.or_else(resource_changed::<PuzzleProgress>),
+                    ),
+                ),
+            )
+            .add_systems(
+                OnEnter(GameState::Packs),
+                (open_pack_menu, show_panel::<PacksPanel>),
+            )
+            .add_systems(OnExit(GameState::Packs), hide_panel::<PacksPanel>);
+    }
+}
+
+fn spawn_packs_panel(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgba(0.0, 0.0, 0.0, 0.85),
+                    custom_size: Some(MENU_SIZE),
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::new(0.0, 0.0, 4.0)),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            PacksPanel,
+            UI_LAYER,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                Text2dBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load("FiraSans-Regular.ttf"),
+                            font_size: 24.0,
+                            ..default()
+                        },
+                    )
+                    .with_justify(JustifyText::Left),
+                    transform: Transform::from_translation(Vec3::Z),
+                    ..default()
+                },
+                PacksText,
+                UI_LAYER,
+            ));
+        });
+}
+
+/// Starts on the pack list, with the pack of the puzzle being played
+/// selected if it is in one.
+fn open_pack_menu(packs: Res<Packs>, puzzle: Res<Puzzle>, mut pack_menu: ResMut<PackMenu>) {
+    let pack = match &puzzle.source {
+        PuzzleSource::File(path) => packs.find(path).map(|(pack, _)| pack),
+        _ => None,
+    };
+    *pack_menu = PackMenu {
+        pack: pack.unwrap_or(0),
+        puzzle: None,
+    };
+}
+
+/// Moves through the packs, or the puzzles of the open pack. Enter opens a
+/// pack or plays a puzzle, and Escape goes back a step.
+fn pack_menu_input(
+    actions: Res<Actions>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    packs: Res<Packs>,
+    puzzle_progress: Res<PuzzleProgress>,
+    mut pack_menu: ResMut<PackMenu>,
+    mut choose_menu_item_ev: EventWriter<ChooseMenuItem>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if actions.triggered(Action::Quit) {
+        match pack_menu.puzzle {
+            Some(_) => pack_menu.puzzle = None,
+            None => next_state.set(GameState::Menu),
+        }
+        return;
+    }
+    let Some(pack) = packs.0.get(pack_menu.pack) else {
+        return;
+    };
+    let count = match pack_menu.puzzle {
+        Some(_) => pack.puzzles.len(),
+        None => packs.0.len(),
+    };
+    if count == 0 {
+        return;
+    }
+    let step = |selected: usize| {
+        if actions.triggered(Action::MoveUp) {
+            (selected + count - 1) % count
+        } else if actions.triggered(Action::MoveDown) {
+            (selected + 1) % count
+        } else {
+            selected
+        }
+    };
+    match pack_menu.puzzle {
+        None => {
+            let selected = step(pack_menu.pack);
+            if selected != pack_menu.pack {
+                pack_menu.pack = selected;
+            }
+        }
+        Some(puzzle) => {
+            let selected = step(puzzle);
+            if selected != puzzle {
+                pack_menu.puzzle = Some(selected);
+            }
+        }
+    }
+    if !(keyboard_input.just_pressed(KeyCode::Enter) || actions.triggered(Action::Toggle)) {
+        return;
+    }
+    match pack_menu.puzzle {
+        // open the pack at its first unsolved puzzle
+        None => {
+            let first_unsolved = pack
+                .puzzles
+                .iter()
+                .position(|path| puzzle_progress.record(path).is_none());
+            pack_menu.puzzle = Some(first_unsolved.unwrap_or(0));
+        }
+        Some(puzzle) if pack.is_unlocked(puzzle, &puzzle_progress) => {
+            if let Some(path) = pack.puzzles.get(puzzle) {
+                choose_menu_item_ev.send(ChooseMenuItem(MenuItem::File(path.clone())));
+            }
+        }
+        Some(_) => {}
+    }
+}
+
+fn update_packs_page(
+    packs: Res<Packs>,
+    pack_menu: Res<PackMenu>,
+    puzzle_progress: Res<PuzzleProgress>,
+    mut packs_text: Query<&mut Text, With<PacksText>>,
+) {
+    let marker = |selected: bool| if selected { "> " } else { "  " };
+    let mut text = String::new();
+    match (packs.0.get(pack_menu.pack), pack_menu.puzzle) {
+        (None, _) => {
+            text.push_str("Puzzle packs\n\nThere are no puzzle packs in assets/puzzles yet.\n");
+            text.push_str("\nM to go back");
+        }
+        (Some(_), None) => {
+            text.push_str("Puzzle packs\n\n");
+            for (i, pack) in packs.0.iter().enumerate() {
+                text.push_str(&format!(
+                    "{}{} ({}/{} solved)\n",
+                    marker(i == pack_menu.pack),
+                    pack.name,
+                    pack.solved(&puzzle_progress),
+                    pack.puzzles.len()
+                ));
+            }
+            text.push_str("\nUp/Down to choose, Enter to open, Esc to go back");
+        }
+        (Some(pack), Some(selected)) => {
+            text.push_str(&format!(
+                "{} ({}/{} solved)\n\n",
+                pack.name,
+                pack.solved(&puzzle_progress),
+                pack.puzzles.len()
+            ));
+            for (i, path) in pack.puzzles.iter().enumerate() {
+                let status = match puzzle_progress.record(path) {
+                    Some(Record { seconds, .. }) => {
+                        format!(" (solved, best {})", progress::format_time(seconds))
+                    }
+                    None if !pack.is_unlocked(i, &puzzle_progress) => {
+                        " (locked until the one before is solved)".to_string()
+                    }
+                    None => String::new(),
+                };
+                text.push_str(&format!(
+                    "{}{}. {}{}\n",
+                    marker(i == selected),
+                    i + 1,
+                    file_name(path),
+                    status
+                ));
+            }
+            text.push_str("\nUp/Down to choose, Enter to play, Esc to go back to the packs");
+        }
+    }
+    packs_text.single_mut().sections[0].value = text;
+}
@@ -0,0 +1,105 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use bevy::prelude::*;
+
+use crate::storage;
+
+const PROFILES_PATH: &str = "./saves/profiles.txt";
+/// Where the first profile keeps its files, which is where every file was
+/// kept before there were profiles.
+const SAVES_DIR: &str = "./saves";
+/// Where every other profile gets a directory of its own.
+const PROFILES_DIR: &str = "./saves/profiles";
+
+/// The save directory of the profile being played, read by `path`. `None`
+/// until the profiles are loaded, which means the first profile.
+static CURRENT_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Where the file called `name` is kept for the profile being played.
+pub fn path(name: &str) -> PathBuf {
+    let dir = CURRENT_DIR.read().ok().and_then(|dir| dir.clone());
+    dir.unwrap_or_else(|| PathBuf::from(SAVES_DIR)).join(name)
+}
+
+/// The people playing on this machine, each with their own settings, key
+/// bindings and progress.
+#[derive(Resource)]
+pub struct Profiles {
+    names: Vec<String>,
+    current: usize,
+}
+
+impl Profiles {
+    /// Reads the profiles file: the name of the current profile, then one
+    /// line per profile. With no file there is a single profile.
+    pub fn load() -> Self {
+        let contents = storage::read(PROFILES_PATH).unwrap_or_default();
+        let mut lines = contents.lines().map(str::trim);
+        let current = lines.next().unwrap_or_default();
+        let mut names: Vec<String> = lines
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+        if names.is_empty() {
+            names.push(profile_name(1));
+        }
+        let current = names.iter().position(|name| name == current).unwrap_or(0);
+        let profiles = Profiles { names, current };
+        profiles.set_current_dir();
+        profiles
+    }
+
+    pub fn current(&self) -> &str {
+        &self.names[self.current]
+    }
+
+    /// Switches to the profile after the current one, wrapping back to the
+    /// first.
+    pub fn select_next(&mut self) {
+        self.current = (self.current + 1) % self.names.len();
+        self.set_current_dir();
+        self.save();
+    }
+
+    /// Makes a new profile, named after how many there are, and switches to
+    /// it.
+    pub fn add(&mut self) {
+        let mut number = self.names.len() + 1;
+        while self.names.contains(&profile_name(number)) {
+            number += 1;
+        }
+        self.names.push(profile_name(number));
+        self.current = self.names.len() - 1;
+        self.set_current_dir();
+        self.save();
+    }
+
+    fn set_current_dir(&self) {
+        let dir = if self.current == 0 {
+            PathBuf::from(SAVES_DIR)
+        } else {
+            Path::new(PROFILES_DIR).join(self.current())
+        };
+        if let Ok(mut current_dir) = CURRENT_DIR.write() {
+            *current_dir = Some(dir);
+        }
+    }
+
+    fn save(&self) {
+        let mut contents = format!("{}\n", self.current());
+        for name in &self.names {
+            contents.push_str(&format!("{}\n", name));
+        }
+        let path = Path::new(PROFILES_PATH);
+        if let Err(err) = storage::write(path, &contents) {
+            warn!("failed to save profiles: {}", err);
+        }
+    }
+}
+
+fn profile_name(number: usize) -> String {
+    format!("Player {}", number)
+}
@@ -0,0 +1,95 @@
+use std::{collections::BTreeMap, path::Path};
+
+use bevy::prelude::*;
+
+use crate::{profile, storage};
+
+const PUZZLE_PROGRESS_FILE: &str = "progress.txt";
+
+/// The fastest solve of a puzzle.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Record {
+    pub seconds: f32,
+    /// The cells changed on the way, or `None` for records set before moves
+    /// were counted.
+    pub moves: Option<u32>,
+}
+
+/// The puzzle files the player has solved and their record on each, keyed
+/// by file name and saved to disk.
+#[derive(Resource, Default)]
+pub struct PuzzleProgress {
+    records: BTreeMap<String, Record>,
+}
+
+impl PuzzleProgress {
+    /// Reads the progress file, one `name,seconds,moves` line per puzzle.
+    /// Older files without the moves are read too.
+    pub fn load() -> Self {
+        let mut records = BTreeMap::new();
+        if let Ok(contents) = storage::read(profile::path(PUZZLE_PROGRESS_FILE)) {
+            for line in contents.lines() {
+                let Some((rest, last)) = line.rsplit_once(',') else {
+                    continue;
+                };
+                let with_moves = rest.rsplit_once(',').and_then(|(name, seconds)| {
+                    Some((name, seconds.parse().ok()?, Some(last.parse().ok()?)))
+                });
+                let record = with_moves.or_else(|| Some((rest, last.parse().ok()?, None)));
+                if let Some((name, seconds, moves)) = record {
+                    records.insert(name.to_string(), Record { seconds, moves });
+                }
+            }
+        }
+        PuzzleProgress { records }
+    }
+
+    /// The record on the puzzle at `path`, or `None` if it hasn't been
+    /// solved yet.
+    pub fn record(&self, path: &Path) -> Option<Record> {
+        self.records.get(&key(path)).copied()
+    }
+
+    /// Marks the puzzle at `path` as solved in `seconds` and `moves`, and
+    /// writes the progress to disk if that's a new best time. Returns whether
+    /// it was.
+    pub fn complete(&mut self, path: &Path, seconds: f32, moves: u32) -> bool {
+        let record = Record {
+            seconds,
+            moves: Some(moves),
+        };
+        let best = self.records.entry(key(path)).or_insert(Record {
+            seconds: f32::INFINITY,
+            moves: None,
+        });
+        if seconds >= best.seconds {
+            return false;
+        }
+        *best = record;
+        let contents: String = self
+            .records
+            .iter()
+            .map(|(name, record)| match record.moves {
+                Some(moves) => format!("{},{},{}\n", name, record.seconds, moves),
+                None => format!("{},{}\n", name, record.seconds),
+            })
+            .collect();
+        let path = profile::path(PUZZLE_PROGRESS_FILE);
+        if let Err(err) = storage::write(path, &contents) {
+            warn!("failed to save puzzle progress: {}", err);
+        }
+        true
+    }
+}
+
+fn key(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy())
+        .to_string()
+}
+
+/// Formats a time in seconds as minutes and seconds, like `3:07`.
+pub fn format_time(seconds: f32) -> String {
+    let seconds = seconds as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
@@ -0,0 +1,148 @@
+//! Finds and reads the puzzle files that come with the game, and the packs
+//! they are grouped into. On the desktop they are read from `assets/puzzles`;
+//! a browser has no file system to read them from, so the web build has them
+//! compiled in instead.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+
+#[cfg(not(target_arch = "wasm32"))]
+use nurikabe_core::format::{PACK_MANIFEST, PUZZLE_EXTENSIONS};
+use nurikabe_core::grid::GridSize;
+
+const PUZZLE_DIR: &str = "./assets/puzzles";
+
+// `EMBEDDED_PUZZLES` and `EMBEDDED_PACKS`, listed by the build script from
+// whatever is in `assets/puzzles`
+#[cfg(target_arch = "wasm32")]
+include!(concat!(env!("OUT_DIR"), "/embedded_puzzles.rs"));
+
+/// The paths of the puzzle files, sorted by name.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn list() -> io::Result<Vec<PathBuf>> {
+    let mut puzzles = Vec::new();
+    for entry in fs::read_dir(PUZZLE_DIR)?.flatten() {
+        let path = entry.path();
+        if path.file_name().is_some_and(|name| name == PACK_MANIFEST) {
+            continue;
+        }
+        if let Some(extension) = path.extension() {
+            if PUZZLE_EXTENSIONS.iter().any(|ext| extension == *ext) {
+                puzzles.push(path);
+            }
+        }
+    }
+    puzzles.sort();
+    Ok(puzzles)
+}
+
+/// The paths of the puzzle files, sorted by name.
+#[cfg(target_arch = "wasm32")]
+pub fn list() -> io::Result<Vec<PathBuf>> {
+    let mut puzzles: Vec<PathBuf> = EMBEDDED_PUZZLES
+        .iter()
+        // the puzzles in packs are listed by their pack
+        .filter(|(name, _)| !name.contains('/'))
+        .map(|(name, _)| Path::new(PUZZLE_DIR).join(name))
+        .collect();
+    puzzles.sort();
+    Ok(puzzles)
+}
+
+/// The folders holding a pack manifest, sorted by name, with the text of
+/// each manifest.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn list_packs() -> io::Result<Vec<(PathBuf, String)>> {
+    let mut packs = Vec::new();
+    for entry in fs::read_dir(PUZZLE_DIR)?.flatten() {
+        let dir = entry.path();
+        if let Ok(manifest) = fs::read_to_string(dir.join(PACK_MANIFEST)) {
+            packs.push((dir, manifest));
+        }
+    }
+    packs.sort();
+    Ok(packs)
+}
+
+/// The folders holding a pack manifest, sorted by name, with the text of
+/// each manifest.
+#[cfg(target_arch = "wasm32")]
+pub fn list_packs() -> io::Result<Vec<(PathBuf, String)>> {
+    Ok(EMBEDDED_PACKS
+        .iter()
+        .map(|(name, manifest)| (Path::new(PUZZLE_DIR).join(name), manifest.to_string()))
+        .collect())
+}
+
+/// Reads the text of the puzzle file at `path`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(path: &Path) -> io::Result<String> {
+    fs::read_to_string(path)
+}
+
+/// Reads the text of the puzzle file at `path`.
+#[cfg(target_arch = "wasm32")]
+pub fn read(path: &Path) -> io::Result<String> {
+    let name = path.strip_prefix(PUZZLE_DIR).ok();
+    EMBEDDED_PUZZLES
+        .iter()
+        .find(|(embedded, _)| Some(Path::new(embedded)) == name)
+        .map(|(_, text)| text.to_string())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such puzzle"))
+}
+
+/// Writes `text` to a new puzzle file named after `grid_size`, numbered
+/// after the files of that size already there, and returns its path.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_new(grid_size: GridSize, text: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(PUZZLE_DIR)?;
+    let mut number = 1;
+    let path = loop {
+        let name = format!(
+            "puzzle-{}x{}-{}.txt",
+            grid_size.cols, grid_size.rows, number
+        );
+        let path = Path::new(PUZZLE_DIR).join(name);
+        if !path.exists() {
+            break path;
+        }
+        number += 1;
+    };
+    fs::write(&path, text)?;
+    Ok(path)
+}
+
+/// Writes `text` to the file at `path` inside `assets/puzzles`, making its
+/// folder if needed, and returns the full path.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(path: &Path, text: &str) -> io::Result<PathBuf> {
+    let path = Path::new(PUZZLE_DIR).join(path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, text)?;
+    Ok(path)
+}
+
+/// The web build's puzzles are compiled in, so there is nowhere to add one.
+#[cfg(target_arch = "wasm32")]
+pub fn save(_path: &Path, _text: &str) -> io::Result<PathBuf> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "puzzles can't be saved in the browser",
+    ))
+}
+
+/// The web build's puzzles are compiled in, so there is nowhere to add one.
+#[cfg(target_arch = "wasm32")]
+pub fn save_new(_grid_size: GridSize, _text: &str) -> io::Result<PathBuf> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "puzzles can't be saved in the browser",
+    ))
+}
@@ -0,0 +1,390 @@
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{input::common_conditions::input_just_pressed, prelude::*};
+use nurikabe_core::{
+    code,
+    grid::Grid,
+    replay::{self, Replay, REPLAY_EXTENSION},
+};
+
+use crate::{
+    clipboard,
+    difficulty::DifficultyCache,
+    hide_panel,
+    input::{Action, Actions},
+    on_desktop, progress,
+    replays::Replays,
+    show_panel, start_puzzle,
+    theme::Theme,
+    Cell, GameState, GridComponent, Puzzle, PuzzleSource, ScaledText, ScreenAnchor, StartPuzzle,
+    ThemedText, EXPORT_DIR, UI_LAYER,
+};
+
+/// The slowest and fastest a replay plays back, as multiples of the speed
+/// it was solved at.
+const MIN_REPLAY_SPEED: f32 = 0.25;
+const MAX_REPLAY_SPEED: f32 = 16.0;
+/// The longest wait between moves during playback, in seconds of the solve,
+/// so the player doesn't sit through every pause the solver took.
+const MAX_REPLAY_PAUSE_SECONDS: f32 = 1.0;
+const REPLAY_KEYS: &str =
+    "Space: play/pause   Left/Right: step   Up/Down: speed   Home/End: jump   E: share   F6: close";
+
+/// The recorded solve being watched, and how far into it the board is.
+/// Moves made in the same frame, like a drag or a reset, are stepped through
+/// together.
+#[derive(Resource)]
+pub struct ReplayViewer {
+    replay: Replay,
+    /// The board as it was after the first `position` moves.
+    board: Grid,
+    position: usize,
+    playing: bool,
+    speed: f32,
+    /// Seconds of the solve played back since the last move was shown.
+    waited: f32,
+    /// The state to go back to once the viewer is closed.
+    previous: GameState,
+}
+
+impl ReplayViewer {
+    fn new(replay: Replay, previous: GameState) -> Self {
+        ReplayViewer {
+            board: replay.start.clone(),
+            replay,
+            position: 0,
+            playing: true,
+            speed: 1.0,
+            waited: 0.0,
+            previous,
+        }
+    }
+
+    /// The board as it is at the point the replay has reached.
+    pub fn board(&self) -> &Grid {
+        &self.board
+    }
+
+    /// How far into the solve the last move on the board was made.
+    fn seconds(&self) -> f32 {
+        self.position
+            .checked_sub(1)
+            .map_or(0.0, |last| self.replay.moves[last].seconds)
+    }
+
+    /// How long playback waits before the next move, or `None` at the end.
+    fn next_pause(&self) -> Option<f32> {
+        let next = self.replay.moves.get(self.position)?;
+        Some((next.seconds - self.seconds()).min(MAX_REPLAY_PAUSE_SECONDS))
+    }
+
+    /// Puts the next moves on the board. Returns `false` at the end.
+    fn step_forward(&mut self) -> bool {
+        let Some(seconds) = self.replay.moves.get(self.position).map(|m| m.seconds) else {
+            return false;
+        };
+        while let Some(m) = self.replay.moves.get(self.position) {
+            if m.seconds != seconds {
+                break;
+            }
+            self.board.set(m.change.row, m.change.col, m.change.to);
+            self.position += 1;
+        }
+        true
+    }
+
+    /// Takes the last moves off the board. Returns `false` at the start.
+    fn step_back(&mut self) -> bool {
+        if self.position == 0 {
+            return false;
+        }
+        let seconds = self.seconds();
+        while let Some(m) = self.position.checked_sub(1).map(|i| self.replay.moves[i]) {
+            if m.seconds != seconds {
+                break;
+            }
+            self.board.set(m.change.row, m.change.col, m.change.from);
+            self.position -= 1;
+        }
+        true
+    }
+}
+
+/// A replay someone shared, waiting for its puzzle to start so it can be
+/// watched.
+#[derive(Resource, Default)]
+pub struct SharedReplay(Option<Replay>);
+
+impl SharedReplay {
+    /// Starts the puzzle `replay` was recorded on, and opens the viewer on
+    /// it once the board is up.
+    pub fn watch(
+        &mut self,
+        replay: Replay,
+        difficulty_cache: &DifficultyCache,
+        start_puzzle_ev: &mut EventWriter<StartPuzzle>,
+    ) {
+        let game_grid = replay.start.clues_only();
+        let code = code::encode(&game_grid);
+        let difficulty = difficulty_cache.rate(&game_grid);
+        start_puzzle_ev.send(StartPuzzle(Puzzle::new(
+            game_grid,
+            PuzzleSource::Code(code),
+            difficulty,
+        )));
+        self.0 = Some(replay);
+    }
+}
+
+/// Shows the status of the replay and the keys for it along the bottom.
+#[derive(Component)]
+struct ReplayText;
+
+/// Watching a recorded solve again: F6 opens the last one of the current
+/// puzzle, and shared replays open when they are pasted or dropped onto the
+/// window. The board follows the replay until it is closed.
+pub struct ReplayViewerPlugin;
+
+impl Plugin for ReplayViewerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SharedReplay>()
+            .add_systems(Startup, spawn_replay_text)
+            .add_systems(
+                Update,
+                (
+                    open_replay.run_if(
+                        input_just_pressed(KeyCode::F6).and_then(
+                            in_state(GameState::Playing).or_else(in_state(GameState::Won)),
+                        ),
+                    ),
+                    open_shared_replay.after(start_puzzle),
+                    (
+                        (replay_input, play_replay, update_replay_text)
+                            .chain()
+                            .after(crate::input::read_actions),
+                        share_replay.run_if(input_just_pressed(KeyCode::KeyE)),
+                    )
+                        .run_if(in_state(GameState::Replay)),
+                ),
+            )
+            .add_systems(
+                OnEnter(GameState::Replay),
+                (show_replay_start, show_panel::<ReplayText>),
+            )
+            .add_systems(
+                OnExit(GameState::Replay),
+                (close_replay, hide_panel::<ReplayText>),
+            );
+    }
+}
+
+fn spawn_replay_text(mut commands: Commands, asset_server: Res<AssetServer>, theme: Res<Theme>) {
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("FiraSans-Regular.ttf"),
+                    font_size: 24.0,
+                    color: theme.text,
+                },
+            )
+            .with_justify(JustifyText::Center),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        ReplayText,
+        ScreenAnchor::new(Vec2::new(0.0, -1.0), Vec2::new(0.0, 60.0)),
+        ThemedText,
+        ScaledText::new(24.0),
+        UI_LAYER,
+    ));
+}
+
+/// Opens the last recorded solve of the current puzzle, if it has one.
+fn open_replay(
+    mut commands: Commands,
+    puzzle: Res<Puzzle>,
+    replays: Res<Replays>,
+    game_state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(replay) = replays.get(&puzzle.to_code()) else {
+        info!("there is no recorded solve of this puzzle to watch");
+        return;
+    };
+    commands.insert_resource(ReplayViewer::new(replay, game_state.get().clone()));
+    next_state.set(GameState::Replay);
+}
+
+/// Opens the viewer on a shared replay once its puzzle has started.
+fn open_shared_replay(
+    mut commands: Commands,
+    puzzle: Option<Res<Puzzle>>,
+    mut shared_replay: ResMut<SharedReplay>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(puzzle) = puzzle else {
+        return;
+    };
+    if shared_replay.0.is_none() {
+        return;
+    }
+    let code = puzzle.to_code();
+    let Some(replay) = shared_replay
+        .0
+        .take_if(|replay| code::encode(&replay.start) == code)
+    else {
+        return;
+    };
+    commands.insert_resource(ReplayViewer::new(replay, GameState::Playing));
+    next_state.set(GameState::Replay);
+}
+
+/// Copies the replay being watched to the clipboard for sending to someone,
+/// and saves it as a file in the exports folder too on the desktop.
+fn share_replay(viewer: Res<ReplayViewer>) {
+    let text = replay::encode(&viewer.replay);
+    match clipboard::copy(&text) {
+        Ok(()) => info!("Copied the replay"),
+        Err(err) => warn!("couldn't copy the replay to the clipboard: {}", err),
+    }
+    if !on_desktop() {
+        return;
+    }
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let path = Path::new(EXPORT_DIR).join(format!("replay-{}.{}", seconds, REPLAY_EXTENSION));
+    match fs::create_dir_all(EXPORT_DIR).and_then(|()| fs::write(&path, text + "\n")) {
+        Ok(()) => info!("Saved the replay to {:?}", path),
+        Err(err) => warn!("couldn't save the replay to {:?}: {}", path, err),
+    }
+}
+
+/// Sets every cell to its state on `board`, leaving the cells that already
+/// match alone so only the ones that changed pop.
+fn show_board(board: &Grid, cells: &mut Query<(&mut Cell, &GridComponent)>) {
+    for (mut cell, location) in cells {
+        let state = board.get(location.row, location.col);
+        if cell.0 != state {
+            cell.0 = state;
+        }
+    }
+}
+
+fn show_replay_start(viewer: Res<ReplayViewer>, mut cells: Query<(&mut Cell, &GridComponent)>) {
+    show_board(&viewer.board, &mut cells);
+}
+
+/// Puts the player's own board back once the viewer is closed.
+fn close_replay(
+    mut commands: Commands,
+    puzzle: Res<Puzzle>,
+    mut cells: Query<(&mut Cell, &GridComponent)>,
+) {
+    show_board(&puzzle.game_grid, &mut cells);
+    commands.remove_resource::<ReplayViewer>();
+}
+
+/// Plays and pauses the replay, steps through it, jumps to either end,
+/// changes the speed, and closes the viewer.
+fn replay_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    actions: Res<Actions>,
+    mut viewer: ResMut<ReplayViewer>,
+    mut cells: Query<(&mut Cell, &GridComponent)>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F6) || actions.triggered(Action::Quit) {
+        next_state.set(viewer.previous.clone());
+        return;
+    }
+    let moved = if keyboard_input.just_pressed(KeyCode::Space) {
+        viewer.playing = !viewer.playing;
+        viewer.waited = 0.0;
+        // playing from the end starts over
+        if viewer.playing && viewer.next_pause().is_none() {
+            while viewer.step_back() {}
+            true
+        } else {
+            false
+        }
+    } else if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+        viewer.playing = false;
+        viewer.step_forward()
+    } else if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+        viewer.playing = false;
+        viewer.step_back()
+    } else if keyboard_input.just_pressed(KeyCode::Home) {
+        viewer.playing = false;
+        while viewer.step_back() {}
+        true
+    } else if keyboard_input.just_pressed(KeyCode::End) {
+        viewer.playing = false;
+        while viewer.step_forward() {}
+        true
+    } else {
+        if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+            viewer.speed = (viewer.speed * 2.0).min(MAX_REPLAY_SPEED);
+        } else if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+            viewer.speed = (viewer.speed / 2.0).max(MIN_REPLAY_SPEED);
+        }
+        false
+    };
+    if moved {
+        show_board(&viewer.board, &mut cells);
+    }
+}
+
+/// Puts the moves on the board as they come due while the replay plays,
+/// keeping the time between them as it was in the solve.
+fn play_replay(
+    time: Res<Time>,
+    mut viewer: ResMut<ReplayViewer>,
+    mut cells: Query<(&mut Cell, &GridComponent)>,
+) {
+    if !viewer.playing {
+        return;
+    }
+    viewer.waited += time.delta_seconds() * viewer.speed;
+    let mut moved = false;
+    loop {
+        let Some(pause) = viewer.next_pause() else {
+            viewer.playing = false;
+            break;
+        };
+        if viewer.waited < pause {
+            break;
+        }
+        viewer.waited -= pause;
+        moved |= viewer.step_forward();
+    }
+    if moved {
+        show_board(&viewer.board, &mut cells);
+    }
+}
+
+fn update_replay_text(
+    viewer: Res<ReplayViewer>,
+    mut replay_text: Query<&mut Text, With<ReplayText>>,
+) {
+    let status = format!(
+        "Replay    move {}/{}    {} of {}    {}x{}\n{}",
+        viewer.position,
+        viewer.replay.moves.len(),
+        progress::format_time(viewer.seconds()),
+        progress::format_time(viewer.replay.seconds()),
+        viewer.speed,
+        if viewer.playing { "" } else { "    paused" },
+        REPLAY_KEYS
+    );
+    let mut text = replay_text.single_mut();
+    if text.sections[0].value != status {
+        text.sections[0].value = status;
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use nurikabe_core::{code, replay::Replay};
+
+use crate::{profile, storage};
+
+const REPLAYS_FILE: &str = "replays.txt";
+/// Bumped whenever the layout of the replays file changes. Files with a
+/// different version are ignored rather than misread.
+const REPLAYS_VERSION: u32 = 1;
+
+/// The latest solve of each puzzle the player has finished, keyed by the
+/// share code of the clues like `SavedGames`, and saved to disk.
+#[derive(Resource, Default)]
+pub struct Replays {
+    /// The marks the solve started from, then its moves as written by
+    /// `Replay::moves_string`.
+    solves: BTreeMap<String, (String, String)>,
+}
+
+impl Replays {
+    /// Reads the replays file: a `version` line, then one line per puzzle
+    /// holding its code, the starting marks as written by
+    /// `Grid::marks_string` and the moves.
+    pub fn load() -> Self {
+        let Ok(contents) = storage::read(profile::path(REPLAYS_FILE)) else {
+            return Replays::default();
+        };
+        let mut lines = contents.lines();
+        let version = lines
+            .next()
+            .and_then(|line| line.strip_prefix("version "))
+            .and_then(|version| version.trim().parse::<u32>().ok());
+        if version != Some(REPLAYS_VERSION) {
+            warn!(
+                "ignoring replays with version {:?}, expected {}",
+                version, REPLAYS_VERSION
+            );
+            return Replays::default();
+        }
+        let solves = lines
+            .filter_map(|line| {
+                let mut parts = line.split(' ');
+                let code = parts.next()?.to_string();
+                let marks = parts.next()?.to_string();
+                let moves = parts.next().unwrap_or_default().to_string();
+                Some((code, (marks, moves)))
+            })
+            .collect();
+        Replays { solves }
+    }
+
+    /// The last recorded solve of the puzzle with share code `code`.
+    pub fn get(&self, code: &str) -> Option<Replay> {
+        let (marks, moves) = self.solves.get(code)?;
+        let mut start = code::decode(code).ok()?;
+        if !start.apply_marks(marks) {
+            warn!("the saved replay doesn't fit the puzzle, ignoring it");
+            return None;
+        }
+        let replay = Replay::from_moves_string(&start, moves);
+        if replay.is_none() {
+            warn!("the saved replay has a move that can't be read, ignoring it");
+        }
+        replay
+    }
+
+    /// Keeps `replay` as the solve of its puzzle, replacing the one before,
+    /// and writes the replays to disk.
+    pub fn store(&mut self, replay: &Replay) {
+        self.solves.insert(
+            code::encode(&replay.start),
+            (replay.start.marks_string(), replay.moves_string()),
+        );
+        self.save();
+    }
+
+    fn save(&self) {
+        let mut contents = format!("version {}\n", REPLAYS_VERSION);
+        for (code, (marks, moves)) in &self.solves {
+            contents.push_str(&format!("{} {} {}\n", code, marks, moves));
+        }
+        let path = profile::path(REPLAYS_FILE);
+        if let Err(err) = storage::write(path, &contents) {
+            warn!("failed to save replays: {}", err);
+        }
+    }
+}
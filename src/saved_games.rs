@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use nurikabe_core::{code, grid::Grid};
+
+use crate::{profile, storage};
+
+const SAVED_GAMES_FILE: &str = "in_progress.txt";
+/// Bumped whenever the layout of the saved games file changes. Files with a
+/// different version are ignored rather than misread, apart from version 1,
+/// which is version 2 without the locks.
+const SAVED_GAMES_VERSION: u32 = 2;
+
+/// The marks on puzzles the player left unfinished, keyed by the share code
+/// of the clues so a puzzle is recognized wherever it was loaded from, and
+/// saved to disk.
+#[derive(Resource, Default)]
+pub struct SavedGames {
+    /// The marks on each board, followed by its locks when it has any.
+    boards: BTreeMap<String, String>,
+}
+
+impl SavedGames {
+    /// Reads the saved games file: a `version` line, then one line per
+    /// puzzle holding its code, its marks as written by `Grid::marks_string`
+    /// and, if any cells are locked, its locks as written by
+    /// `Grid::locks_string`.
+    pub fn load() -> Self {
+        let Ok(contents) = storage::read(profile::path(SAVED_GAMES_FILE)) else {
+            return SavedGames::default();
+        };
+        let mut lines = contents.lines();
+        let version = lines
+            .next()
+            .and_then(|line| line.strip_prefix("version "))
+            .and_then(|version| version.trim().parse::<u32>().ok());
+        if !matches!(version, Some(1 | SAVED_GAMES_VERSION)) {
+            warn!(
+                "ignoring saved games with version {:?}, expected {}",
+                version, SAVED_GAMES_VERSION
+            );
+            return SavedGames::default();
+        }
+        let boards = lines
+            .filter_map(|line| line.split_once(' '))
+            .map(|(code, marks)| (code.to_string(), marks.to_string()))
+            .collect();
+        SavedGames { boards }
+    }
+
+    /// Fills in the marks and locks saved for the puzzle in `grid`, unless
+    /// the grid already has marks of its own.
+    pub fn restore(&self, grid: &mut Grid) {
+        if grid.has_marks() {
+            return;
+        }
+        let Some(board) = self.boards.get(&code::encode(grid)) else {
+            return;
+        };
+        let (marks, locks) = match board.split_once(' ') {
+            Some((marks, locks)) => (marks, Some(locks)),
+            None => (board.as_str(), None),
+        };
+        if !grid.apply_marks(marks) {
+            warn!("the saved marks don't fit the puzzle, ignoring them");
+        } else if locks.is_some_and(|locks| !grid.apply_locks(locks)) {
+            warn!("the saved locks don't fit the puzzle, ignoring them");
+        }
+    }
+
+    /// Remembers the marks and locks on `grid` and writes them to disk. Boards with no
+    /// marks or that are already solved are forgotten instead.
+    pub fn store(&mut self, grid: &Grid) {
+        let code = code::encode(grid);
+        let changed = if grid.has_marks() && !grid.validate_rules() {
+            let mut board = grid.marks_string();
+            if grid.has_locks() {
+                board = format!("{} {}", board, grid.locks_string());
+            }
+            self.boards.insert(code, board.clone()) != Some(board)
+        } else {
+            self.boards.remove(&code).is_some()
+        };
+        if changed {
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        let mut contents = format!("version {}\n", SAVED_GAMES_VERSION);
+        for (code, board) in &self.boards {
+            contents.push_str(&format!("{} {}\n", code, board));
+        }
+        let path = profile::path(SAVED_GAMES_FILE);
+        if let Err(err) = storage::write(path, &contents) {
+            warn!("failed to save unfinished puzzles: {}", err);
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use std::fs;
+
+use bevy::prelude::*;
+
+use crate::{profile, storage, theme::ThemeName};
+
+const SETTINGS_FILE: &str = "settings.txt";
+const KEY_REPEAT_DELAY_SECONDS: f32 = 0.4;
+const KEY_REPEAT_INTERVAL_SECONDS: f32 = 0.08;
+pub const DEFAULT_CELL_SIZE: f32 = 60.0;
+/// Where players can drop tile sheets of their own, laid out like the
+/// built-in `tile_sheet.png`.
+const SKINS_DIR: &str = "./assets/skins";
+
+/// Player preferences, saved to disk when changed.
+#[derive(Resource, Clone, PartialEq, Debug)]
+pub struct Settings {
+    pub theme: ThemeName,
+    /// Whether island and river tiles are drawn with patterns as well as
+    /// shades, so they can be told apart without relying on color.
+    pub patterned_tiles: bool,
+    /// Whether the board is drawn in black, white and bright colors with
+    /// thick outlines and a bigger cursor, whatever the theme.
+    pub high_contrast: bool,
+    /// The file name of the tile sheet in `assets/skins` the cells are drawn
+    /// from, or `None` for the built-in tiles.
+    pub skin: Option<String>,
+    /// Whether moving the cursor off one edge of the grid brings it back on
+    /// the opposite edge, instead of stopping at the edge.
+    pub wrap_cursor: bool,
+    /// Whether h/j/k/l move the cursor, with an optional count in front. The
+    /// hint moves from H to `?` while these are on.
+    pub vim_keys: bool,
+    /// How long a movement key has to be held before the cursor starts
+    /// moving on its own.
+    pub key_repeat_delay: f32,
+    /// The time between cursor moves once a held key starts repeating.
+    pub key_repeat_interval: f32,
+    /// The width and height of a cell on screen.
+    pub cell_size: f32,
+    /// How big the text around the board is drawn, as a fraction of its
+    /// usual size.
+    pub ui_scale: f32,
+    /// Whether cells pop when they change, instead of switching instantly,
+    /// and confetti flies when a puzzle is solved.
+    pub animations: bool,
+    /// How loud the game is overall, from 0 to 1.
+    pub master_volume: f32,
+    /// How loud the sound effects are, from 0 to 1, on top of the master
+    /// volume.
+    pub sfx_volume: f32,
+    /// How loud the background music is, from 0 to 1, on top of the master
+    /// volume.
+    pub music_volume: f32,
+    /// Whether the background music is silenced, keeping its volume for when
+    /// it is turned back on.
+    pub music_muted: bool,
+    /// Whether the player's marks survive when the puzzle file changes on
+    /// disk and is reloaded, instead of starting over.
+    pub keep_marks_on_reload: bool,
+    /// Whether the time spent on the puzzle is shown in the corner.
+    pub show_timer: bool,
+    /// Whether marks that don't match the solution are flashed and counted
+    /// as mistakes.
+    pub assist_mode: bool,
+    /// Whether the blank cells around an island are turned to river as soon
+    /// as the island reaches the size of its clue.
+    pub auto_surround: bool,
+    /// Whether the sea that follows from the clues alone is filled in when a
+    /// puzzle starts.
+    pub prefill_sea: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            theme: ThemeName::Dark,
+            patterned_tiles: false,
+            high_contrast: false,
+            skin: None,
+            wrap_cursor: false,
+            vim_keys: false,
+            key_repeat_delay: KEY_REPEAT_DELAY_SECONDS,
+            key_repeat_interval: KEY_REPEAT_INTERVAL_SECONDS,
+            cell_size: DEFAULT_CELL_SIZE,
+            ui_scale: 1.0,
+            animations: true,
+            master_volume: 1.0,
+            sfx_volume: 0.5,
+            music_volume: 0.5,
+            music_muted: false,
+            keep_marks_on_reload: true,
+            show_timer: true,
+            assist_mode: false,
+            auto_surround: false,
+            prefill_sea: false,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings file, one `name=value` line per setting. Settings
+    /// that are missing or can't be read keep their defaults.
+    pub fn load() -> Self {
+        let mut settings = Settings::default();
+        let Ok(contents) = storage::read(profile::path(SETTINGS_FILE)) else {
+            return settings;
+        };
+        for line in contents.lines() {
+            let Some((name, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            let ok = match name.trim() {
+                "theme" => value.parse().map(|v| settings.theme = v).is_ok(),
+                "wrap_cursor" => value.parse().map(|v| settings.wrap_cursor = v).is_ok(),
+                "vim_keys" => value.parse().map(|v| settings.vim_keys = v).is_ok(),
+                "key_repeat_delay" => value.parse().map(|v| settings.key_repeat_delay = v).is_ok(),
+                "key_repeat_interval" => value
+                    .parse()
+                    .map(|v| settings.key_repeat_interval = v)
+                    .is_ok(),
+                "cell_size" => value.parse().map(|v| settings.cell_size = v).is_ok(),
+                "ui_scale" => value.parse().map(|v| settings.ui_scale = v).is_ok(),
+                "animations" => value.parse().map(|v| settings.animations = v).is_ok(),
+                "master_volume" => value.parse().map(|v| settings.master_volume = v).is_ok(),
+                "sfx_volume" => value.parse().map(|v| settings.sfx_volume = v).is_ok(),
+                "music_volume" => value.parse().map(|v| settings.music_volume = v).is_ok(),
+                "music_muted" => value.parse().map(|v| settings.music_muted = v).is_ok(),
+                "keep_marks_on_reload" => value
+                    .parse()
+                    .map(|v| settings.keep_marks_on_reload = v)
+                    .is_ok(),
+                "show_timer" => value.parse().map(|v| settings.show_timer = v).is_ok(),
+                "assist_mode" => value.parse().map(|v| settings.assist_mode = v).is_ok(),
+                "auto_surround" => value.parse().map(|v| settings.auto_surround = v).is_ok(),
+                "prefill_sea" => value.parse().map(|v| settings.prefill_sea = v).is_ok(),
+                "patterned_tiles" => value.parse().map(|v| settings.patterned_tiles = v).is_ok(),
+                "high_contrast" => value.parse().map(|v| settings.high_contrast = v).is_ok(),
+                "skin" => {
+                    settings.skin = (!value.is_empty()).then(|| value.to_string());
+                    true
+                }
+                _ => false,
+            };
+            if !ok {
+                warn!("ignoring setting {:?}", line);
+            }
+        }
+        settings
+    }
+
+    pub fn save(&self) {
+        let contents = format!(
+            "theme={}\nwrap_cursor={}\nvim_keys={}\nkey_repeat_delay={}\nkey_repeat_interval={}\ncell_size={}\nkeep_marks_on_reload={}\nshow_timer={}\nassist_mode={}\nauto_surround={}\nprefill_sea={}\npatterned_tiles={}\nhigh_contrast={}\nskin={}\nui_scale={}\nanimations={}\nmaster_volume={}\nsfx_volume={}\nmusic_volume={}\nmusic_muted={}\n",
+            self.theme,
+            self.wrap_cursor,
+            self.vim_keys,
+            self.key_repeat_delay,
+            self.key_repeat_interval,
+            self.cell_size,
+            self.keep_marks_on_reload,
+            self.show_timer,
+            self.assist_mode,
+            self.auto_surround,
+            self.prefill_sea,
+            self.patterned_tiles,
+            self.high_contrast,
+            self.skin.as_deref().unwrap_or_default(),
+            self.ui_scale,
+            self.animations,
+            self.master_volume,
+            self.sfx_volume,
+            self.music_volume,
+            self.music_muted,
+        );
+        let path = profile::path(SETTINGS_FILE);
+        if let Err(err) = storage::write(path, &contents) {
+            warn!("failed to save settings: {}", err);
+        }
+    }
+}
+
+/// A setting that can be changed on the settings page.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Setting {
+    Theme,
+    PatternedTiles,
+    HighContrast,
+    Skin,
+    WrapCursor,
+    VimKeys,
+    KeyRepeatDelay,
+    KeyRepeatInterval,
+    CellSize,
+    UiScale,
+    Animations,
+    MasterVolume,
+    SfxVolume,
+    MusicVolume,
+    MuteMusic,
+    KeepMarksOnReload,
+    ShowTimer,
+    AssistMode,
+    AutoSurround,
+    PrefillSea,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 20] = [
+        Setting::Theme,
+        Setting::PatternedTiles,
+        Setting::HighContrast,
+        Setting::Skin,
+        Setting::WrapCursor,
+        Setting::VimKeys,
+        Setting::KeyRepeatDelay,
+        Setting::KeyRepeatInterval,
+        Setting::CellSize,
+        Setting::UiScale,
+        Setting::Animations,
+        Setting::MasterVolume,
+        Setting::SfxVolume,
+        Setting::MusicVolume,
+        Setting::MuteMusic,
+        Setting::KeepMarksOnReload,
+        Setting::ShowTimer,
+        Setting::AssistMode,
+        Setting::AutoSurround,
+        Setting::PrefillSea,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Setting::Theme => "Theme",
+            Setting::PatternedTiles => "Patterned tiles",
+            Setting::HighContrast => "High contrast",
+            Setting::Skin => "Tile skin",
+            Setting::WrapCursor => "Wrap cursor",
+            Setting::VimKeys => "Vim keys",
+            Setting::KeyRepeatDelay => "Key repeat delay",
+            Setting::KeyRepeatInterval => "Key repeat interval",
+            Setting::CellSize => "Cell size",
+            Setting::UiScale => "Text size",
+            Setting::Animations => "Animations",
+            Setting::MasterVolume => "Master volume",
+            Setting::SfxVolume => "Sound effects",
+            Setting::MusicVolume => "Music volume",
+            Setting::MuteMusic => "Mute music",
+            Setting::KeepMarksOnReload => "Keep marks on reload",
+            Setting::ShowTimer => "Show timer",
+            Setting::AssistMode => "Assist mode",
+            Setting::AutoSurround => "Surround finished islands",
+            Setting::PrefillSea => "Fill in obvious sea",
+        }
+    }
+
+    pub fn value(&self, settings: &Settings) -> String {
+        let on_off = |on| if on { "On" } else { "Off" }.to_string();
+        match self {
+            Setting::Theme => settings.theme.to_string(),
+            Setting::PatternedTiles => on_off(settings.patterned_tiles),
+            Setting::HighContrast => on_off(settings.high_contrast),
+            Setting::Skin => settings
+                .skin
+                .clone()
+                .unwrap_or_else(|| "Built-in".to_string()),
+            Setting::WrapCursor => on_off(settings.wrap_cursor),
+            Setting::VimKeys => on_off(settings.vim_keys),
+            Setting::KeyRepeatDelay => format!("{:.2} s", settings.key_repeat_delay),
+            Setting::KeyRepeatInterval => format!("{:.2} s", settings.key_repeat_interval),
+            Setting::CellSize => format!("{}", settings.cell_size),
+            Setting::UiScale => format!("{:.0}%", settings.ui_scale * 100.0),
+            Setting::Animations => on_off(settings.animations),
+            Setting::MasterVolume => format!("{:.0}%", settings.master_volume * 100.0),
+            Setting::SfxVolume => format!("{:.0}%", settings.sfx_volume * 100.0),
+            Setting::MusicVolume => format!("{:.0}%", settings.music_volume * 100.0),
+            Setting::MuteMusic => on_off(settings.music_muted),
+            Setting::KeepMarksOnReload => on_off(settings.keep_marks_on_reload),
+            Setting::ShowTimer => on_off(settings.show_timer),
+            Setting::AssistMode => on_off(settings.assist_mode),
+            Setting::AutoSurround => on_off(settings.auto_surround),
+            Setting::PrefillSea => on_off(settings.prefill_sea),
+        }
+    }
+
+    /// Steps the setting up (`steps > 0`) or down. Switches flip either way.
+    pub fn adjust(&self, settings: &mut Settings, steps: i32) {
+        let steps = steps as f32;
+        match self {
+            Setting::Theme => {
+                settings.theme = match settings.theme {
+                    ThemeName::Dark => ThemeName::Light,
+                    ThemeName::Light => ThemeName::Dark,
+                }
+            }
+            Setting::WrapCursor => settings.wrap_cursor = !settings.wrap_cursor,
+            Setting::VimKeys => settings.vim_keys = !settings.vim_keys,
+            Setting::KeyRepeatDelay => {
+                settings.key_repeat_delay =
+                    (settings.key_repeat_delay + steps * 0.05).clamp(0.1, 1.0)
+            }
+            Setting::KeyRepeatInterval => {
+                settings.key_repeat_interval =
+                    (settings.key_repeat_interval + steps * 0.01).clamp(0.02, 0.5)
+            }
+            Setting::CellSize => {
+                settings.cell_size = (settings.cell_size + steps * 5.0).clamp(30.0, 100.0)
+            }
+            Setting::UiScale => {
+                settings.ui_scale = (settings.ui_scale + steps * 0.25).clamp(0.75, 2.0)
+            }
+            Setting::Animations => settings.animations = !settings.animations,
+            Setting::MasterVolume => {
+                settings.master_volume = step_volume(settings.master_volume, steps)
+            }
+            Setting::SfxVolume => settings.sfx_volume = step_volume(settings.sfx_volume, steps),
+            Setting::MusicVolume => {
+                settings.music_volume = step_volume(settings.music_volume, steps)
+            }
+            Setting::MuteMusic => settings.music_muted = !settings.music_muted,
+            Setting::KeepMarksOnReload => {
+                settings.keep_marks_on_reload = !settings.keep_marks_on_reload
+            }
+            Setting::ShowTimer => settings.show_timer = !settings.show_timer,
+            Setting::AssistMode => settings.assist_mode = !settings.assist_mode,
+            Setting::AutoSurround => settings.auto_surround = !settings.auto_surround,
+            Setting::PrefillSea => settings.prefill_sea = !settings.prefill_sea,
+            Setting::PatternedTiles => settings.patterned_tiles = !settings.patterned_tiles,
+            Setting::HighContrast => settings.high_contrast = !settings.high_contrast,
+            Setting::Skin => {
+                // the built-in tiles come first, then every skin on disk
+                let mut skins = vec![None];
+                skins.extend(available_skins().into_iter().map(Some));
+                let current = skins
+                    .iter()
+                    .position(|skin| *skin == settings.skin)
+                    .unwrap_or(0);
+                let next = (current as i32 + steps as i32).rem_euclid(skins.len() as i32);
+                settings.skin = skins.swap_remove(next as usize);
+            }
+        }
+    }
+}
+
+/// Moves a volume between 0 and 1 by tenths, rounded so the steps always
+/// land on whole tens of percent.
+fn step_volume(volume: f32, steps: f32) -> f32 {
+    ((volume + steps * 0.1) * 10.0).round().clamp(0.0, 10.0) / 10.0
+}
+
+/// The file names of the tile sheets in `assets/skins`, in alphabetical
+/// order.
+pub fn available_skins() -> Vec<String> {
+    let Ok(files) = fs::read_dir(SKINS_DIR) else {
+        return Vec::new();
+    };
+    let mut skins: Vec<String> = files
+        .flatten()
+        .map(|file| file.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "png"))
+        .filter_map(|path| Some(path.file_name()?.to_str()?.to_string()))
+        .collect();
+    skins.sort();
+    skins
+}
@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use nurikabe_core::{code, grid::Grid};
+
+use crate::{profile, storage};
+
+const STATS_FILE: &str = "stats.txt";
+
+/// What the player has done on one puzzle, or on every puzzle added up.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct PuzzleStats {
+    pub solves: u32,
+    /// The time taken by every solve added up, in seconds.
+    pub solve_time: f32,
+    /// The time spent on the board, solved or not, in seconds.
+    pub play_time: f32,
+    pub hints: u32,
+    pub resets: u32,
+}
+
+impl PuzzleStats {
+    pub fn average_solve_time(&self) -> Option<f32> {
+        (self.solves > 0).then(|| self.solve_time / self.solves as f32)
+    }
+
+    fn add(&mut self, other: &PuzzleStats) {
+        self.solves += other.solves;
+        self.solve_time += other.solve_time;
+        self.play_time += other.play_time;
+        self.hints += other.hints;
+        self.resets += other.resets;
+    }
+}
+
+/// The player's stats on every puzzle they've played, keyed by the share
+/// code of the clues like `SavedGames`, and saved to disk.
+#[derive(Resource, Default)]
+pub struct Stats {
+    puzzles: BTreeMap<String, PuzzleStats>,
+}
+
+impl Stats {
+    /// Reads the stats file, one line per puzzle holding its code and then
+    /// each number in `PuzzleStats` separated by spaces.
+    pub fn load() -> Self {
+        let Ok(contents) = storage::read(profile::path(STATS_FILE)) else {
+            return Stats::default();
+        };
+        let mut puzzles = BTreeMap::new();
+        for line in contents.lines() {
+            let mut fields = line.split_whitespace();
+            let Some(code) = fields.next() else {
+                continue;
+            };
+            let numbers: Option<Vec<f32>> = fields.map(|field| field.parse().ok()).collect();
+            let stats = match numbers.as_deref() {
+                Some(&[solves, solve_time, play_time, hints, resets]) => Some(PuzzleStats {
+                    solves: solves as u32,
+                    solve_time,
+                    play_time,
+                    hints: hints as u32,
+                    resets: resets as u32,
+                }),
+                _ => None,
+            };
+            match stats {
+                Some(stats) => {
+                    puzzles.insert(code.to_string(), stats);
+                }
+                None => warn!("ignoring stats line {:?}", line),
+            }
+        }
+        Stats { puzzles }
+    }
+
+    /// The stats on the puzzle in `grid`.
+    pub fn puzzle(&self, grid: &Grid) -> PuzzleStats {
+        self.puzzles
+            .get(&code::encode(grid))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The stats on every puzzle added up.
+    pub fn total(&self) -> PuzzleStats {
+        let mut total = PuzzleStats::default();
+        for stats in self.puzzles.values() {
+            total.add(stats);
+        }
+        total
+    }
+
+    /// The number of different puzzles solved at least once.
+    pub fn puzzles_solved(&self) -> usize {
+        self.puzzles
+            .values()
+            .filter(|stats| stats.solves > 0)
+            .count()
+    }
+
+    pub fn record_solve(&mut self, grid: &Grid, seconds: f32) {
+        self.update(grid, |stats| {
+            stats.solves += 1;
+            stats.solve_time += seconds;
+        });
+    }
+
+    pub fn record_play_time(&mut self, grid: &Grid, seconds: f32) {
+        if seconds > 0.0 {
+            self.update(grid, |stats| stats.play_time += seconds);
+        }
+    }
+
+    pub fn record_hint(&mut self, grid: &Grid) {
+        self.update(grid, |stats| stats.hints += 1);
+    }
+
+    pub fn record_reset(&mut self, grid: &Grid) {
+        self.update(grid, |stats| stats.resets += 1);
+    }
+
+    /// Changes the stats on the puzzle in `grid` and writes them to disk.
+    fn update(&mut self, grid: &Grid, change: impl FnOnce(&mut PuzzleStats)) {
+        change(self.puzzles.entry(code::encode(grid)).or_default());
+        self.save();
+    }
+
+    fn save(&self) {
+        let contents: String = self
+            .puzzles
+            .iter()
+            .map(|(code, stats)| {
+                format!(
+                    "{} {} {} {} {} {}\n",
+                    code,
+                    stats.solves,
+                    stats.solve_time,
+                    stats.play_time,
+                    stats.hints,
+                    stats.resets
+                )
+            })
+            .collect();
+        let path = profile::path(STATS_FILE);
+        if let Err(err) = storage::write(path, &contents) {
+            warn!("failed to save stats: {}", err);
+        }
+    }
+}
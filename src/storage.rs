@@ -0,0 +1,87 @@
+//! Where the save files live. The desktop build keeps them as files under
+//! `saves`, while the web build keeps them in the browser's local storage
+//! under the same paths.
+
+use std::{io, path::Path};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+
+/// A place the game can keep its save files, each named by a path.
+pub trait Storage: Sync {
+    /// Reads the file at `path`, failing with `NotFound` if there is none.
+    fn read(&self, path: &Path) -> io::Result<String>;
+
+    /// Writes `contents` to the file at `path`, replacing what was there.
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()>;
+}
+
+/// Keeps the save files on disk, making their folders as needed.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct FileStorage;
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Storage for FileStorage {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        fs::write(path, contents)
+    }
+}
+
+/// Keeps the save files in the browser's local storage, with each path as
+/// the key.
+#[cfg(target_arch = "wasm32")]
+pub struct LocalStorage;
+
+#[cfg(target_arch = "wasm32")]
+impl LocalStorage {
+    fn storage() -> io::Result<web_sys::Storage> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| io::Error::other("local storage isn't available"))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Storage for LocalStorage {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        LocalStorage::storage()?
+            .get_item(&path.to_string_lossy())
+            .map_err(|err| io::Error::other(format!("{:?}", err)))?
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        LocalStorage::storage()?
+            .set_item(&path.to_string_lossy(), contents)
+            .map_err(|err| io::Error::other(format!("{:?}", err)))
+    }
+}
+
+/// The storage for the platform the game is running on.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn storage() -> &'static dyn Storage {
+    &FileStorage
+}
+
+/// The storage for the platform the game is running on.
+#[cfg(target_arch = "wasm32")]
+pub fn storage() -> &'static dyn Storage {
+    &LocalStorage
+}
+
+/// Reads the save file at `path`.
+pub fn read(path: impl AsRef<Path>) -> io::Result<String> {
+    storage().read(path.as_ref())
+}
+
+/// Writes the save file at `path`.
+pub fn write(path: impl AsRef<Path>, contents: &str) -> io::Result<()> {
+    storage().write(path.as_ref(), contents)
+}
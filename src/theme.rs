@@ -0,0 +1,102 @@
+use core::fmt;
+use std::str::FromStr;
+
+use bevy::prelude::*;
+
+use crate::settings::Settings;
+
+/// The built-in themes the player can pick between in the settings.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ThemeName {
+    Dark,
+    Light,
+}
+
+impl fmt::Display for ThemeName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ThemeName::Dark => "Dark",
+            ThemeName::Light => "Light",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ThemeName {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Dark" => Ok(ThemeName::Dark),
+            "Light" => Ok(ThemeName::Light),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The colors the game is drawn in, kept in step with the theme picked in
+/// the settings.
+#[derive(Resource, Clone, Copy, PartialEq, Debug)]
+pub struct Theme {
+    pub background: Color,
+    /// Tints the tile sheet, which is drawn in black and white.
+    pub tile: Color,
+    /// The text drawn straight on the background.
+    pub text: Color,
+    pub cursor: Color,
+    /// How much bigger than a cell the cursor is drawn.
+    pub cursor_scale: f32,
+    /// The border drawn around every cell, or `Color::NONE` for none.
+    pub outline: Color,
+    /// Whether the cells use the patterned tile sheet, where island and
+    /// river differ in pattern as well as shade.
+    pub patterned_tiles: bool,
+}
+
+impl Theme {
+    pub const DARK: Theme = Theme {
+        background: Color::srgb(0.17, 0.17, 0.17),
+        tile: Color::srgb(0.85, 0.85, 0.85),
+        text: Color::WHITE,
+        cursor: Color::srgb(0.14, 0.58, 0.74),
+        cursor_scale: 1.0,
+        outline: Color::NONE,
+        patterned_tiles: false,
+    };
+
+    pub const LIGHT: Theme = Theme {
+        background: Color::srgb(0.93, 0.93, 0.9),
+        tile: Color::WHITE,
+        text: Color::BLACK,
+        cursor: Color::srgb(0.1, 0.35, 0.7),
+        cursor_scale: 1.0,
+        outline: Color::NONE,
+        patterned_tiles: false,
+    };
+
+    /// Pure black and white tiles on black, with thick yellow outlines and a
+    /// big magenta cursor, for players who have trouble seeing the board.
+    pub const HIGH_CONTRAST: Theme = Theme {
+        background: Color::BLACK,
+        tile: Color::WHITE,
+        text: Color::WHITE,
+        cursor: Color::srgb(1.0, 0.0, 1.0),
+        cursor_scale: 1.3,
+        outline: Color::srgb(1.0, 1.0, 0.0),
+        patterned_tiles: false,
+    };
+
+    /// The theme the settings ask for. High contrast mode takes the place of
+    /// the picked theme.
+    pub fn new(settings: &Settings) -> Theme {
+        let theme = match settings.theme {
+            _ if settings.high_contrast => Theme::HIGH_CONTRAST,
+            ThemeName::Dark => Theme::DARK,
+            ThemeName::Light => Theme::LIGHT,
+        };
+        Theme {
+            patterned_tiles: settings.patterned_tiles,
+            ..theme
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use bevy::prelude::*;
+use nurikabe_core::{format, grid::Grid, CellState};
+
+/// The puzzle the tutorial is played on, a small one with a single solution.
+const TUTORIAL_PUZZLE: &str = "2----\n---3-\n-1---\n-----\n2----\n";
+
+/// What the player has to do to get past a step of the tutorial.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Goal {
+    /// Read the text and press enter.
+    Continue,
+    /// Move the cursor onto the cell.
+    MoveTo { row: usize, col: usize },
+    /// Mark the cell. Nothing else on the board can be changed meanwhile.
+    Mark {
+        row: usize,
+        col: usize,
+        state: CellState,
+    },
+    /// Finish the puzzle without any help from the tutorial.
+    Solve,
+    /// The tutorial is over and only the closing words are left.
+    Done,
+}
+
+pub struct Step {
+    pub text: &'static str,
+    pub goal: Goal,
+}
+
+const STEPS: [Step; 9] = [
+    Step {
+        text: "Welcome to Nurikabe! Every number on the board is part of an island, \
+               and says how many cells the island has. Press Enter to go on.",
+        goal: Goal::Continue,
+    },
+    Step {
+        text: "Move the cursor onto the highlighted 1 with the arrow keys or WASD.",
+        goal: Goal::MoveTo { row: 2, col: 1 },
+    },
+    Step {
+        text: "An island of 1 is already finished, so every cell touching it belongs \
+               to the sea. Press Enter to go on.",
+        goal: Goal::Continue,
+    },
+    Step {
+        text: "Press space to mark the highlighted cell as sea.",
+        goal: Goal::Mark {
+            row: 2,
+            col: 2,
+            state: CellState::River,
+        },
+    },
+    Step {
+        text: "This 2 still needs one more cell. Press I or right-click to mark the \
+               highlighted cell as island.",
+        goal: Goal::Mark {
+            row: 0,
+            col: 1,
+            state: CellState::Island,
+        },
+    },
+    Step {
+        text: "Every island has exactly one number, and islands can't touch each other \
+               except at the corners. Press Enter to go on.",
+        goal: Goal::Continue,
+    },
+    Step {
+        text: "All of the sea has to join up into one, and it can't have a 2x2 block of \
+               sea anywhere. Press Enter to go on.",
+        goal: Goal::Continue,
+    },
+    Step {
+        text: "Now finish the puzzle on your own. Z undoes a mark, and H gives a hint \
+               if you get stuck.",
+        goal: Goal::Solve,
+    },
+    Step {
+        text: "Well done, that's the whole of Nurikabe! Press Enter for another puzzle.",
+        goal: Goal::Done,
+    },
+];
+
+/// How far the player has got through the tutorial, if it is running.
+#[derive(Resource, Default)]
+pub struct Tutorial {
+    step: Option<usize>,
+}
+
+impl Tutorial {
+    pub fn start(&mut self) {
+        self.step = Some(0);
+    }
+
+    pub fn stop(&mut self) {
+        self.step = None;
+    }
+
+    pub fn step(&self) -> Option<&'static Step> {
+        STEPS.get(self.step?)
+    }
+
+    pub fn advance(&mut self) {
+        if let Some(step) = &mut self.step {
+            *step = (*step + 1).min(STEPS.len() - 1);
+        }
+    }
+
+    /// The cell the current step is about, if it is about one.
+    pub fn target(&self) -> Option<(usize, usize)> {
+        match self.step()?.goal {
+            Goal::MoveTo { row, col } | Goal::Mark { row, col, .. } => Some((row, col)),
+            _ => None,
+        }
+    }
+
+    /// Whether the player may change the cell at `row` and `col` right now.
+    /// Until the tutorial lets go, only the cell it is explaining can be
+    /// changed.
+    pub fn allows(&self, row: usize, col: usize) -> bool {
+        match self.step().map(|step| step.goal) {
+            None | Some(Goal::Solve | Goal::Done) => true,
+            Some(Goal::Mark {
+                row: target_row,
+                col: target_col,
+                ..
+            }) => (row, col) == (target_row, target_col),
+            Some(Goal::Continue | Goal::MoveTo { .. }) => false,
+        }
+    }
+
+    /// Whether the player can use the rest of the game's tools, like undo and
+    /// hints, which would get in the way of a step.
+    pub fn allows_free_play(&self) -> bool {
+        matches!(
+            self.step().map(|step| step.goal),
+            None | Some(Goal::Solve | Goal::Done)
+        )
+    }
+}
+
+pub fn tutorial_puzzle() -> Grid {
+    format::parse_any(TUTORIAL_PUZZLE).expect("the tutorial puzzle is valid")
+}